@@ -78,6 +78,34 @@ where
         autocxx_incs,
         extra_clang_args,
         None,
+        None,
+        dependency_recorder,
+    )
+}
+
+/// Like [build], but also writes a Markdown summary of the bindings
+/// generated by each `include_cpp!` into the given directory, named after
+/// its mod, for example `ffi.md`. Setting the `AUTOCXX_SUMMARY` environment
+/// variable to a directory has the same effect for any build.
+pub fn build_with_summary<P1, P2, I, T>(
+    rs_file: P1,
+    autocxx_incs: I,
+    extra_clang_args: &[&str],
+    summary_dir: P2,
+    dependency_recorder: Option<Box<dyn RebuildDependencyRecorder>>,
+) -> BuilderResult
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+    I: IntoIterator<Item = T>,
+    T: AsRef<OsStr>,
+{
+    build_to_custom_directory(
+        rs_file,
+        autocxx_incs,
+        extra_clang_args,
+        None,
+        Some(summary_dir.as_ref().to_path_buf()),
         dependency_recorder,
     )
 }
@@ -108,6 +136,7 @@ pub(crate) fn build_to_custom_directory<P1, I, T>(
     autocxx_incs: I,
     extra_clang_args: &[&str],
     custom_gendir: Option<PathBuf>,
+    summary_dir: Option<PathBuf>,
     dependency_recorder: Option<Box<dyn RebuildDependencyRecorder>>,
 ) -> BuilderResult
 where
@@ -141,7 +170,9 @@ where
     parsed_file
         .resolve_all(autocxx_inc, extra_clang_args, dependency_recorder)
         .map_err(BuilderError::ParseError)?;
-    build_with_existing_parsed_file(parsed_file, cxxdir, incdir, rsdir)
+    let summary_dir =
+        summary_dir.or_else(|| std::env::var_os("AUTOCXX_SUMMARY").map(PathBuf::from));
+    build_with_existing_parsed_file(parsed_file, cxxdir, incdir, rsdir, summary_dir)
 }
 
 pub(crate) fn build_with_existing_parsed_file(
@@ -149,6 +180,7 @@ pub(crate) fn build_with_existing_parsed_file(
     cxxdir: PathBuf,
     incdir: PathBuf,
     rsdir: PathBuf,
+    summary_dir: Option<PathBuf>,
 ) -> BuilderResult {
     let mut counter = 0;
     let mut builder = cc::Build::new();
@@ -176,6 +208,16 @@ pub(crate) fn build_with_existing_parsed_file(
         let rs = include_cpp.generate_rs();
         generated_rs.push(write_rs_to_file(&rsdir, &fname, rs)?);
     }
+    if let Some(summary_dir) = summary_dir {
+        ensure_created(&summary_dir)?;
+        for include_cpp in parsed_file.get_rs_buildables() {
+            write_to_file(
+                &summary_dir,
+                &include_cpp.get_summary_filename(),
+                include_cpp.generate_summary().as_bytes(),
+            )?;
+        }
+    }
     if counter == 0 {
         Err(BuilderError::NoIncludeCxxMacrosFound)
     } else {
@@ -216,7 +258,7 @@ fn write_rs_to_file(
     filename: &str,
     content: TokenStream,
 ) -> Result<PathBuf, BuilderError> {
-    write_to_file(dir, filename, content.to_string().as_bytes())
+    write_to_file(dir, filename, crate::pretty_print(&content).as_bytes())
 }
 
 fn rust_version_check() {
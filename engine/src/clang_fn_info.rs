@@ -0,0 +1,827 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int, c_uint, c_ulong},
+    ptr,
+    sync::Arc,
+};
+
+use autocxx_parser::Instantiation;
+use clang_sys::*;
+use itertools::Itertools;
+use once_cell::sync::OnceCell;
+use syn::{Attribute, ForeignItem, Ident, Item, ItemMod, Lit, Meta};
+
+use crate::types::QualifiedName;
+
+/// bindgen discards some details of functions, so we ask libclang about
+/// them directly. We record how many trailing parameters of each function
+/// have defaults, whether it's deprecated, and its original C++ signature,
+/// keyed by the mangled name which bindgen puts in `#[link_name]`.
+/// We also record the methods of template instantiations, which bindgen
+/// doesn't tell us about at all.
+#[derive(Default)]
+pub(crate) struct ClangFnInfo {
+    default_args: HashMap<String, usize>,
+    deprecations: HashMap<String, String>,
+    signatures: HashMap<String, String>,
+    /// Keyed by the name of the function we declared for each.
+    instantiated_methods: HashMap<String, InstantiatedMethod>,
+}
+
+impl ClangFnInfo {
+    /// Asks libclang about those functions which bindgen generated,
+    /// that is, those which made it through the allowlist. bindgen
+    /// doesn't give us access to its own translation unit, so we parse
+    /// the same header with the same arguments, but we skip function
+    /// bodies, don't look inside namespaces or classes which can't
+    /// contain any of these functions, and stop as soon as we've found
+    /// them all. bindgen has already loaded libclang by the time we're
+    /// called, and already reported any errors in the header, so if
+    /// anything goes wrong here we simply find nothing.
+    pub(crate) fn find(
+        bindings: &ItemMod,
+        header: &str,
+        filename: &str,
+        clang_args: &[String],
+        instantiated_methods: Vec<InstantiatedMethod>,
+    ) -> Self {
+        let mut wanted = HashSet::new();
+        find_mangled_names(bindings, &mut wanted);
+        let mut results = Self {
+            instantiated_methods: instantiated_methods
+                .into_iter()
+                .map(|method| (method.declaration_name.clone(), method))
+                .collect(),
+            ..Default::default()
+        };
+        if wanted.is_empty() {
+            return results;
+        }
+        parse_header(header, filename, clang_args, 0, |tu| {
+            let mut visitor = Visitor {
+                tu,
+                wanted,
+                results: &mut results,
+            };
+            unsafe {
+                clang_visitChildren(
+                    clang_getTranslationUnitCursor(tu),
+                    visit,
+                    &mut visitor as *mut Visitor as CXClientData,
+                );
+            }
+        });
+        results
+    }
+
+    /// The number of trailing parameters with default values, for a
+    /// function with the given bindgen attributes.
+    pub(crate) fn count_defaults_for(&self, attrs: &[Attribute]) -> usize {
+        get_mangled_name(attrs)
+            .and_then(|mangled| self.default_args.get(&mangled).cloned())
+            .unwrap_or_default()
+    }
+
+    /// If a function with the given bindgen attributes is deprecated,
+    /// the message given for its deprecation, which may be empty.
+    pub(crate) fn deprecation_for(&self, attrs: &[Attribute]) -> Option<&str> {
+        get_mangled_name(attrs)
+            .and_then(|mangled| self.deprecations.get(&mangled))
+            .map(|message| message.as_str())
+    }
+
+    /// The C++ signature of a function with the given bindgen attributes,
+    /// for example `int ns::Foo::get(int index) const`.
+    pub(crate) fn signature_for(&self, attrs: &[Attribute]) -> Option<&str> {
+        get_mangled_name(attrs)
+            .and_then(|mangled| self.signatures.get(&mangled))
+            .map(|signature| signature.as_str())
+    }
+
+    /// If this function is one which we declared to stand in for a method
+    /// of a template instantiation, the details of that method.
+    pub(crate) fn instantiated_method(&self, fn_name: &Ident) -> Option<&InstantiatedMethod> {
+        self.instantiated_methods.get(&fn_name.to_string())
+    }
+}
+
+pub(crate) fn get_mangled_name(attrs: &[Attribute]) -> Option<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("link_name"))
+        .find_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(nv)) => match nv.lit {
+                Lit::Str(s) => Some(s.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        // bindgen asks LLVM not to mangle this name any further.
+        .map(|link_name| link_name.trim_start_matches('\u{1}').to_string())
+}
+
+/// A method of a template instantiation named using `instantiate!`.
+/// bindgen never generates methods for templates, so we find them using
+/// libclang, and then declare a function for each, which bindgen does
+/// understand. It takes a pointer to the instantiation in place of `this`.
+/// We never define or call these functions; instead we call the real
+/// method.
+#[derive(Clone)]
+pub(crate) struct InstantiatedMethod {
+    declaration_name: String,
+    /// The instantiation's name, for example `ns::PointF`.
+    pub(crate) self_ty: QualifiedName,
+    pub(crate) method: String,
+    pub(crate) is_static: bool,
+    /// For example, `float ns::Point<float>::sum(float scale) const`.
+    pub(crate) signature: String,
+    declaration: String,
+}
+
+impl InstantiatedMethod {
+    /// Finds the public methods of the templates of which the user has
+    /// named instantiations. As bindgen hasn't yet run, this parses the
+    /// header which we're about to give to bindgen, so we only do so if
+    /// there are any instantiations. Operators, constructors and methods
+    /// which are themselves templates are skipped, as are any methods
+    /// whose types we can't write down for this instantiation.
+    pub(crate) fn find_all(
+        header: &str,
+        filename: &str,
+        clang_args: &[String],
+        instantiations: &[Instantiation],
+    ) -> Vec<Self> {
+        let targets: Vec<_> = instantiations
+            .iter()
+            .filter_map(InstantiationTarget::new)
+            .collect();
+        if targets.is_empty() {
+            return Vec::new();
+        }
+        let mut visitor = TemplateVisitor {
+            targets,
+            results: Vec::new(),
+        };
+        parse_header(header, filename, clang_args, 0, |tu| unsafe {
+            clang_visitChildren(
+                clang_getTranslationUnitCursor(tu),
+                visit_templates,
+                &mut visitor as *mut TemplateVisitor as CXClientData,
+            );
+        });
+        visitor.results
+    }
+
+    /// The C++ declaration we give bindgen.
+    pub(crate) fn declaration(&self) -> &str {
+        &self.declaration
+    }
+
+    /// The name of the function we declare for bindgen.
+    pub(crate) fn declaration_name(&self) -> &str {
+        &self.declaration_name
+    }
+}
+
+/// An instantiation whose template we're looking for.
+struct InstantiationTarget {
+    name: QualifiedName,
+    /// For example, `ns::Point`.
+    template: String,
+    args: Vec<String>,
+}
+
+impl InstantiationTarget {
+    fn new(instantiation: &Instantiation) -> Option<Self> {
+        let cpp_type = instantiation.cpp_type.trim();
+        let open = cpp_type.find('<')?;
+        let args = cpp_type.strip_suffix('>')?.get(open + 1..)?;
+        Some(Self {
+            name: QualifiedName::new_from_cpp_name(&instantiation.name),
+            template: cpp_type[..open].trim().trim_start_matches("::").to_string(),
+            args: split_template_args(args),
+        })
+    }
+
+    fn cpp_type(&self) -> String {
+        format!("{}<{}>", self.template, self.args.join(", "))
+    }
+
+    /// Makes declarations for bindgen of all the given methods, so far as
+    /// we can.
+    fn instantiate(
+        &self,
+        params: &[String],
+        methods: &[TemplateMethod],
+    ) -> Vec<InstantiatedMethod> {
+        let template_id = self.template.rsplit("::").next().unwrap();
+        let template_ns = self.template.rfind("::").map(|pos| &self.template[..pos]);
+        let self_id = self.name.get_final_item();
+        let substitute =
+            |spelling: &str| substitute_template_args(spelling, params, &self.args, template_id);
+        methods
+            .iter()
+            .enumerate()
+            .filter_map(|(index, method)| {
+                let ret_type = substitute(&method.ret_type)?;
+                let param_types: Vec<_> = method
+                    .params
+                    .iter()
+                    .map(|(ty, _)| substitute(ty))
+                    .collect::<Option<_>>()?;
+                let params: Vec<_> = param_types
+                    .iter()
+                    .zip(method.params.iter())
+                    .enumerate()
+                    .map(|(i, (ty, (_, name)))| match name.as_str() {
+                        "" => format!("{} arg{}", ty, i),
+                        _ => format!("{} {}", ty, name),
+                    })
+                    .collect();
+                let receiver = match (method.is_static, method.is_const) {
+                    (true, _) => None,
+                    (false, true) => Some(format!("const {}* autocxx_this", self_id)),
+                    (false, false) => Some(format!("{}* autocxx_this", self_id)),
+                };
+                let declaration_name =
+                    format!("{}_{}_autocxx_method{}", self_id, method.name, index);
+                let mut declaration = format!(
+                    "extern \"C\" {} {}({});",
+                    ret_type,
+                    declaration_name,
+                    receiver
+                        .into_iter()
+                        .chain(params.iter().cloned())
+                        .join(", ")
+                );
+                if let Some(template_ns) = template_ns {
+                    declaration = format!("using namespace {}; {}", template_ns, declaration);
+                }
+                let ns_segments: Vec<_> = self.name.ns_segment_iter().collect();
+                for ns in ns_segments.into_iter().rev() {
+                    declaration = format!("namespace {} {{ {} }}", ns, declaration);
+                }
+                let mut signature = format!(
+                    "{} {}::{}({})",
+                    ret_type,
+                    self.cpp_type(),
+                    method.name,
+                    params.join(", ")
+                );
+                if method.is_static {
+                    signature = format!("static {}", signature);
+                }
+                if method.is_const {
+                    signature.push_str(" const");
+                }
+                Some(InstantiatedMethod {
+                    declaration_name,
+                    self_ty: self.name.clone(),
+                    method: method.name.clone(),
+                    is_static: method.is_static,
+                    signature,
+                    declaration,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Splits the arguments of a template at the top-level commas.
+fn split_template_args(args: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in args.chars() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        result.push(current.trim().to_string());
+    }
+    result
+}
+
+/// Replaces template parameters in libclang's spelling of a type, as
+/// found within a template, with the arguments of an instantiation.
+/// libclang spells types declared within the template, such as
+/// `value_type`, as `Point::value_type`, so we supply the arguments
+/// there too. If any parameters are left over, perhaps because they
+/// have defaults, we give up.
+fn substitute_template_args(
+    spelling: &str,
+    params: &[String],
+    args: &[String],
+    template_id: &str,
+) -> Option<String> {
+    if spelling.contains("type-parameter") || spelling.contains("dependent") {
+        return None;
+    }
+    let mut result = String::new();
+    let mut rest = spelling;
+    while let Some(start) = rest.find(|c: char| c.is_alphanumeric() || c == '_') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+        rest = &rest[end..];
+        match params.iter().position(|param| param == word) {
+            Some(index) => result.push_str(args.get(index)?),
+            None if word == "auto" => return None,
+            None => {
+                result.push_str(word);
+                if word == template_id && rest.starts_with("::") {
+                    result.push_str(&format!("<{}>", args.join(", ")));
+                }
+            }
+        }
+    }
+    result.push_str(rest);
+    Some(result)
+}
+
+/// A public method of a class template, with its types as spelled
+/// within the template.
+struct TemplateMethod {
+    name: String,
+    ret_type: String,
+    params: Vec<(String, String)>,
+    is_static: bool,
+    is_const: bool,
+}
+
+struct TemplateVisitor {
+    targets: Vec<InstantiationTarget>,
+    results: Vec<InstantiatedMethod>,
+}
+
+impl TemplateVisitor {
+    fn might_contain_templates(&self, ns: &str) -> bool {
+        self.targets
+            .iter()
+            .any(|target| target.template.starts_with(&format!("{}::", ns)))
+    }
+}
+
+#[allow(non_upper_case_globals)] // libclang's names
+extern "C" fn visit_templates(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let visitor = unsafe { &mut *(data as *mut TemplateVisitor) };
+    match unsafe { clang_getCursorKind(cursor) } {
+        CXCursor_Namespace => {
+            if visitor.might_contain_templates(&unsafe { qualified_name(cursor) }) {
+                CXChildVisit_Recurse
+            } else {
+                CXChildVisit_Continue
+            }
+        }
+        CXCursor_LinkageSpec => CXChildVisit_Recurse,
+        CXCursor_ClassTemplate => {
+            let template = unsafe { qualified_name(cursor) };
+            if visitor
+                .targets
+                .iter()
+                .any(|target| target.template == template)
+            {
+                let mut members = TemplateMembers::default();
+                unsafe {
+                    clang_visitChildren(
+                        cursor,
+                        visit_template_members,
+                        &mut members as *mut TemplateMembers as CXClientData,
+                    );
+                }
+                for target in visitor
+                    .targets
+                    .iter()
+                    .filter(|target| target.template == template)
+                {
+                    visitor
+                        .results
+                        .extend(target.instantiate(&members.params, &members.methods));
+                }
+            }
+            CXChildVisit_Continue
+        }
+        _ => CXChildVisit_Continue,
+    }
+}
+
+#[derive(Default)]
+struct TemplateMembers {
+    params: Vec<String>,
+    methods: Vec<TemplateMethod>,
+}
+
+#[allow(non_upper_case_globals)] // libclang's names
+extern "C" fn visit_template_members(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let members = unsafe { &mut *(data as *mut TemplateMembers) };
+    match unsafe { clang_getCursorKind(cursor) } {
+        CXCursor_TemplateTypeParameter
+        | CXCursor_NonTypeTemplateParameter
+        | CXCursor_TemplateTemplateParameter => members
+            .params
+            .push(unsafe { cxstring_into_string(clang_getCursorSpelling(cursor)) }),
+        CXCursor_CXXMethod => {
+            if let Some(method) = unsafe { template_method(cursor) } {
+                members.methods.push(method);
+            }
+        }
+        _ => {}
+    }
+    CXChildVisit_Continue
+}
+
+unsafe fn template_method(cursor: CXCursor) -> Option<TemplateMethod> {
+    let name = cxstring_into_string(clang_getCursorSpelling(cursor));
+    if clang_getCXXAccessSpecifier(cursor) != CX_CXXPublic
+        || name.starts_with("operator")
+        || clang_isFunctionTypeVariadic(clang_getCursorType(cursor)) != 0
+    {
+        return None;
+    }
+    let num_args = clang_Cursor_getNumArguments(cursor);
+    let params = (0..num_args.max(0) as c_uint)
+        .map(|i| {
+            let param = clang_Cursor_getArgument(cursor, i);
+            (
+                cxstring_into_string(clang_getTypeSpelling(clang_getCursorType(param))),
+                cxstring_into_string(clang_getCursorSpelling(param)),
+            )
+        })
+        .collect();
+    Some(TemplateMethod {
+        name,
+        ret_type: cxstring_into_string(clang_getTypeSpelling(clang_getCursorResultType(cursor))),
+        params,
+        is_static: clang_CXXMethod_isStatic(cursor) != 0,
+        is_const: clang_CXXMethod_isConst(cursor) != 0,
+    })
+}
+
+/// The fully qualified name of a namespace or class template.
+#[allow(non_upper_case_globals)] // libclang's names
+unsafe fn qualified_name(cursor: CXCursor) -> String {
+    let mut segments = vec![cxstring_into_string(clang_getCursorSpelling(cursor))];
+    let mut parent = clang_getCursorSemanticParent(cursor);
+    while matches!(
+        clang_getCursorKind(parent),
+        CXCursor_Namespace | CXCursor_ClassDecl | CXCursor_StructDecl
+    ) {
+        segments.push(cxstring_into_string(clang_getCursorSpelling(parent)));
+        parent = clang_getCursorSemanticParent(parent);
+    }
+    segments
+        .into_iter()
+        .rev()
+        .filter(|s| !s.is_empty())
+        .join("::")
+}
+
+/// Parses a header with libclang, skipping function bodies, and calls
+/// `f` with the resulting translation unit. If libclang isn't available
+/// or can't parse the header, `f` is never called.
+pub(crate) fn parse_header(
+    header: &str,
+    filename: &str,
+    clang_args: &[String],
+    extra_flags: CXTranslationUnit_Flags,
+    f: impl FnOnce(CXTranslationUnit),
+) {
+    if !ensure_libclang_is_loaded() {
+        return;
+    }
+    let filename = CString::new(filename).unwrap();
+    let contents = match CString::new(header) {
+        Ok(contents) => contents,
+        Err(_) => return,
+    };
+    let clang_args: Vec<_> = clang_args
+        .iter()
+        .filter_map(|arg| CString::new(arg.as_str()).ok())
+        .collect();
+    let clang_arg_ptrs: Vec<*const c_char> = clang_args.iter().map(|arg| arg.as_ptr()).collect();
+    let mut unsaved = CXUnsavedFile {
+        Filename: filename.as_ptr(),
+        Contents: contents.as_ptr(),
+        Length: header.len() as c_ulong,
+    };
+    unsafe {
+        let index = clang_createIndex(0, 0);
+        let tu = clang_parseTranslationUnit(
+            index,
+            filename.as_ptr(),
+            clang_arg_ptrs.as_ptr(),
+            clang_arg_ptrs.len() as c_int,
+            &mut unsaved,
+            1,
+            CXTranslationUnit_SkipFunctionBodies | CXTranslationUnit_Incomplete | extra_flags,
+        );
+        if !tu.is_null() {
+            f(tu);
+            clang_disposeTranslationUnit(tu);
+        }
+        clang_disposeIndex(index);
+    }
+}
+
+/// We may be called before bindgen has loaded libclang on this thread.
+/// Like bindgen, we load it just once and share it between threads, and
+/// if we're first, bindgen uses the same library.
+fn ensure_libclang_is_loaded() -> bool {
+    static LIBCLANG: OnceCell<Option<Arc<SharedLibrary>>> = OnceCell::new();
+    if !clang_sys::is_loaded() {
+        let library = LIBCLANG.get_or_init(|| {
+            clang_sys::load().ok()?;
+            clang_sys::get_library()
+        });
+        clang_sys::set_library(library.clone());
+    }
+    clang_sys::is_loaded()
+}
+
+/// Finds the mangled names of all the functions in bindgen's output.
+fn find_mangled_names(module: &ItemMod, names: &mut HashSet<String>) {
+    for item in module.content.iter().flat_map(|(_, items)| items) {
+        match item {
+            Item::Mod(module) => find_mangled_names(module, names),
+            Item::ForeignMod(fm) => names.extend(fm.items.iter().filter_map(|item| match item {
+                ForeignItem::Fn(f) => get_mangled_name(&f.attrs),
+                _ => None,
+            })),
+            _ => {}
+        }
+    }
+}
+
+struct Visitor<'a> {
+    tu: CXTranslationUnit,
+    /// Mangled names of the functions we've yet to find.
+    wanted: HashSet<String>,
+    results: &'a mut ClangFnInfo,
+}
+
+#[allow(non_upper_case_globals)] // libclang's names
+extern "C" fn visit(cursor: CXCursor, _parent: CXCursor, data: CXClientData) -> CXChildVisitResult {
+    let visitor = unsafe { &mut *(data as *mut Visitor) };
+    match unsafe { clang_getCursorKind(cursor) } {
+        // bindgen never generates methods for templates, so we don't
+        // look inside them.
+        CXCursor_Namespace | CXCursor_ClassDecl | CXCursor_StructDecl => {
+            let name = unsafe { cxstring_into_string(clang_getCursorSpelling(cursor)) };
+            if visitor.might_contain_wanted(&name) {
+                CXChildVisit_Recurse
+            } else {
+                CXChildVisit_Continue
+            }
+        }
+        CXCursor_LinkageSpec => CXChildVisit_Recurse,
+        CXCursor_FunctionDecl | CXCursor_CXXMethod | CXCursor_Constructor => {
+            let mangled = match unsafe { mangling(cursor) } {
+                Some(mangled) if visitor.wanted.remove(&mangled) => mangled,
+                _ => return CXChildVisit_Continue,
+            };
+            let defaults = unsafe { count_trailing_defaults(visitor.tu, cursor) };
+            if defaults > 0 {
+                visitor
+                    .results
+                    .default_args
+                    .insert(mangled.clone(), defaults);
+            }
+            if let Some(deprecation) = unsafe { deprecation(cursor) } {
+                visitor
+                    .results
+                    .deprecations
+                    .insert(mangled.clone(), deprecation);
+            }
+            let signature = unsafe { signature(cursor) };
+            visitor.results.signatures.insert(mangled, signature);
+            if visitor.wanted.is_empty() {
+                CXChildVisit_Break
+            } else {
+                CXChildVisit_Continue
+            }
+        }
+        _ => CXChildVisit_Continue,
+    }
+}
+
+impl<'a> Visitor<'a> {
+    /// Whether a namespace or class of this name might contain any of
+    /// the functions we want. Their names appear within the mangled
+    /// names of all their members, except that the Itanium ABI
+    /// abbreviates `std`.
+    fn might_contain_wanted(&self, name: &str) -> bool {
+        name.is_empty()
+            || self
+                .wanted
+                .iter()
+                .any(|mangled| mangled.contains(name) || (name == "std" && mangled.contains("St")))
+    }
+}
+
+unsafe fn count_trailing_defaults(tu: CXTranslationUnit, cursor: CXCursor) -> usize {
+    let num_args = clang_Cursor_getNumArguments(cursor);
+    (0..num_args.max(0) as c_uint)
+        .rev()
+        .take_while(|i| has_default(tu, clang_Cursor_getArgument(cursor, *i)))
+        .count()
+}
+
+/// libclang doesn't directly tell us whether a parameter has a default,
+/// but if so, its declaration contains an `=` token.
+unsafe fn has_default(tu: CXTranslationUnit, param: CXCursor) -> bool {
+    let mut tokens = ptr::null_mut();
+    let mut num_tokens = 0;
+    clang_tokenize(
+        tu,
+        clang_getCursorExtent(param),
+        &mut tokens,
+        &mut num_tokens,
+    );
+    if tokens.is_null() {
+        return false;
+    }
+    let found = (0..num_tokens as usize).any(|i| {
+        let token = *tokens.add(i);
+        clang_getTokenKind(token) == CXToken_Punctuation
+            && cxstring_into_string(clang_getTokenSpelling(tu, token)) == "="
+    });
+    clang_disposeTokens(tu, tokens, num_tokens);
+    found
+}
+
+/// The message given by `[[deprecated]]`, if the function is deprecated.
+unsafe fn deprecation(cursor: CXCursor) -> Option<String> {
+    let mut deprecated = 0;
+    let mut deprecated_message = CXString::default();
+    let mut unavailable = 0;
+    let mut unavailable_message = CXString::default();
+    clang_getCursorPlatformAvailability(
+        cursor,
+        &mut deprecated,
+        &mut deprecated_message,
+        &mut unavailable,
+        &mut unavailable_message,
+        ptr::null_mut(),
+        0,
+    );
+    let message = cxstring_into_string(deprecated_message);
+    clang_disposeString(unavailable_message);
+    if deprecated != 0 {
+        Some(message)
+    } else {
+        None
+    }
+}
+
+/// The function's declaration as it might be written in C++, with its
+/// fully qualified name, but without any default arguments.
+#[allow(non_upper_case_globals)] // libclang's names
+unsafe fn signature(cursor: CXCursor) -> String {
+    let mut name = cxstring_into_string(clang_getCursorSpelling(cursor));
+    let mut parent = clang_getCursorSemanticParent(cursor);
+    while matches!(
+        clang_getCursorKind(parent),
+        CXCursor_Namespace | CXCursor_ClassDecl | CXCursor_StructDecl
+    ) {
+        let parent_name = cxstring_into_string(clang_getCursorSpelling(parent));
+        if !parent_name.is_empty() {
+            name = format!("{}::{}", parent_name, name);
+        }
+        parent = clang_getCursorSemanticParent(parent);
+    }
+    let num_args = clang_Cursor_getNumArguments(cursor);
+    let params: Vec<_> = (0..num_args.max(0) as c_uint)
+        .map(|i| {
+            let param = clang_Cursor_getArgument(cursor, i);
+            let ty = cxstring_into_string(clang_getTypeSpelling(clang_getCursorType(param)));
+            let param_name = cxstring_into_string(clang_getCursorSpelling(param));
+            if param_name.is_empty() {
+                ty
+            } else {
+                format!("{} {}", ty, param_name)
+            }
+        })
+        .collect();
+    let mut signature = format!("{}({})", name, params.join(", "));
+    if clang_getCursorKind(cursor) != CXCursor_Constructor {
+        let ret = cxstring_into_string(clang_getTypeSpelling(clang_getCursorResultType(cursor)));
+        signature = format!("{} {}", ret, signature);
+    }
+    if clang_CXXMethod_isStatic(cursor) != 0 {
+        signature = format!("static {}", signature);
+    }
+    if clang_CXXMethod_isVirtual(cursor) != 0 {
+        signature = format!("virtual {}", signature);
+    }
+    if clang_CXXMethod_isConst(cursor) != 0 {
+        signature.push_str(" const");
+    }
+    signature
+}
+
+/// The same mangled name which bindgen finds for a function.
+unsafe fn mangling(cursor: CXCursor) -> Option<String> {
+    let manglings = clang_Cursor_getCXXManglings(cursor);
+    if !manglings.is_null() {
+        let count = (*manglings).Count as usize;
+        let last = match count {
+            0 => None,
+            _ => Some(cxstring_to_string(*(*manglings).Strings.add(count - 1))),
+        };
+        clang_disposeStringSet(manglings);
+        if last.is_some() {
+            return last;
+        }
+    }
+    let mangling = cxstring_into_string(clang_Cursor_getMangling(cursor));
+    if mangling.is_empty() {
+        None
+    } else {
+        Some(mangling)
+    }
+}
+
+unsafe fn cxstring_to_string(s: CXString) -> String {
+    let c_str = clang_getCString(s);
+    if c_str.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(c_str).to_string_lossy().into_owned()
+    }
+}
+
+pub(crate) unsafe fn cxstring_into_string(s: CXString) -> String {
+    let result = cxstring_to_string(s);
+    clang_disposeString(s);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_template_args, substitute_template_args};
+
+    #[test]
+    fn test_split_template_args() {
+        assert_eq!(
+            split_template_args("std::map<int, float>, 4"),
+            vec!["std::map<int, float>", "4"]
+        );
+        assert_eq!(split_template_args(" float "), vec!["float"]);
+    }
+
+    #[test]
+    fn test_substitute_template_args() {
+        let params = vec!["T".to_string(), "N".to_string()];
+        let args = vec!["float".to_string(), "4".to_string()];
+        let substitute = |spelling| substitute_template_args(spelling, &params, &args, "Matrix");
+        assert_eq!(
+            substitute("const Matrix<T, N> &").as_deref(),
+            Some("const Matrix<float, 4> &")
+        );
+        assert_eq!(
+            substitute("ns::Matrix::value_type").as_deref(),
+            Some("ns::Matrix<float, 4>::value_type")
+        );
+        assert_eq!(substitute("Tee *").as_deref(), Some("Tee *"));
+        assert_eq!(substitute("auto"), None);
+        assert_eq!(
+            substitute_template_args("N", &params, &args[..1], "Matrix"),
+            None
+        );
+    }
+}
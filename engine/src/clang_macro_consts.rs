@@ -0,0 +1,351 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashSet, os::raw::c_uint, ptr};
+
+use autocxx_parser::IncludeCppConfig;
+use clang_sys::*;
+use itertools::Itertools;
+use proc_macro2::Literal;
+use syn::{parse_quote, ForeignItem, Item, ItemConst, ItemMod};
+
+use crate::{
+    clang_fn_info::{cxstring_into_string, parse_header},
+    types::make_ident,
+};
+
+const PROBE_PREFIX: &str = "autocxx_macro_probe_";
+
+/// bindgen evaluates `#define`s only if they're literals or simple
+/// arithmetic. For other object-like macros of floating point, `char` or
+/// enum type, such as `#define SCALE (2 * M_PI)` or
+/// `#define DEFAULT_COLOR Color::Green`, we ask libclang to evaluate them,
+/// and add the resulting constants to bindgen's output as if bindgen had
+/// generated them, for the rest of our pipeline to find. We consider only
+/// macros which are on the allowlist, defined outside system headers, and
+/// not already generated by bindgen or requested with `macro_fn!`.
+///
+/// To evaluate each macro we declare a variable initialized from it,
+/// after the header. If we need to look for macros at all, that's two
+/// parses of the header: one to find them, and one to evaluate them.
+pub(crate) fn add_macro_consts(
+    bindings: &mut ItemMod,
+    header: &str,
+    filename: &str,
+    clang_args: &[String],
+    config: &IncludeCppConfig,
+) {
+    let root = match find_root_mod(bindings) {
+        Some(root) => root,
+        None => return,
+    };
+    let mut already_generated = HashSet::new();
+    find_generated_names(root, &mut already_generated);
+    already_generated.extend(config.get_macro_fns().iter().map(|m| m.name.clone()));
+    // Macros can't be namespaced.
+    let candidates: Option<HashSet<String>> = config.bindgen_allowlist().map(|allowlist| {
+        allowlist
+            .filter(|name| !name.contains("::") && !already_generated.contains(name))
+            .collect()
+    });
+    if matches!(&candidates, Some(candidates) if candidates.is_empty()) {
+        return;
+    }
+    let mut macros = MacroFinder {
+        tu: None,
+        candidates,
+        already_generated,
+        results: Vec::new(),
+    };
+    parse_header(
+        header,
+        filename,
+        clang_args,
+        CXTranslationUnit_DetailedPreprocessingRecord,
+        |tu| unsafe {
+            macros.tu = Some(tu);
+            clang_visitChildren(
+                clang_getTranslationUnitCursor(tu),
+                visit_macros,
+                &mut macros as *mut MacroFinder as CXClientData,
+            );
+        },
+    );
+    if macros.results.is_empty() {
+        return;
+    }
+    let probes = macros
+        .results
+        .iter()
+        .map(|name| format!("static const auto {}{} = {};", PROBE_PREFIX, name, name))
+        .join("\n");
+    let header = format!("{}\n{}\n", header, probes);
+    let mut evaluator = Evaluator {
+        config,
+        results: Vec::new(),
+    };
+    parse_header(&header, filename, clang_args, 0, |tu| unsafe {
+        clang_visitChildren(
+            clang_getTranslationUnitCursor(tu),
+            visit_probes,
+            &mut evaluator as *mut Evaluator as CXClientData,
+        );
+    });
+    if let Some((_, items)) = &mut root.content {
+        items.extend(evaluator.results.into_iter().map(Item::Const));
+    }
+}
+
+/// bindgen puts everything within `mod root`, which is itself within
+/// the `mod bindgen` we wrap around its output.
+fn find_root_mod(bindings: &mut ItemMod) -> Option<&mut ItemMod> {
+    bindings
+        .content
+        .as_mut()?
+        .1
+        .iter_mut()
+        .find_map(|item| match item {
+            Item::Mod(root) if root.ident == "root" => Some(root),
+            _ => None,
+        })
+}
+
+/// Finds the names of the items at the top level of bindgen's output,
+/// which is where it would have put constants for any macros.
+fn find_generated_names(root: &ItemMod, names: &mut HashSet<String>) {
+    for item in root.content.iter().flat_map(|(_, items)| items) {
+        match item {
+            Item::Const(c) => names.insert(c.ident.to_string()),
+            Item::Static(s) => names.insert(s.ident.to_string()),
+            Item::Struct(s) => names.insert(s.ident.to_string()),
+            Item::Enum(e) => names.insert(e.ident.to_string()),
+            Item::Union(u) => names.insert(u.ident.to_string()),
+            Item::Type(t) => names.insert(t.ident.to_string()),
+            Item::Fn(f) => names.insert(f.sig.ident.to_string()),
+            Item::ForeignMod(fm) => {
+                names.extend(fm.items.iter().filter_map(|item| match item {
+                    ForeignItem::Fn(f) => Some(f.sig.ident.to_string()),
+                    ForeignItem::Static(s) => Some(s.ident.to_string()),
+                    _ => None,
+                }));
+                continue;
+            }
+            _ => continue,
+        };
+    }
+}
+
+struct MacroFinder {
+    tu: Option<CXTranslationUnit>,
+    /// The macros which we may generate, or `None` for any.
+    candidates: Option<HashSet<String>>,
+    already_generated: HashSet<String>,
+    results: Vec<String>,
+}
+
+impl MacroFinder {
+    fn is_candidate(&self, name: &str) -> bool {
+        !self.already_generated.contains(name)
+            && match &self.candidates {
+                Some(candidates) => candidates.contains(name),
+                None => true,
+            }
+    }
+}
+
+/// Macros appear at the top level of the translation unit, so we never
+/// need to recurse.
+extern "C" fn visit_macros(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let finder = unsafe { &mut *(data as *mut MacroFinder) };
+    unsafe {
+        if clang_getCursorKind(cursor) == CXCursor_MacroDefinition
+            && clang_Cursor_isMacroBuiltin(cursor) == 0
+            && clang_Cursor_isMacroFunctionLike(cursor) == 0
+            && clang_Location_isInSystemHeader(clang_getCursorLocation(cursor)) == 0
+        {
+            let name = cxstring_into_string(clang_getCursorSpelling(cursor));
+            if finder.is_candidate(&name) && is_expression(finder.tu.unwrap(), cursor) {
+                finder.results.push(name);
+            }
+        }
+    }
+    CXChildVisit_Continue
+}
+
+/// Whether a macro expands to something which might be an expression.
+/// We avoid declaring variables initialized from macros which are empty,
+/// or which contain braces or semicolons, such as
+/// `#define EXTERN_C_BEGIN extern "C" {`, because they could prevent
+/// libclang from making sense of the rest of our declarations.
+unsafe fn is_expression(tu: CXTranslationUnit, cursor: CXCursor) -> bool {
+    let mut tokens = ptr::null_mut();
+    let mut num_tokens: c_uint = 0;
+    clang_tokenize(
+        tu,
+        clang_getCursorExtent(cursor),
+        &mut tokens,
+        &mut num_tokens,
+    );
+    if tokens.is_null() {
+        return false;
+    }
+    // The first token is the name of the macro.
+    let result = num_tokens > 1
+        && (1..num_tokens as isize).all(|i| {
+            let token = *tokens.offset(i);
+            clang_getTokenKind(token) != CXToken_Punctuation
+                || !matches!(
+                    cxstring_into_string(clang_getTokenSpelling(tu, token)).as_str(),
+                    "{" | "}" | ";"
+                )
+        });
+    clang_disposeTokens(tu, tokens, num_tokens);
+    result
+}
+
+struct Evaluator<'a> {
+    config: &'a IncludeCppConfig,
+    results: Vec<ItemConst>,
+}
+
+#[allow(non_upper_case_globals)] // libclang's names
+extern "C" fn visit_probes(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let evaluator = unsafe { &mut *(data as *mut Evaluator) };
+    unsafe {
+        if clang_getCursorKind(cursor) == CXCursor_VarDecl {
+            let name = cxstring_into_string(clang_getCursorSpelling(cursor));
+            if let Some(name) = name.strip_prefix(PROBE_PREFIX) {
+                if let Some(item) = evaluate(cursor, name, evaluator.config) {
+                    evaluator.results.push(item);
+                }
+            }
+        }
+    }
+    CXChildVisit_Continue
+}
+
+/// Evaluates one of our variables, and returns the constant we should
+/// generate for the macro from which it's initialized, if it's one of
+/// those we're interested in. Floating point values are represented as
+/// `f64` and `char`s as `u8`, like the `#define`s which bindgen evaluates.
+#[allow(non_upper_case_globals)] // libclang's names
+unsafe fn evaluate(cursor: CXCursor, name: &str, config: &IncludeCppConfig) -> Option<ItemConst> {
+    let ty = clang_getCanonicalType(clang_getCursorType(cursor));
+    let result = clang_Cursor_Evaluate(cursor);
+    if result.is_null() {
+        return None;
+    }
+    let kind = clang_EvalResult_getKind(result);
+    let float_value = clang_EvalResult_getAsDouble(result);
+    let int_value = clang_EvalResult_getAsLongLong(result);
+    clang_EvalResult_dispose(result);
+    let id = make_ident(name);
+    match (ty.kind, kind) {
+        (CXType_Float, CXEval_Float)
+        | (CXType_Double, CXEval_Float)
+        | (CXType_LongDouble, CXEval_Float)
+            if float_value.is_finite() =>
+        {
+            let value = Literal::f64_suffixed(float_value);
+            Some(parse_quote! {
+                pub const #id: f64 = #value;
+            })
+        }
+        (CXType_Char_S, CXEval_Int)
+        | (CXType_Char_U, CXEval_Int)
+        | (CXType_SChar, CXEval_Int)
+        | (CXType_UChar, CXEval_Int) => {
+            let value = Literal::u8_suffixed(int_value as u8);
+            Some(parse_quote! {
+                pub const #id: u8 = #value;
+            })
+        }
+        (CXType_Enum, CXEval_Int) => {
+            let decl = clang_getTypeDeclaration(ty);
+            let mut path = enum_path(decl)?;
+            if !config.is_on_allowlist(&path.iter().join("::")) {
+                return None;
+            }
+            let mut finder = EnumeratorFinder {
+                value: int_value,
+                result: None,
+            };
+            clang_visitChildren(
+                decl,
+                visit_enumerators,
+                &mut finder as *mut EnumeratorFinder as CXClientData,
+            );
+            let ty_path: Vec<_> = path.iter().map(make_ident).collect();
+            path.push(finder.result?);
+            let value_path = path.iter().map(make_ident);
+            Some(parse_quote! {
+                pub const #id: root::#(#ty_path)::* = root::#(#value_path)::*;
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The namespaces and name of an enum, provided that it's named and isn't
+/// nested within a class, so that bindgen uses the same path.
+#[allow(non_upper_case_globals)] // libclang's names
+unsafe fn enum_path(decl: CXCursor) -> Option<Vec<String>> {
+    let mut path = vec![cxstring_into_string(clang_getCursorSpelling(decl))];
+    let mut parent = clang_getCursorSemanticParent(decl);
+    loop {
+        match clang_getCursorKind(parent) {
+            CXCursor_TranslationUnit => break,
+            CXCursor_Namespace => {
+                path.push(cxstring_into_string(clang_getCursorSpelling(parent)));
+                parent = clang_getCursorSemanticParent(parent);
+            }
+            _ => return None,
+        }
+    }
+    if path.iter().any(String::is_empty) {
+        return None;
+    }
+    path.reverse();
+    Some(path)
+}
+
+struct EnumeratorFinder {
+    value: i64,
+    result: Option<String>,
+}
+
+extern "C" fn visit_enumerators(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let finder = unsafe { &mut *(data as *mut EnumeratorFinder) };
+    unsafe {
+        if clang_getCursorKind(cursor) == CXCursor_EnumConstantDecl
+            && clang_getEnumConstantDeclValue(cursor) == finder.value
+        {
+            finder.result = Some(cxstring_into_string(clang_getCursorSpelling(cursor)));
+            return CXChildVisit_Break;
+        }
+    }
+    CXChildVisit_Continue
+}
@@ -12,9 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::{HashMap, HashSet};
-
-use syn::Ident;
+use std::collections::{BTreeMap, HashSet};
 
 use crate::{conversion::api::Api, known_types::known_types, types::QualifiedName};
 use crate::{conversion::api::ApiDetail, types::Namespace};
@@ -24,16 +22,17 @@ use super::fun::FnAnalysis;
 /// Spot any variable-length C types (e.g. unsigned long)
 /// used in the [Api]s and append those as extra APIs.
 pub(crate) fn append_ctype_information(apis: &mut Vec<Api<FnAnalysis>>) {
-    let ctypes: HashMap<Ident, QualifiedName> = apis
+    // Sorted, so that we generate them in the same order each time.
+    let ctypes: BTreeMap<String, QualifiedName> = apis
         .iter()
         .map(|api| api.deps.iter())
         .flatten()
         .filter(|ty| known_types().is_ctype(ty))
-        .map(|ty| (ty.get_final_ident(), ty.clone()))
+        .map(|ty| (ty.get_final_item().to_string(), ty.clone()))
         .collect();
-    for (id, tn) in ctypes {
+    for (_, tn) in ctypes {
         apis.push(Api {
-            name: QualifiedName::new(&Namespace::new(), id),
+            name: QualifiedName::new(&Namespace::new(), tn.get_final_ident()),
             original_name: None,
             deps: HashSet::new(),
             detail: ApiDetail::CType { typename: tn },
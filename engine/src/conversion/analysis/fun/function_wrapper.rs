@@ -12,20 +12,39 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::types::Namespace;
-use syn::{parse_quote, Ident, Type};
+use crate::types::{Namespace, QualifiedName};
+use syn::{parse_quote, GenericArgument, Ident, Pat, PathArguments, Type};
 
 #[derive(Clone)]
 pub(crate) enum CppConversionType {
     None,
     FromUniquePtrToValue,
     FromValueToUniquePtr,
+    FromValueToSharedPtr,
+    /// An out parameter, for which the C++ wrapper allocates a new
+    /// value and stores it in the `std::unique_ptr` it's given.
+    FromUniquePtrToOutParam,
+    /// An out parameter of a POD type, whose storage belongs to Rust.
+    /// The C++ wrapper default-constructs a value there before the
+    /// call, so Rust can rely on it being initialized.
+    ConstructInOutParam,
 }
 
 #[derive(Clone)]
 pub(crate) enum RustConversionType {
     None,
     FromStr,
+    /// A pointer which may be null, given in Rust as an `Option` of a
+    /// reference.
+    FromOptionalReference,
+    /// A pointer to an array, given in Rust as a slice.
+    FromSlice,
+    /// The length of the slice given for the named parameter, which
+    /// is therefore not a parameter of the Rust function.
+    FromSliceLength(Pat),
+    /// A pointer or non-const reference through which C++ writes a value,
+    /// which the Rust function returns instead.
+    ToOutParam,
 }
 
 /// A policy for converting types. Conversion may occur on both the Rust and
@@ -63,6 +82,14 @@ impl TypeConversionPolicy {
         }
     }
 
+    pub(crate) fn new_to_shared_ptr(ty: Type) -> Self {
+        TypeConversionPolicy {
+            unwrapped_type: ty,
+            cpp_conversion: CppConversionType::FromValueToSharedPtr,
+            rust_conversion: RustConversionType::None,
+        }
+    }
+
     pub(crate) fn new_from_unique_ptr(ty: Type) -> Self {
         TypeConversionPolicy {
             unwrapped_type: ty,
@@ -86,6 +113,7 @@ impl TypeConversionPolicy {
     pub(crate) fn unconverted_rust_type(&self) -> Type {
         match self.cpp_conversion {
             CppConversionType::FromValueToUniquePtr => self.make_unique_ptr_type(),
+            CppConversionType::FromValueToSharedPtr => self.make_shared_ptr_type(),
             _ => self.unwrapped_type.clone(),
         }
     }
@@ -93,6 +121,12 @@ impl TypeConversionPolicy {
     pub(crate) fn converted_rust_type(&self) -> Type {
         match self.cpp_conversion {
             CppConversionType::FromUniquePtrToValue => self.make_unique_ptr_type(),
+            CppConversionType::FromUniquePtrToOutParam => {
+                let innerty = self.out_param_type().unwrap();
+                parse_quote! {
+                    &mut cxx::UniquePtr < #innerty >
+                }
+            }
             _ => self.unwrapped_type.clone(),
         }
     }
@@ -104,16 +138,102 @@ impl TypeConversionPolicy {
         }
     }
 
+    fn make_shared_ptr_type(&self) -> Type {
+        let innerty = &self.unwrapped_type;
+        parse_quote! {
+            cxx::SharedPtr < #innerty >
+        }
+    }
+
+    /// A policy for a raw pointer parameter which is presented differently
+    /// in Rust, for example as a slice.
+    pub(crate) fn new_for_pointer(ty: Type, rust_conversion: RustConversionType) -> Self {
+        TypeConversionPolicy {
+            unwrapped_type: ty,
+            cpp_conversion: CppConversionType::None,
+            rust_conversion,
+        }
+    }
+
+    /// A policy for a parameter through which C++ returns a value. Values
+    /// of POD types can be written directly into Rust memory; others are
+    /// allocated by C++.
+    pub(crate) fn new_to_out_param(ty: Type, is_pod: bool) -> Self {
+        TypeConversionPolicy {
+            unwrapped_type: ty,
+            cpp_conversion: if is_pod {
+                CppConversionType::ConstructInOutParam
+            } else {
+                CppConversionType::FromUniquePtrToOutParam
+            },
+            rust_conversion: RustConversionType::ToOutParam,
+        }
+    }
+
+    pub(crate) fn is_out_param(&self) -> bool {
+        matches!(self.rust_conversion, RustConversionType::ToOutParam)
+    }
+
+    /// Whether this is an out parameter whose value C++ allocates and
+    /// returns in a `UniquePtr`, rather than writing it into Rust memory.
+    pub(crate) fn is_unique_ptr_out_param(&self) -> bool {
+        matches!(
+            self.cpp_conversion,
+            CppConversionType::FromUniquePtrToOutParam
+        )
+    }
+
+    /// The type of value returned through an out parameter.
+    pub(crate) fn out_param_type(&self) -> Option<&Type> {
+        out_param_pointee(&self.unwrapped_type).map(|(ty, _)| ty)
+    }
+
     pub(crate) fn rust_work_needed(&self) -> bool {
         !matches!(self.rust_conversion, RustConversionType::None)
     }
 }
 
+/// If this type could be an out parameter - a non-const pointer, or a
+/// non-const reference (which by now is a `Pin<&mut T>`) - returns the type
+/// to which it refers, and whether it's a pointer.
+pub(crate) fn out_param_pointee(ty: &Type) -> Option<(&Type, bool)> {
+    match ty {
+        Type::Ptr(ptr) if ptr.mutability.is_some() => Some((ptr.elem.as_ref(), true)),
+        Type::Path(typ) => {
+            let last_seg = typ.path.segments.last()?;
+            match &last_seg.arguments {
+                PathArguments::AngleBracketed(ab) if last_seg.ident == "Pin" => {
+                    match ab.args.first() {
+                        Some(GenericArgument::Type(Type::Reference(r)))
+                            if r.mutability.is_some() =>
+                        {
+                            Some((r.elem.as_ref(), false))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
 #[derive(Clone)] // TODO wish this didn't need to be cloneable
 pub(crate) enum FunctionWrapperPayload {
     FunctionCall(Namespace, Ident),
     StaticMethodCall(Namespace, Ident, Ident),
+    /// A global variable, or a static member of the given type.
+    StaticVariable(Namespace, Option<Ident>, Ident),
     Constructor,
+    /// Placement new of the given type, at the address given by
+    /// the first argument.
+    Emplace(QualifiedName),
+    /// Conversion of the receiver to a reference to one of its
+    /// base classes.
+    Upcast,
+    /// `dynamic_cast` of the receiver to a pointer of the return type.
+    Downcast,
 }
 
 #[derive(Clone)] // TODO wish this didn't need to be cloneable
@@ -0,0 +1,592 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+
+use syn::{
+    parse_quote, punctuated::Punctuated, FnArg, GenericArgument, PathArguments, ReturnType, Token,
+    Type,
+};
+
+use crate::{
+    conversion::{
+        analysis::pod::ByValueChecker,
+        api::{Api, ApiDetail, Downcast, FuncToConvert},
+        codegen_cpp::AdditionalNeed,
+    },
+    types::{make_ident, QualifiedName},
+};
+
+use super::{
+    function_wrapper::{FunctionWrapper, FunctionWrapperPayload, TypeConversionPolicy},
+    ArgumentAnalysis, FnAnalysis, FnAnalysisBody, FnAnalyzer, FnKind, MethodKind,
+    RustRenameStrategy,
+};
+
+/// The base classes of each type, as far as we know them.
+pub(super) struct ClassHierarchy {
+    bases: HashMap<QualifiedName, Vec<QualifiedName>>,
+    /// Types with virtual functions of their own.
+    vtables: HashSet<QualifiedName>,
+}
+
+impl ClassHierarchy {
+    pub(super) fn new(apis: &[Api<FnAnalysis>]) -> Self {
+        let types: HashSet<_> = apis
+            .iter()
+            .filter(|api| matches!(api.detail, ApiDetail::Struct { .. }))
+            .map(|api| api.name())
+            .collect();
+        let bases = apis
+            .iter()
+            .filter_map(|api| match &api.detail {
+                ApiDetail::Struct { details, .. } if !details.bases.is_empty() => Some((
+                    api.name(),
+                    details
+                        .bases
+                        .iter()
+                        .filter(|base| types.contains(base))
+                        .cloned()
+                        .collect(),
+                )),
+                _ => None,
+            })
+            .collect();
+        let vtables = apis
+            .iter()
+            .filter_map(|api| match &api.detail {
+                ApiDetail::Struct { item, .. } if ByValueChecker::has_vtable(item) => {
+                    Some(api.name())
+                }
+                _ => None,
+            })
+            .collect();
+        Self { bases, vtables }
+    }
+
+    /// Whether this type has a vtable, either of its own or from a base.
+    /// Only such types may be the source of a `dynamic_cast`.
+    pub(super) fn is_polymorphic(&self, ty: &QualifiedName) -> bool {
+        self.vtables.contains(ty)
+            || self
+                .bases
+                .get(ty)
+                .into_iter()
+                .flatten()
+                .any(|base| self.is_polymorphic(base))
+    }
+
+    /// Every ancestor of this type, grouped by distance from it. Ancestors
+    /// which are reachable in more than one way are ambiguous in C++, so
+    /// are omitted, as is anything we reach only through them.
+    pub(super) fn ancestors_by_depth(&self, ty: &QualifiedName) -> Vec<Vec<QualifiedName>> {
+        let mut paths = HashMap::new();
+        self.count_paths(ty, &mut paths);
+        let mut levels = Vec::new();
+        let mut current = vec![ty.clone()];
+        let mut seen = HashSet::new();
+        loop {
+            let next: Vec<_> = current
+                .iter()
+                .flat_map(|ty| self.bases.get(ty).into_iter().flatten())
+                .filter(|base| paths.get(*base) == Some(&1))
+                .filter(|base| seen.insert((*base).clone()))
+                .cloned()
+                .collect();
+            if next.is_empty() {
+                break;
+            }
+            levels.push(next.clone());
+            current = next;
+        }
+        levels
+    }
+
+    fn count_paths(&self, ty: &QualifiedName, paths: &mut HashMap<QualifiedName, usize>) {
+        for base in self.bases.get(ty).into_iter().flatten() {
+            *paths.entry(base.clone()).or_default() += 1;
+            self.count_paths(base, paths);
+        }
+    }
+}
+
+impl<'a> FnAnalyzer<'a> {
+    /// Generates functions to convert a reference to a derived type into
+    /// a reference to each of its bases. Also records the bases to which
+    /// each type can be converted, so we can implement `AsRef` later.
+    /// Those implementations call the upcast functions, so the type
+    /// depends upon them.
+    pub(super) fn make_upcasts(
+        &mut self,
+        apis: &mut [Api<FnAnalysis>],
+        hierarchy: &ClassHierarchy,
+    ) -> Vec<Api<FnAnalysis>> {
+        let mut results = Vec::new();
+        for api in apis.iter_mut() {
+            let name = api.name();
+            let details = match &mut api.detail {
+                ApiDetail::Struct { details, .. } => details,
+                _ => continue,
+            };
+            details.upcasts = hierarchy
+                .ancestors_by_depth(&name)
+                .into_iter()
+                .flatten()
+                .collect();
+            for base in &details.upcasts {
+                for mutable in [false, true].iter() {
+                    let upcast = self.make_upcast(&name, base, *mutable);
+                    api.deps.insert(upcast.name());
+                    results.push(upcast);
+                }
+            }
+        }
+        results
+    }
+
+    /// Generates functions to convert a reference to a polymorphic base
+    /// class into a reference to a derived type, if that's what it really
+    /// is. This uses the bases we found in `make_upcasts`, since the
+    /// same bases are unambiguous for `dynamic_cast`.
+    pub(super) fn make_downcasts(
+        &mut self,
+        apis: &mut [Api<FnAnalysis>],
+        hierarchy: &ClassHierarchy,
+    ) -> Vec<Api<FnAnalysis>> {
+        let mut results = Vec::new();
+        let mut bases_with_downcasts = HashSet::new();
+        for api in apis.iter_mut() {
+            let name = api.name();
+            let details = match &mut api.detail {
+                ApiDetail::Struct { details, .. } => details,
+                _ => continue,
+            };
+            for base in &details.upcasts {
+                if !hierarchy.is_polymorphic(base) {
+                    continue;
+                }
+                let downcast = self.make_downcast(base, &name, false);
+                let mut_downcast = self.make_downcast(base, &name, true);
+                details.downcasts_from.push(Downcast {
+                    base: base.clone(),
+                    cxxbridge_name: downcast.name().get_final_ident(),
+                    mut_cxxbridge_name: mut_downcast.name().get_final_ident(),
+                });
+                api.deps.insert(downcast.name());
+                api.deps.insert(mut_downcast.name());
+                results.push(downcast);
+                results.push(mut_downcast);
+                bases_with_downcasts.insert(base.clone());
+            }
+        }
+        for api in apis.iter_mut() {
+            if let ApiDetail::Struct { details, .. } = &mut api.detail {
+                details.has_downcasts = bases_with_downcasts.contains(&api.name);
+            }
+        }
+        results
+    }
+
+    /// A function which calls `dynamic_cast`, returning a pointer which
+    /// is null if the object isn't actually of the derived type. The
+    /// `DowncastFrom` implementation turns that into an `Option`.
+    fn make_downcast(
+        &mut self,
+        base: &QualifiedName,
+        derived: &QualifiedName,
+        mutable: bool,
+    ) -> Api<FnAnalysis> {
+        let ns = base.get_namespace();
+        let base_type = base.to_type_path();
+        let derived_type = derived.to_type_path();
+        let (rust_name, self_type, ret_type): (_, Type, Type) = if mutable {
+            (
+                format!("downcast_to_{}_mut", derived.get_final_item()),
+                parse_quote! { ::std::pin::Pin<&mut #base_type> },
+                parse_quote! { *mut #derived_type },
+            )
+        } else {
+            (
+                format!("downcast_to_{}", derived.get_final_item()),
+                parse_quote! { & #base_type },
+                parse_quote! { *const #derived_type },
+            )
+        };
+        let cxxbridge_name = self.get_cxx_bridge_name(Some(base.get_final_item()), &rust_name, ns);
+        let cxxbridge_name = Self::make_wrapper_name(&make_ident(&cxxbridge_name));
+        let self_conversion = TypeConversionPolicy::new_unconverted(self_type.clone());
+        let cpp_wrapper = FunctionWrapper {
+            payload: FunctionWrapperPayload::Downcast,
+            wrapper_function_name: cxxbridge_name.clone(),
+            return_conversion: Some(TypeConversionPolicy::new_unconverted(ret_type.clone())),
+            argument_conversion: vec![self_conversion.clone()],
+            is_a_method: true,
+        };
+        let mut params = Punctuated::new();
+        params.push(parse_quote! { autocxx_gen_this: #self_type });
+        let deps = [base.clone(), derived.clone()].iter().cloned().collect();
+        Api {
+            name: QualifiedName::new(ns, cxxbridge_name.clone()),
+            original_name: None,
+            deps,
+            detail: ApiDetail::Function {
+                fun: Box::new(FuncToConvert {
+                    item: parse_quote! {
+                        fn #cxxbridge_name();
+                    },
+                    virtual_this_type: None,
+                    self_ty: Some(base.clone()),
+                    is_static_accessor: false,
+                    default_args_suffix: None,
+                }),
+                analysis: FnAnalysisBody {
+                    // No Rust wrapper method is generated, because the
+                    // Rust name is the same as the cxx::bridge name.
+                    rust_name: cxxbridge_name.to_string(),
+                    cxxbridge_name,
+                    rust_rename_strategy: RustRenameStrategy::None,
+                    params,
+                    kind: FnKind::Method(base.clone(), MethodKind::Normal),
+                    ret_type: parse_quote! { -> #ret_type },
+                    param_details: vec![ArgumentAnalysis {
+                        conversion: self_conversion,
+                        name: parse_quote!(self),
+                        self_type: Some(base.clone()),
+                        was_reference: true,
+                        deps: HashSet::new(),
+                        is_virtual: false,
+                        requires_unsafe: false,
+                    }],
+                    cpp_call_name: rust_name,
+                    requires_unsafe: false,
+                    vis: parse_quote!(pub),
+                    cpp_wrapper: Some(AdditionalNeed::FunctionWrapper(Box::new(cpp_wrapper))),
+                    output_lifetime: None,
+                    result_status: None,
+                },
+            },
+        }
+    }
+
+    fn make_upcast(
+        &mut self,
+        derived: &QualifiedName,
+        base: &QualifiedName,
+        mutable: bool,
+    ) -> Api<FnAnalysis> {
+        let ns = derived.get_namespace();
+        let derived_type = derived.to_type_path();
+        let base_type = base.to_type_path();
+        let (rust_name, self_type, ret_type): (_, Type, Type) = if mutable {
+            (
+                format!("as_{}_mut", base.get_final_item()),
+                parse_quote! { ::std::pin::Pin<&mut #derived_type> },
+                parse_quote! { ::std::pin::Pin<&mut #base_type> },
+            )
+        } else {
+            (
+                format!("as_{}", base.get_final_item()),
+                parse_quote! { & #derived_type },
+                parse_quote! { & #base_type },
+            )
+        };
+        let doc = format!(
+            "Converts to a reference to the base class `{}`.",
+            base.get_final_item()
+        );
+        let cxxbridge_name =
+            self.get_cxx_bridge_name(Some(derived.get_final_item()), &rust_name, ns);
+        let cxxbridge_name = Self::make_wrapper_name(&make_ident(&cxxbridge_name));
+        let self_conversion = TypeConversionPolicy::new_unconverted(self_type.clone());
+        let cpp_wrapper = FunctionWrapper {
+            payload: FunctionWrapperPayload::Upcast,
+            wrapper_function_name: cxxbridge_name.clone(),
+            return_conversion: Some(TypeConversionPolicy::new_unconverted(ret_type.clone())),
+            argument_conversion: vec![self_conversion.clone()],
+            is_a_method: true,
+        };
+        let mut params = Punctuated::new();
+        params.push(parse_quote! { autocxx_gen_this: #self_type });
+        let cpp_call_name = rust_name.clone();
+        let deps = [derived.clone(), base.clone()].iter().cloned().collect();
+        Api {
+            name: QualifiedName::new(ns, cxxbridge_name.clone()),
+            original_name: None,
+            deps,
+            detail: ApiDetail::Function {
+                fun: Box::new(FuncToConvert {
+                    item: parse_quote! {
+                        #[doc = #doc]
+                        fn #cxxbridge_name();
+                    },
+                    virtual_this_type: None,
+                    self_ty: Some(derived.clone()),
+                    is_static_accessor: false,
+                    default_args_suffix: None,
+                }),
+                analysis: FnAnalysisBody {
+                    cxxbridge_name,
+                    rust_name,
+                    rust_rename_strategy: RustRenameStrategy::None,
+                    params,
+                    kind: FnKind::Method(derived.clone(), MethodKind::Normal),
+                    ret_type: parse_quote! { -> #ret_type },
+                    param_details: vec![ArgumentAnalysis {
+                        conversion: self_conversion,
+                        name: parse_quote!(self),
+                        self_type: Some(derived.clone()),
+                        was_reference: true,
+                        deps: HashSet::new(),
+                        is_virtual: false,
+                        requires_unsafe: false,
+                    }],
+                    cpp_call_name,
+                    requires_unsafe: false,
+                    vis: parse_quote!(pub),
+                    cpp_wrapper: Some(AdditionalNeed::FunctionWrapper(Box::new(cpp_wrapper))),
+                    output_lifetime: None,
+                    result_status: None,
+                },
+            },
+        }
+    }
+
+    /// Makes the methods of each type's base classes callable on the
+    /// derived type, unless they're hidden by a method of the same name
+    /// in a class further down the hierarchy.
+    pub(super) fn make_inherited_methods(
+        &mut self,
+        apis: &[Api<FnAnalysis>],
+        hierarchy: &ClassHierarchy,
+    ) -> Vec<Api<FnAnalysis>> {
+        let mut methods_by_type: HashMap<&QualifiedName, Vec<&Api<FnAnalysis>>> = HashMap::new();
+        for api in apis {
+            if let ApiDetail::Function {
+                analysis:
+                    FnAnalysisBody {
+                        kind: FnKind::Method(self_ty, _),
+                        ..
+                    },
+                ..
+            } = &api.detail
+            {
+                methods_by_type.entry(self_ty).or_default().push(api);
+            }
+        }
+        let mut results = Vec::new();
+        for api in apis {
+            if !matches!(api.detail, ApiDetail::Struct { .. }) {
+                continue;
+            }
+            let derived = api.name();
+            let own_methods = methods_by_type.get(&derived).cloned().unwrap_or_default();
+            let mut hidden: HashSet<String> = own_methods
+                .iter()
+                .filter_map(|api| Self::method_analysis(api))
+                .map(|analysis| analysis.cpp_call_name.clone())
+                .collect();
+            let mut rust_names: HashSet<String> = own_methods
+                .iter()
+                .filter_map(|api| Self::method_analysis(api))
+                .map(|analysis| analysis.rust_name.clone())
+                .collect();
+            for level in hierarchy.ancestors_by_depth(&derived) {
+                // Names declared in more than one class at the same level
+                // are ambiguous in C++, unless they're hidden anyway.
+                let mut names_at_level: HashMap<&str, HashSet<&QualifiedName>> = HashMap::new();
+                let inheritable: Vec<_> = level
+                    .iter()
+                    .flat_map(|base| methods_by_type.get(base).into_iter().flatten())
+                    .filter_map(|api| Self::method_analysis(api).map(|analysis| (api, analysis)))
+                    .filter(|(_, analysis)| {
+                        matches!(
+                            analysis.kind,
+                            FnKind::Method(_, MethodKind::Normal)
+                                | FnKind::Method(_, MethodKind::Virtual)
+                                | FnKind::Method(_, MethodKind::PureVirtual)
+                        )
+                    })
+                    .collect();
+                for (_, analysis) in &inheritable {
+                    if let FnKind::Method(base, _) = &analysis.kind {
+                        names_at_level
+                            .entry(&analysis.cpp_call_name)
+                            .or_default()
+                            .insert(base);
+                    }
+                }
+                for (api, analysis) in &inheritable {
+                    if hidden.contains(&analysis.cpp_call_name)
+                        || names_at_level[analysis.cpp_call_name.as_str()].len() > 1
+                        || !rust_names.insert(analysis.rust_name.clone())
+                    {
+                        continue;
+                    }
+                    results.push(self.make_inherited_method(api, analysis, &derived));
+                }
+                hidden.extend(names_at_level.keys().map(|name| name.to_string()));
+            }
+        }
+        results
+    }
+
+    fn method_analysis(api: &Api<FnAnalysis>) -> Option<&FnAnalysisBody> {
+        match &api.detail {
+            ApiDetail::Function { analysis, .. } => Some(analysis),
+            _ => None,
+        }
+    }
+
+    /// A copy of a method of a base class, but with the derived type
+    /// as its receiver. We always call it via a C++ wrapper, in which
+    /// the C++ compiler finds the base class method for us.
+    fn make_inherited_method(
+        &mut self,
+        api: &Api<FnAnalysis>,
+        analysis: &FnAnalysisBody,
+        derived: &QualifiedName,
+    ) -> Api<FnAnalysis> {
+        let fun = match &api.detail {
+            ApiDetail::Function { fun, .. } => fun.clone(),
+            _ => unreachable!(),
+        };
+        let (base, kind) = match &analysis.kind {
+            FnKind::Method(base, MethodKind::Virtual) => (base, MethodKind::Virtual),
+            FnKind::Method(base, MethodKind::PureVirtual) => (base, MethodKind::PureVirtual),
+            FnKind::Method(base, _) => (base, MethodKind::Normal),
+            FnKind::Function => unreachable!(),
+        };
+        let ns = derived.get_namespace();
+        let rust_name = analysis.rust_name.clone();
+        let cxxbridge_name =
+            self.get_cxx_bridge_name(Some(derived.get_final_item()), &rust_name, ns);
+        let cxxbridge_name = Self::make_wrapper_name(&make_ident(&cxxbridge_name));
+        let param_details: Vec<_> = analysis
+            .param_details
+            .iter()
+            .map(|pd| {
+                if pd.self_type.is_some() {
+                    ArgumentAnalysis {
+                        conversion: TypeConversionPolicy {
+                            unwrapped_type: replace_type(
+                                &pd.conversion.unwrapped_type,
+                                base,
+                                derived,
+                            ),
+                            ..pd.conversion.clone()
+                        },
+                        self_type: Some(derived.clone()),
+                        ..pd.clone()
+                    }
+                } else {
+                    pd.clone()
+                }
+            })
+            .collect();
+        let params: Punctuated<FnArg, Token![,]> = param_details
+            .iter()
+            .map(|pd| -> FnArg {
+                let type_name = pd.conversion.converted_rust_type();
+                let arg_name = if pd.self_type.is_some() {
+                    parse_quote!(autocxx_gen_this)
+                } else {
+                    pd.name.clone()
+                };
+                parse_quote!(#arg_name: #type_name)
+            })
+            .collect();
+        let return_conversion = match &analysis.cpp_wrapper {
+            Some(AdditionalNeed::FunctionWrapper(wrapper)) => wrapper.return_conversion.clone(),
+            _ => match &analysis.ret_type {
+                ReturnType::Type(_, ty) => {
+                    Some(TypeConversionPolicy::new_unconverted(ty.as_ref().clone()))
+                }
+                ReturnType::Default => None,
+            },
+        };
+        let cpp_wrapper = FunctionWrapper {
+            payload: FunctionWrapperPayload::FunctionCall(
+                ns.clone(),
+                make_ident(&analysis.cpp_call_name),
+            ),
+            wrapper_function_name: cxxbridge_name.clone(),
+            return_conversion,
+            argument_conversion: param_details
+                .iter()
+                .map(|pd| pd.conversion.clone())
+                .collect(),
+            is_a_method: true,
+        };
+        let mut deps = api.deps.clone();
+        deps.insert(derived.clone());
+        Api {
+            name: QualifiedName::new(ns, cxxbridge_name.clone()),
+            original_name: None,
+            deps,
+            detail: ApiDetail::Function {
+                fun,
+                analysis: FnAnalysisBody {
+                    cxxbridge_name,
+                    rust_name,
+                    rust_rename_strategy: RustRenameStrategy::None,
+                    params,
+                    kind: FnKind::Method(derived.clone(), kind),
+                    ret_type: analysis.ret_type.clone(),
+                    param_details,
+                    cpp_call_name: analysis.cpp_call_name.clone(),
+                    requires_unsafe: analysis.requires_unsafe,
+                    vis: analysis.vis.clone(),
+                    cpp_wrapper: Some(AdditionalNeed::FunctionWrapper(Box::new(cpp_wrapper))),
+                    output_lifetime: analysis.output_lifetime.clone(),
+                    result_status: analysis.result_status.clone(),
+                },
+            },
+        }
+    }
+}
+
+/// Replaces any mention of one type with another, for example within
+/// `Pin<&mut A>`.
+fn replace_type(ty: &Type, from: &QualifiedName, to: &QualifiedName) -> Type {
+    match ty {
+        Type::Path(typ) if typ.qself.is_none() => {
+            let tn = QualifiedName::from_type_path(typ);
+            if &tn == from && typ.path.segments.last().unwrap().arguments.is_empty() {
+                return Type::Path(to.to_type_path());
+            }
+            let mut typ = typ.clone();
+            for seg in typ.path.segments.iter_mut() {
+                if let PathArguments::AngleBracketed(ab) = &mut seg.arguments {
+                    for arg in ab.args.iter_mut() {
+                        if let GenericArgument::Type(inner) = arg {
+                            *inner = replace_type(inner, from, to);
+                        }
+                    }
+                }
+            }
+            Type::Path(typ)
+        }
+        Type::Reference(r) => {
+            let mut r = r.clone();
+            *r.elem = replace_type(&r.elem, from, to);
+            Type::Reference(r)
+        }
+        Type::Ptr(p) => {
+            let mut p = p.clone();
+            *p.elem = replace_type(&p.elem, from, to);
+            Type::Ptr(p)
+        }
+        _ => ty.clone(),
+    }
+}
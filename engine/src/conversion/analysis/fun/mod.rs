@@ -14,6 +14,7 @@
 
 mod bridge_name_tracker;
 pub(crate) mod function_wrapper;
+mod inheritance;
 mod overload_tracker;
 mod rust_name_tracker;
 
@@ -32,26 +33,36 @@ use crate::{
 };
 use std::collections::{HashMap, HashSet};
 
-use autocxx_parser::{IncludeCppConfig, UnsafePolicy};
-use function_wrapper::{FunctionWrapper, FunctionWrapperPayload, TypeConversionPolicy};
+use autocxx_parser::{IncludeCppConfig, PointerPolicy, UnsafePolicy};
+use function_wrapper::{
+    out_param_pointee, FunctionWrapper, FunctionWrapperPayload, RustConversionType,
+    TypeConversionPolicy,
+};
+use itertools::Itertools;
 use proc_macro2::Span;
+use quote::ToTokens;
 use syn::{
-    parse_quote, punctuated::Punctuated, FnArg, ForeignItemFn, Ident, LitStr, Pat, ReturnType,
-    Type, TypePtr, Visibility,
+    parse_quote, punctuated::Punctuated, FnArg, ForeignItemFn, GenericArgument, Ident, Lifetime,
+    LitStr, Pat, PathArguments, ReturnType, Type, TypePtr, Visibility,
 };
 
 use crate::{
     conversion::{
         api::{AnalysisPhase, Api, ApiDetail, FuncToConvert, TypeKind, UnanalyzedApi},
-        codegen_cpp::AdditionalNeed,
+        codegen_cpp::{
+            type_to_cpp::{original_name_map_from_apis, type_to_cpp, OriginalNameMap},
+            AdditionalNeed,
+        },
         ConvertError,
     },
     types::{make_ident, validate_ident_ok_for_cxx, Namespace, QualifiedName},
 };
 
 use self::{
-    bridge_name_tracker::BridgeNameTracker, overload_tracker::OverloadTracker,
-    rust_name_tracker::RustNameTracker,
+    bridge_name_tracker::BridgeNameTracker,
+    inheritance::ClassHierarchy,
+    overload_tracker::{signature_suffix, OverloadTracker},
+    rust_name_tracker::{to_snake_case, RustNameTracker},
 };
 
 use super::pod::PodAnalysis;
@@ -93,8 +104,50 @@ pub(crate) struct FnAnalysisBody {
     pub(crate) requires_unsafe: bool,
     pub(crate) vis: Visibility,
     pub(crate) cpp_wrapper: Option<AdditionalNeed>,
+    /// A lifetime shared by the returned reference and the parameter which
+    /// it borrows from, where Rust couldn't otherwise deduce it.
+    pub(crate) output_lifetime: Option<Lifetime>,
+    /// Set if out parameters should be returned as a `Result`, depending
+    /// on the status returned by the function.
+    pub(crate) result_status: Option<StatusCheck>,
+}
+
+/// How to tell whether the status returned by a function with out
+/// parameters indicates success.
+#[derive(Clone)]
+pub(crate) enum StatusCheck {
+    /// `true` means success.
+    True,
+    /// Zero means success.
+    Zero,
+    /// Zero means success, within one of our C integer newtypes.
+    ZeroInNewtype,
+}
+
+impl StatusCheck {
+    fn for_return_type(ret_type: &ReturnType) -> Option<Self> {
+        let typ = match ret_type {
+            ReturnType::Type(_, ty) => match ty.as_ref() {
+                Type::Path(typ) => typ,
+                _ => return None,
+            },
+            ReturnType::Default => return None,
+        };
+        let ident = typ.path.segments.last()?.ident.to_string();
+        match ident.as_str() {
+            "bool" => Some(StatusCheck::True),
+            "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" => {
+                Some(StatusCheck::Zero)
+            }
+            _ if known_types().is_c_integer_newtype(&QualifiedName::from_type_path(typ)) => {
+                Some(StatusCheck::ZeroInNewtype)
+            }
+            _ => None,
+        }
+    }
 }
 
+#[derive(Clone)]
 pub(crate) struct ArgumentAnalysis {
     pub(crate) conversion: TypeConversionPolicy,
     pub(crate) name: Pat,
@@ -129,9 +182,15 @@ pub(crate) struct FnAnalyzer<'a> {
     pod_safe_types: HashSet<QualifiedName>,
     config: &'a IncludeCppConfig,
     overload_trackers_by_mod: HashMap<Namespace, OverloadTracker>,
+    overloaded_names: HashSet<OverloadKey>,
+    mut_overloads: HashSet<(OverloadKey, String)>,
+    original_name_map: OriginalNameMap,
     generate_utilities: bool,
 }
 
+/// The namespace, type (for methods) and C++ name shared by overloads.
+type OverloadKey = (Namespace, Option<QualifiedName>, String);
+
 struct FnAnalysisResult(FnAnalysisBody, Ident, HashSet<QualifiedName>);
 
 impl<'a> FnAnalyzer<'a> {
@@ -148,15 +207,45 @@ impl<'a> FnAnalyzer<'a> {
             bridge_name_tracker: BridgeNameTracker::new(),
             config,
             overload_trackers_by_mod: HashMap::new(),
+            overloaded_names: Self::find_overloaded_names(&apis),
+            mut_overloads: Self::find_mut_overloads(&apis),
+            original_name_map: original_name_map_from_apis(&apis),
             pod_safe_types: Self::build_pod_safe_type_set(&apis),
             generate_utilities: Self::should_generate_utilities(&apis),
         };
         let mut results = Vec::new();
         convert_apis(apis, &mut results, |api| me.analyze_fn_api(api));
+        let shared_constructors: Vec<_> = results
+            .iter()
+            .filter_map(|api| me.make_shared_constructor(api))
+            .collect();
+        results.extend(shared_constructors);
+        let storage_types = Self::build_storage_type_set(&results);
+        let emplace_constructors: Vec<_> = results
+            .iter()
+            .filter_map(|api| me.make_emplace_constructor(api, &storage_types))
+            .collect();
+        results.extend(emplace_constructors);
+        let hierarchy = ClassHierarchy::new(&results);
+        let inherited_methods = me.make_inherited_methods(&results, &hierarchy);
+        let upcasts = me.make_upcasts(&mut results, &hierarchy);
+        let downcasts = me.make_downcasts(&mut results, &hierarchy);
+        results.extend(inherited_methods);
+        results.extend(upcasts);
+        results.extend(downcasts);
         results.extend(me.extra_apis.into_iter().map(add_analysis));
         results
     }
 
+    fn build_storage_type_set(apis: &[Api<FnAnalysis>]) -> HashSet<QualifiedName> {
+        apis.iter()
+            .filter_map(|api| match &api.detail {
+                ApiDetail::Struct { details, .. } if details.has_storage => Some(api.name()),
+                _ => None,
+            })
+            .collect()
+    }
+
     fn should_generate_utilities(apis: &[Api<PodAnalysis>]) -> bool {
         apis.iter()
             .any(|api| matches!(api.detail, ApiDetail::StringConstructor))
@@ -166,8 +255,12 @@ impl<'a> FnAnalyzer<'a> {
         apis.iter()
             .filter_map(|api| match api.detail {
                 ApiDetail::Struct {
-                    item: _,
                     analysis: TypeKind::Pod,
+                    ..
+                } => Some(api.name()),
+                ApiDetail::Union {
+                    analysis: TypeKind::Pod,
+                    ..
                 } => Some(api.name()),
                 ApiDetail::Enum {
                     item: _,
@@ -226,7 +319,25 @@ impl<'a> FnAnalyzer<'a> {
             ApiDetail::Typedef { item, analysis } => ApiDetail::Typedef { item, analysis },
             ApiDetail::CType { typename } => ApiDetail::CType { typename },
             ApiDetail::Enum { item, analysis } => ApiDetail::Enum { item, analysis },
-            ApiDetail::Struct { item, analysis } => ApiDetail::Struct { item, analysis },
+            ApiDetail::Struct {
+                item,
+                details,
+                analysis,
+            } => ApiDetail::Struct {
+                item,
+                details,
+                analysis,
+            },
+            ApiDetail::Union {
+                item,
+                details,
+                analysis,
+            } => ApiDetail::Union {
+                item,
+                details,
+                analysis,
+            },
+            ApiDetail::BitfieldUnit { items } => ApiDetail::BitfieldUnit { items },
             ApiDetail::ForwardDeclaration => ApiDetail::ForwardDeclaration,
             ApiDetail::IgnoredItem { err, ctx } => ApiDetail::IgnoredItem { err, ctx },
         };
@@ -277,8 +388,13 @@ impl<'a> FnAnalyzer<'a> {
         self.config.is_on_allowlist(&type_name.to_cpp_name())
     }
 
-    fn should_be_unsafe(&self) -> bool {
-        self.unsafe_policy == UnsafePolicy::AllFunctionsUnsafe
+    /// Whether a function should be `unsafe`, either because of a `safe!`
+    /// or `unsafe_fn!` directive, or else the overall policy.
+    fn should_be_unsafe(&self, qualified_cpp_name: &str) -> bool {
+        match self.config.safety_override_for(qualified_cpp_name) {
+            Some(is_safe) => !is_safe,
+            None => self.unsafe_policy == UnsafePolicy::AllFunctionsUnsafe,
+        }
     }
 
     /// Determine how to materialize a function.
@@ -304,6 +420,7 @@ impl<'a> FnAnalyzer<'a> {
     ) -> Result<Option<FnAnalysisResult>, ConvertErrorWithContext> {
         let fun = &func_information.item;
         let virtual_this = &func_information.virtual_this_type;
+        let is_static_accessor = func_information.is_static_accessor;
 
         // Let's gather some pre-wisdom about the name of the function.
         // We're shortly going to plunge into analyzing the parameters,
@@ -346,9 +463,6 @@ impl<'a> FnAnalyzer<'a> {
             .next()
             .cloned();
 
-        let requires_unsafe =
-            self.should_be_unsafe() || param_details.iter().any(|pd| pd.requires_unsafe);
-
         // End of parameter processing.
         // Work out naming, part one.
         let mut rust_name;
@@ -365,6 +479,12 @@ impl<'a> FnAnalyzer<'a> {
         } else {
             cpp_call_name.clone()
         };
+        // Functions which we generated to evaluate macros should be
+        // named after the macro.
+        let ideal_rust_name = match self.config.macro_fn_for_wrapper(&ideal_rust_name) {
+            Some(macro_fn) => macro_fn.name.clone(),
+            None => ideal_rust_name,
+        };
 
         // Let's spend some time figuring out the kind of this function (i.e. method,
         // virtual function, etc.)
@@ -376,6 +496,15 @@ impl<'a> FnAnalyzer<'a> {
         } else {
             (false, self_ty)
         };
+        let qualified_cpp_name = Self::get_qualified_cpp_name(ns, self_ty.as_ref(), &cpp_call_name);
+        let ideal_rust_name = self.choose_ideal_rust_name(
+            ns,
+            func_information,
+            &param_details,
+            self_ty.as_ref(),
+            &cpp_call_name,
+            ideal_rust_name,
+        );
 
         let (kind, error_context) = if let Some(self_ty) = self_ty {
             // Some kind of method.
@@ -396,7 +525,7 @@ impl<'a> FnAnalyzer<'a> {
             // strip off the class name.
             let overload_tracker = self.overload_trackers_by_mod.entry(ns.clone()).or_default();
             rust_name = overload_tracker.get_method_real_name(&type_ident, ideal_rust_name);
-            let method_kind = if rust_name.starts_with(&type_ident) {
+            let method_kind = if !is_static_accessor && rust_name.starts_with(&type_ident) {
                 // It's a constructor. bindgen generates
                 // fn new(this: *Type, ...args)
                 // We want
@@ -474,6 +603,20 @@ impl<'a> FnAnalyzer<'a> {
             ));
         }
 
+        if let Err(err) = self.apply_pointer_policies(&qualified_cpp_name, &mut param_details) {
+            return Err(contextualize_error(err));
+        }
+        if let Err(err) = self.apply_out_params(&qualified_cpp_name, &mut param_details) {
+            return Err(contextualize_error(err));
+        }
+
+        // Anyone may be modifying a mutable global at any time.
+        let is_mutable_static = is_static_accessor
+            && matches!(&fun.sig.output, ReturnType::Type(_, ty) if matches!(ty.as_ref(), Type::Ptr(ptr) if ptr.mutability.is_some()));
+        let requires_unsafe = self.should_be_unsafe(&qualified_cpp_name)
+            || is_mutable_static
+            || param_details.iter().any(|pd| pd.requires_unsafe);
+
         // Analyze the return type, just as we previously did for the
         // parameters.
         let mut return_analysis = if let FnKind::Method(ref self_ty, MethodKind::Constructor) = kind
@@ -502,17 +645,72 @@ impl<'a> FnAnalyzer<'a> {
         };
         let mut deps = params_deps;
         deps.extend(return_analysis.deps.drain());
+        let mut output_lifetime = None;
 
-        if return_analysis.was_reference {
+        if is_static_accessor {
+            // The variable lives forever, so we can return a reference
+            // to it irrespective of our parameters (of which there are none).
+            // We need the C++ accessor to return the reference itself,
+            // never a copy of the variable within a UniquePtr.
+            if let ReturnType::Type(_, ref mut ty) = return_analysis.rt {
+                **ty = make_static_reference(ty);
+                return_analysis.conversion =
+                    Some(TypeConversionPolicy::new_unconverted(*ty.clone()));
+            }
+        } else if return_analysis.was_reference {
             // cxx only allows functions to return a reference if they take exactly
-            // one reference as a parameter. Let's see...
+            // one reference as a parameter, unless we spell out which parameter
+            // the result borrows from using a lifetime. Let's see...
             let num_input_references = param_details.iter().filter(|pd| pd.was_reference).count();
             if num_input_references != 1 {
-                return Err(contextualize_error(ConvertError::NotOneInputReference(
-                    rust_name,
-                )));
+                let borrowed_param = match self.config.borrows_from(&qualified_cpp_name) {
+                    Some(param_name) => param_details.iter().position(|pd| {
+                        pd.name.to_token_stream().to_string() == param_name
+                            && pd.self_type.is_none()
+                    }),
+                    // Methods' results usually borrow from the object.
+                    None => param_details.iter().position(|pd| pd.self_type.is_some()),
+                };
+                let borrowed_param = match borrowed_param {
+                    Some(borrowed_param) => borrowed_param,
+                    None => {
+                        return Err(contextualize_error(ConvertError::NotOneInputReference(
+                            rust_name,
+                        )))
+                    }
+                };
+                let lifetime: Lifetime = parse_quote! { 'a };
+                let mut lifetime_added = add_lifetime(
+                    &mut param_details[borrowed_param].conversion.unwrapped_type,
+                    &lifetime,
+                );
+                if let Some(FnArg::Typed(pt)) = params.iter_mut().nth(borrowed_param) {
+                    lifetime_added &= add_lifetime(&mut pt.ty, &lifetime);
+                }
+                if let ReturnType::Type(_, ref mut ty) = return_analysis.rt {
+                    lifetime_added &= add_lifetime(ty, &lifetime);
+                }
+                if let Some(ref mut conversion) = return_analysis.conversion {
+                    lifetime_added &= add_lifetime(&mut conversion.unwrapped_type, &lifetime);
+                }
+                if !lifetime_added {
+                    return Err(contextualize_error(ConvertError::NotOneInputReference(
+                        rust_name,
+                    )));
+                }
+                output_lifetime = Some(lifetime);
             }
         }
+        let result_status = if self.config.out_params_as_result(&qualified_cpp_name)
+            && param_details.iter().any(|pd| pd.conversion.is_out_param())
+        {
+            match StatusCheck::for_return_type(&return_analysis.rt) {
+                Some(status_check) => Some(status_check),
+                None => return Err(contextualize_error(ConvertError::UnsuitableStatusType)),
+            }
+        } else {
+            None
+        };
         let mut ret_type = return_analysis.rt;
         let ret_type_conversion = return_analysis.conversion;
 
@@ -527,10 +725,21 @@ impl<'a> FnAnalyzer<'a> {
         // That wrapper function is included in the cxx::bridge, and calls through to the
         // original function.
         let wrapper_function_needed = match kind {
+            _ if is_static_accessor => true,
+            _ if func_information.default_args_suffix.is_some() => true,
             FnKind::Method(_, MethodKind::Static)
             | FnKind::Method(_, MethodKind::Virtual)
             | FnKind::Method(_, MethodKind::PureVirtual) => true,
             FnKind::Method(..) if cxxbridge_name != rust_name => true,
+            // Methods with Rust wrappers mustn't clash with the methods
+            // which cxx generates.
+            FnKind::Method(..)
+                if param_details
+                    .iter()
+                    .any(|pd| pd.conversion.rust_work_needed()) =>
+            {
+                true
+            }
             _ if param_conversion_needed => true,
             _ if ret_type_conversion_needed => true,
             _ => false,
@@ -540,13 +749,19 @@ impl<'a> FnAnalyzer<'a> {
             // Generate a new layer of C++ code to wrap/unwrap parameters
             // and return values into/out of std::unique_ptrs.
             let cpp_construction_ident = make_ident(&cpp_call_name);
-            let joiner = if cxxbridge_name.to_string().ends_with('_') {
-                ""
-            } else {
-                "_"
-            };
-            cxxbridge_name = make_ident(&format!("{}{}autocxx_wrapper", cxxbridge_name, joiner));
+            cxxbridge_name = Self::make_wrapper_name(&cxxbridge_name);
             let (payload, has_receiver) = match kind {
+                _ if is_static_accessor => (
+                    FunctionWrapperPayload::StaticVariable(
+                        ns.clone(),
+                        match kind {
+                            FnKind::Method(ref self_ty, _) => Some(self_ty.get_final_ident()),
+                            FnKind::Function => None,
+                        },
+                        cpp_construction_ident,
+                    ),
+                    false,
+                ),
                 FnKind::Method(_, MethodKind::Constructor) => {
                     (FunctionWrapperPayload::Constructor, false)
                 }
@@ -642,12 +857,469 @@ impl<'a> FnAnalyzer<'a> {
                 requires_unsafe,
                 vis,
                 cpp_wrapper,
+                output_lifetime,
+                result_status,
             },
             id,
             deps,
         )))
     }
 
+    /// Names a function as requested by any `rename!` directive. Otherwise,
+    /// method names become `snake_case` if `snake_case_methods!` is in
+    /// effect. Then, if `overloads_by_signature!` is in effect, overloads
+    /// are named after their parameter types, and variants which omit
+    /// default arguments are treated as overloads. Or, those variants are
+    /// named after the last parameter they take. Any remaining clashes are
+    /// resolved later by numbering.
+    fn choose_ideal_rust_name(
+        &self,
+        ns: &Namespace,
+        func_information: &FuncToConvert,
+        param_details: &[ArgumentAnalysis],
+        self_ty: Option<&QualifiedName>,
+        cpp_call_name: &str,
+        ideal_rust_name: String,
+    ) -> String {
+        let cpp_param_types: Vec<_> = param_details
+            .iter()
+            .filter(|pd| pd.self_type.is_none())
+            .map(|pd| {
+                type_to_cpp(&pd.conversion.unwrapped_type, &self.original_name_map)
+                    .unwrap_or_default()
+            })
+            .collect();
+        let is_constructor = !func_information.is_static_accessor
+            && matches!(self_ty, Some(ty) if ty.get_final_item() == cpp_call_name);
+        let is_overloaded = self.overloaded_names.contains(&(
+            ns.clone(),
+            func_information.self_ty.clone(),
+            cpp_call_name.to_string(),
+        ));
+        // Constructors must keep their C++ names for now, such that we can
+        // recognize them as constructors.
+        let ideal_rust_name =
+            if self.config.snake_case_methods() && self_ty.is_some() && !is_constructor {
+                to_snake_case(&ideal_rust_name)
+            } else {
+                ideal_rust_name
+            };
+        let default_args_suffix = &func_information.default_args_suffix;
+        let qualified_cpp_name = Self::get_qualified_cpp_name(ns, self_ty, cpp_call_name);
+        // Non-const methods with const equivalents are named like
+        // Rust's get/get_mut.
+        let is_mut_overload = self.mut_overloads.contains(&(
+            (
+                ns.clone(),
+                func_information.self_ty.clone(),
+                cpp_call_name.to_string(),
+            ),
+            Self::overload_signature(&func_information.item),
+        ));
+        let name = match self
+            .config
+            .rename_for(&qualified_cpp_name, &cpp_param_types)
+        {
+            // Constructors must still be named such that we recognize them
+            // as constructors, and they become make_unique_{name}.
+            Some(name) if default_args_suffix.is_none() && is_constructor => {
+                return format!("{}_{}", cpp_call_name, name)
+            }
+            // A rename gives the exact name wanted, so no _mut suffix.
+            Some(name) if default_args_suffix.is_none() => return name.to_string(),
+            _ if self.config.overloads_by_signature() && is_overloaded => {
+                format!("{}{}", ideal_rust_name, signature_suffix(&cpp_param_types))
+            }
+            _ => match default_args_suffix {
+                Some(suffix) => format!("{}{}", ideal_rust_name, suffix),
+                None => ideal_rust_name,
+            },
+        };
+        if is_mut_overload {
+            format!("{}_mut", name)
+        } else {
+            name
+        }
+    }
+
+    /// Presents raw pointer parameters as requested by `pointer_param!`
+    /// directives. Such parameters no longer make the function `unsafe`,
+    /// because the Rust wrapper function only gives C++ valid pointers.
+    fn apply_pointer_policies(
+        &self,
+        qualified_cpp_name: &str,
+        param_details: &mut [ArgumentAnalysis],
+    ) -> Result<(), ConvertError> {
+        for idx in 0..param_details.len() {
+            let param_name = param_details[idx].name.to_token_stream().to_string();
+            let policy = match self.config.pointer_policy(qualified_cpp_name, &param_name) {
+                Some(policy) => policy,
+                None => continue,
+            };
+            let invalid = || ConvertError::InvalidPointerParam(param_name.clone());
+            let param_pat = param_details[idx].name.clone();
+            let ty = param_details[idx].conversion.unwrapped_type.clone();
+            let (is_mut, is_pod) = match &ty {
+                Type::Ptr(ptr) => match ptr.elem.as_ref() {
+                    Type::Path(typ) => (
+                        ptr.mutability.is_some(),
+                        self.pod_safe_types
+                            .contains(&QualifiedName::from_type_path(typ)),
+                    ),
+                    _ => return Err(invalid()),
+                },
+                _ => return Err(invalid()),
+            };
+            let rust_conversion = match policy {
+                PointerPolicy::Nullable => RustConversionType::FromOptionalReference,
+                PointerPolicy::Slice { length_param } if is_pod => {
+                    let length_pd = param_details
+                        .iter_mut()
+                        .find(|pd| {
+                            pd.name.to_token_stream().to_string() == *length_param
+                                && matches!(pd.conversion.unwrapped_type, Type::Path(_))
+                        })
+                        .ok_or_else(invalid)?;
+                    length_pd.conversion = TypeConversionPolicy::new_for_pointer(
+                        length_pd.conversion.unwrapped_type.clone(),
+                        RustConversionType::FromSliceLength(param_pat),
+                    );
+                    RustConversionType::FromSlice
+                }
+                PointerPolicy::Out if is_mut => {
+                    let pd = &mut param_details[idx];
+                    pd.conversion = self.out_param_conversion(&ty).ok_or_else(invalid)?;
+                    pd.requires_unsafe = false;
+                    continue;
+                }
+                _ => return Err(invalid()),
+            };
+            let pd = &mut param_details[idx];
+            pd.conversion = TypeConversionPolicy::new_for_pointer(ty, rust_conversion);
+            pd.requires_unsafe = false;
+        }
+        Ok(())
+    }
+
+    /// Turns parameters through which C++ returns values into out
+    /// parameters, if they're named by `out_param!` or, when
+    /// `detect_out_params!` is used, if they're non-const references named
+    /// `out_*`.
+    fn apply_out_params(
+        &self,
+        qualified_cpp_name: &str,
+        param_details: &mut [ArgumentAnalysis],
+    ) -> Result<(), ConvertError> {
+        for pd in param_details.iter_mut().filter(|pd| pd.self_type.is_none()) {
+            let param_name = pd.name.to_token_stream().to_string();
+            let conversion = if self.config.is_out_param(qualified_cpp_name, &param_name) {
+                self.out_param_conversion(&pd.conversion.unwrapped_type)
+                    .ok_or_else(|| ConvertError::InvalidOutParam(param_name.clone()))?
+            } else if self.config.detect_out_params()
+                && param_name.starts_with("out_")
+                && matches!(
+                    out_param_pointee(&pd.conversion.unwrapped_type),
+                    Some((_, false))
+                )
+            {
+                match self.out_param_conversion(&pd.conversion.unwrapped_type) {
+                    Some(conversion) => conversion,
+                    None => continue,
+                }
+            } else {
+                continue;
+            };
+            pd.conversion = conversion;
+            pd.requires_unsafe = false;
+        }
+        Ok(())
+    }
+
+    /// The conversion for a non-const pointer or reference through which
+    /// C++ returns a value. Values of POD types are written directly
+    /// into Rust memory; others are allocated in C++.
+    fn out_param_conversion(&self, ty: &Type) -> Option<TypeConversionPolicy> {
+        match out_param_pointee(ty)? {
+            (Type::Path(typ), _) => Some(TypeConversionPolicy::new_to_out_param(
+                ty.clone(),
+                self.pod_safe_types
+                    .contains(&QualifiedName::from_type_path(typ)),
+            )),
+            _ => None,
+        }
+    }
+
+    /// The name of a function as it's known in C++, such as
+    /// `ns::Type::method`, as used to match directives.
+    fn get_qualified_cpp_name(
+        ns: &Namespace,
+        self_ty: Option<&QualifiedName>,
+        cpp_call_name: &str,
+    ) -> String {
+        ns.iter()
+            .map(|segment| segment.as_str())
+            .chain(self_ty.map(|ty| ty.get_final_item()))
+            .chain(std::iter::once(cpp_call_name))
+            .join("::")
+    }
+
+    /// Finds functions with more than one overload in the same scope,
+    /// not counting the variants which omit default arguments.
+    fn find_overloaded_names(apis: &[Api<PodAnalysis>]) -> HashSet<OverloadKey> {
+        let mut counts: HashMap<OverloadKey, usize> = HashMap::new();
+        for api in apis {
+            if let ApiDetail::Function { fun, .. } = &api.detail {
+                if fun.default_args_suffix.is_none() {
+                    let cpp_name = api
+                        .original_name
+                        .clone()
+                        .unwrap_or_else(|| fun.item.sig.ident.to_string());
+                    *counts
+                        .entry((
+                            api.name.get_namespace().clone(),
+                            fun.self_ty.clone(),
+                            cpp_name,
+                        ))
+                        .or_default() += 1;
+                }
+            }
+        }
+        counts
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// Finds non-const methods which are overloaded by a const method taking
+    /// the same parameters, such as `T& get()` alongside
+    /// `const T& get() const`.
+    fn find_mut_overloads(apis: &[Api<PodAnalysis>]) -> HashSet<(OverloadKey, String)> {
+        let mut const_methods = HashSet::new();
+        let mut mut_methods = HashSet::new();
+        for api in apis {
+            if let ApiDetail::Function { fun, .. } = &api.detail {
+                if fun.default_args_suffix.is_some() || fun.self_ty.is_none() {
+                    continue;
+                }
+                let cpp_name = api
+                    .original_name
+                    .clone()
+                    .unwrap_or_else(|| fun.item.sig.ident.to_string());
+                let key = (
+                    (
+                        api.name.get_namespace().clone(),
+                        fun.self_ty.clone(),
+                        cpp_name,
+                    ),
+                    Self::overload_signature(&fun.item),
+                );
+                match Self::is_const_method(&fun.item) {
+                    Some(true) => {
+                        const_methods.insert(key);
+                    }
+                    Some(false) => {
+                        mut_methods.insert(key);
+                    }
+                    None => {}
+                }
+            }
+        }
+        mut_methods
+            .into_iter()
+            .filter(|key| const_methods.contains(key))
+            .collect()
+    }
+
+    /// Whether this method's receiver, as given by bindgen, is const. `None`
+    /// for anything which isn't a method with a receiver.
+    fn is_const_method(fun: &ForeignItemFn) -> Option<bool> {
+        match fun.sig.inputs.first()? {
+            FnArg::Typed(pt) => match (pt.pat.as_ref(), pt.ty.as_ref()) {
+                (Pat::Ident(pp), Type::Ptr(ptr)) if pp.ident == "this" => {
+                    Some(ptr.mutability.is_none())
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The types of a method's parameters other than its receiver, as used
+    /// to match up const and non-const overloads.
+    fn overload_signature(fun: &ForeignItemFn) -> String {
+        fun.sig
+            .inputs
+            .iter()
+            .skip(1)
+            .map(|arg| match arg {
+                FnArg::Typed(pt) => pt.ty.to_token_stream().to_string(),
+                FnArg::Receiver(_) => String::new(),
+            })
+            .join(", ")
+    }
+
+    fn make_wrapper_name(cxxbridge_name: &Ident) -> Ident {
+        let joiner = if cxxbridge_name.to_string().ends_with('_') {
+            ""
+        } else {
+            "_"
+        };
+        make_ident(&format!("{}{}autocxx_wrapper", cxxbridge_name, joiner))
+    }
+
+    /// For each constructor, we generate a `make_unique` function during
+    /// the main analysis. Here we generate a `make_shared` equivalent,
+    /// which is identical except that it results in a `SharedPtr`.
+    /// This is necessary for types which derive from
+    /// `std::enable_shared_from_this`, since their `shared_from_this`
+    /// method only works if the object is owned by a `std::shared_ptr`.
+    fn make_shared_constructor(&mut self, api: &Api<FnAnalysis>) -> Option<Api<FnAnalysis>> {
+        let (fun, analysis) = match &api.detail {
+            ApiDetail::Function { fun, analysis } => (fun, analysis),
+            _ => return None,
+        };
+        let self_ty = match &analysis.kind {
+            FnKind::Method(self_ty, MethodKind::Constructor) => self_ty,
+            _ => return None,
+        };
+        let wrapper = match &analysis.cpp_wrapper {
+            Some(AdditionalNeed::FunctionWrapper(wrapper)) => wrapper,
+            _ => return None,
+        };
+        let ns = api.name.get_namespace();
+        let rust_name = analysis.rust_name.replacen("make_unique", "make_shared", 1);
+        let cxxbridge_name =
+            self.get_cxx_bridge_name(Some(self_ty.get_final_item()), &rust_name, ns);
+        let cxxbridge_name = Self::make_wrapper_name(&make_ident(&cxxbridge_name));
+        let constructed_type = self_ty.to_type_path();
+        let return_conversion = TypeConversionPolicy::new_to_shared_ptr(parse_quote! {
+            #constructed_type
+        });
+        let new_ret_type = return_conversion.unconverted_rust_type();
+        let cpp_wrapper = FunctionWrapper {
+            wrapper_function_name: cxxbridge_name.clone(),
+            return_conversion: Some(return_conversion),
+            ..wrapper.as_ref().clone()
+        };
+        let id = make_ident(&rust_name);
+        Some(Api {
+            name: QualifiedName::new(ns, id),
+            original_name: api.original_name.clone(),
+            deps: api.deps.clone(),
+            detail: ApiDetail::Function {
+                fun: fun.clone(),
+                analysis: FnAnalysisBody {
+                    cxxbridge_name,
+                    rust_name,
+                    rust_rename_strategy: RustRenameStrategy::None,
+                    params: analysis.params.clone(),
+                    kind: FnKind::Method(self_ty.clone(), MethodKind::Constructor),
+                    ret_type: parse_quote!(
+                        -> #new_ret_type
+                    ),
+                    param_details: analysis.param_details.clone(),
+                    cpp_call_name: analysis.cpp_call_name.clone(),
+                    requires_unsafe: analysis.requires_unsafe,
+                    vis: analysis.vis.clone(),
+                    cpp_wrapper: Some(AdditionalNeed::FunctionWrapper(Box::new(cpp_wrapper))),
+                    output_lifetime: None,
+                    result_status: None,
+                },
+            },
+        })
+    }
+
+    /// For types which have a storage type, we also generate an `emplace`
+    /// equivalent of each `make_unique` function. This constructs the
+    /// object at a given address, which is in practice within that storage.
+    fn make_emplace_constructor(
+        &mut self,
+        api: &Api<FnAnalysis>,
+        storage_types: &HashSet<QualifiedName>,
+    ) -> Option<Api<FnAnalysis>> {
+        let (fun, analysis) = match &api.detail {
+            ApiDetail::Function { fun, analysis } => (fun, analysis),
+            _ => return None,
+        };
+        let self_ty = match &analysis.kind {
+            FnKind::Method(self_ty, MethodKind::Constructor)
+                if storage_types.contains(self_ty)
+                    && analysis.rust_name.starts_with("make_unique") =>
+            {
+                self_ty
+            }
+            _ => return None,
+        };
+        let wrapper = match &analysis.cpp_wrapper {
+            Some(AdditionalNeed::FunctionWrapper(wrapper)) => wrapper,
+            _ => return None,
+        };
+        let ns = api.name.get_namespace();
+        let rust_name = analysis.rust_name.replacen("make_unique", "emplace", 1);
+        let cxxbridge_name =
+            self.get_cxx_bridge_name(Some(self_ty.get_final_item()), &rust_name, ns);
+        let cxxbridge_name = Self::make_wrapper_name(&make_ident(&cxxbridge_name));
+        let constructed_type = self_ty.to_type_path();
+        let this_type: Type = parse_quote! {
+            *mut #constructed_type
+        };
+        let this_conversion = TypeConversionPolicy::new_unconverted(this_type.clone());
+        let mut params = analysis.params.clone();
+        params.insert(
+            0,
+            parse_quote! {
+                this: #this_type
+            },
+        );
+        let mut param_details = analysis.param_details.clone();
+        param_details.insert(
+            0,
+            ArgumentAnalysis {
+                conversion: this_conversion.clone(),
+                name: parse_quote!(this),
+                self_type: None,
+                was_reference: false,
+                deps: HashSet::new(),
+                is_virtual: false,
+                requires_unsafe: true,
+            },
+        );
+        let cpp_wrapper = FunctionWrapper {
+            payload: FunctionWrapperPayload::Emplace(self_ty.clone()),
+            wrapper_function_name: cxxbridge_name.clone(),
+            return_conversion: None,
+            argument_conversion: std::iter::once(this_conversion)
+                .chain(wrapper.argument_conversion.iter().cloned())
+                .collect(),
+            is_a_method: true,
+        };
+        let id = make_ident(&rust_name);
+        Some(Api {
+            name: QualifiedName::new(ns, id),
+            original_name: api.original_name.clone(),
+            deps: api.deps.clone(),
+            detail: ApiDetail::Function {
+                fun: fun.clone(),
+                analysis: FnAnalysisBody {
+                    cxxbridge_name,
+                    rust_name,
+                    rust_rename_strategy: RustRenameStrategy::None,
+                    params,
+                    kind: FnKind::Method(self_ty.clone(), MethodKind::Constructor),
+                    ret_type: ReturnType::Default,
+                    param_details,
+                    cpp_call_name: analysis.cpp_call_name.clone(),
+                    requires_unsafe: true,
+                    vis: analysis.vis.clone(),
+                    cpp_wrapper: Some(AdditionalNeed::FunctionWrapper(Box::new(cpp_wrapper))),
+                    output_lifetime: None,
+                    result_status: None,
+                },
+            },
+        })
+    }
+
     fn convert_fn_arg(
         &mut self,
         arg: &FnArg,
@@ -861,7 +1533,69 @@ impl Api<FnAnalysis> {
                 ))
             }
             ApiDetail::CType { typename } => Some(AdditionalNeed::CTypeTypedef(typename.clone())),
+            // We only generate storage for types whose layout we know, so
+            // this also checks that layout.
+            ApiDetail::Struct { details, .. } if details.has_storage => Some(
+                AdditionalNeed::StorageDestructor(self.name.clone(), details.clone()),
+            ),
+            // Opaque types have the layout which bindgen calculated, too.
+            ApiDetail::Struct { details, .. } | ApiDetail::Union { details, .. }
+                if details.layout.is_some() =>
+            {
+                Some(AdditionalNeed::LayoutAssertion(
+                    self.name.clone(),
+                    details.clone(),
+                ))
+            }
             _ => None,
         }
     }
 }
+
+/// Adds a `'static` lifetime to a reference (or a `Pin` of a reference)
+/// as returned by the accessor for a global or static member variable.
+fn make_static_reference(ty: &Type) -> Type {
+    match ty {
+        Type::Reference(typr) => {
+            let mut typr = typr.clone();
+            typr.lifetime = Some(parse_quote! { 'static });
+            Type::Reference(typr)
+        }
+        Type::Path(typ) => {
+            let mut typ = typ.clone();
+            if let Some(PathArguments::AngleBracketed(ab)) =
+                typ.path.segments.last_mut().map(|seg| &mut seg.arguments)
+            {
+                for arg in ab.args.iter_mut() {
+                    if let GenericArgument::Type(inner) = arg {
+                        *inner = make_static_reference(inner);
+                    }
+                }
+            }
+            Type::Path(typ)
+        }
+        _ => ty.clone(),
+    }
+}
+
+/// Adds a lifetime to a reference, or a reference within a `Pin`.
+/// Returns whether there was such a reference.
+fn add_lifetime(ty: &mut Type, lifetime: &Lifetime) -> bool {
+    match ty {
+        Type::Reference(r) => {
+            r.lifetime = Some(lifetime.clone());
+            true
+        }
+        Type::Path(typ) => match typ.path.segments.last_mut() {
+            Some(seg) if seg.ident == "Pin" => match &mut seg.arguments {
+                PathArguments::AngleBracketed(ab) => match ab.args.iter_mut().next() {
+                    Some(GenericArgument::Type(inner)) => add_lifetime(inner, lifetime),
+                    _ => false,
+                },
+                _ => false,
+            },
+            _ => false,
+        },
+        _ => false,
+    }
+}
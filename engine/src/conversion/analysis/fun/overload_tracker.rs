@@ -60,9 +60,42 @@ impl OverloadTracker {
     }
 }
 
+/// A suffix derived from the parameter types of an overload, such as
+/// `_int_double` for `(int, double)`, so that its name doesn't depend
+/// upon which other overloads exist. Namespaces, `const` and references
+/// are ignored; pointers are marked with `ptr`.
+pub(crate) fn signature_suffix(cpp_param_types: &[String]) -> String {
+    cpp_param_types
+        .iter()
+        .map(|ty| {
+            let mut words = Vec::new();
+            let mut rest = ty.as_str();
+            while !rest.is_empty() {
+                let word_len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                let (word, after) = rest.split_at(word_len);
+                if word.is_empty() {
+                    if after.starts_with('*') {
+                        words.push("ptr");
+                    }
+                    rest = &after[after.chars().next().unwrap().len_utf8()..];
+                    continue;
+                }
+                let is_qualifier = after.starts_with("::");
+                if !is_qualifier && !matches!(word, "const" | "struct" | "class" | "enum") {
+                    words.push(word);
+                }
+                rest = after;
+            }
+            format!("_{}", words.join("_"))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::OverloadTracker;
+    use super::{signature_suffix, OverloadTracker};
 
     #[test]
     fn test_by_function() {
@@ -80,4 +113,21 @@ mod tests {
         assert_eq!(ot.get_method_real_name("Ty2", "bob".into()), "bob");
         assert_eq!(ot.get_method_real_name("Ty2", "bob".into()), "bob1");
     }
+
+    #[test]
+    fn test_signature_suffix() {
+        assert_eq!(signature_suffix(&[]), "");
+        assert_eq!(
+            signature_suffix(&["int".into(), "double".into()]),
+            "_int_double"
+        );
+        assert_eq!(
+            signature_suffix(&["const ns::Fred&".into(), "unsigned int".into()]),
+            "_Fred_unsigned_int"
+        );
+        assert_eq!(
+            signature_suffix(&["std::vector<uint32_t>".into(), "const char*".into()]),
+            "_vector_uint32_t_char_ptr"
+        );
+    }
 }
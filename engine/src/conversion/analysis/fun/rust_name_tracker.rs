@@ -36,9 +36,47 @@ impl RustNameTracker {
     }
 }
 
+/// Converts a C++ `CamelCase` or `camelCase` name to Rust `snake_case`,
+/// treating runs of capitals as acronyms, so `GetHTTPResponse` becomes
+/// `get_http_response`. Digits followed by a capital which doesn't start
+/// a new word are a word of their own, so `Draw2D` becomes `draw_2d`.
+/// Existing underscores are kept.
+pub(crate) fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let is_lower_at = |i: usize| chars.get(i).map(|c| c.is_lowercase()) == Some(true);
+    let mut result = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if i > 0 {
+            let prev = chars[i - 1];
+            let word_starts = if c.is_uppercase() {
+                prev.is_lowercase()
+                    || (prev.is_ascii_digit() && is_lower_at(i + 1))
+                    || (prev.is_uppercase() && is_lower_at(i + 1))
+            } else if c.is_ascii_digit() && prev.is_lowercase() {
+                // Digits then a capital, such as the "2D" in "Draw2D".
+                let end = chars[i..]
+                    .iter()
+                    .position(|c| !c.is_ascii_digit())
+                    .map(|pos| i + pos);
+                match end {
+                    Some(end) => chars[end].is_uppercase() && !is_lower_at(end + 1),
+                    None => false,
+                }
+            } else {
+                false
+            };
+            if word_starts {
+                result.push('_');
+            }
+        }
+        result.extend(c.to_lowercase());
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
-    use super::RustNameTracker;
+    use super::{to_snake_case, RustNameTracker};
 
     #[test]
     fn test() {
@@ -47,4 +85,17 @@ mod tests {
         assert!(!rnt.ok_to_use_rust_name("a"));
         assert!(rnt.ok_to_use_rust_name("b"));
     }
+
+    #[test]
+    fn test_to_snake_case() {
+        assert_eq!(to_snake_case("GetValue"), "get_value");
+        assert_eq!(to_snake_case("getValue"), "get_value");
+        assert_eq!(to_snake_case("GetHTTPResponse"), "get_http_response");
+        assert_eq!(to_snake_case("Draw2D"), "draw_2d");
+        assert_eq!(to_snake_case("Draw2DLine"), "draw_2d_line");
+        assert_eq!(to_snake_case("GetUTF8String"), "get_utf8_string");
+        assert_eq!(to_snake_case("Vec3"), "vec3");
+        assert_eq!(to_snake_case("already_snake"), "already_snake");
+        assert_eq!(to_snake_case("Get_Value"), "get_value");
+    }
 }
@@ -49,23 +49,24 @@ pub(crate) fn filter_apis_by_following_edges_from_allowlist(
         })
         .map(Api::name)
         .collect();
-    let mut by_typename: HashMap<QualifiedName, Vec<Api<FnAnalysis>>> = HashMap::new();
-    for api in apis.drain(..) {
-        let tn = api.name();
-        by_typename.entry(tn).or_default().push(api);
+    let mut by_typename: HashMap<QualifiedName, Vec<&Api<FnAnalysis>>> = HashMap::new();
+    for api in &apis {
+        by_typename.entry(api.name()).or_default().push(api);
     }
     let mut done = HashSet::new();
-    let mut output = Vec::new();
     while !todos.is_empty() {
         let todo = todos.remove(0);
         if done.contains(&todo) {
             continue;
         }
-        if let Some(mut these_apis) = by_typename.remove(&todo) {
+        if let Some(these_apis) = by_typename.remove(&todo) {
             todos.extend(these_apis.iter().flat_map(|api| api.deps.iter().cloned()));
-            output.append(&mut these_apis);
         } // otherwise, probably an intrinsic e.g. uint32_t.
         done.insert(todo);
     }
-    output
+    // Keep the APIs in their original order, rather than the order in
+    // which we found them, which depends on the iteration order of each
+    // API's dependencies.
+    apis.retain(|api| done.contains(&api.name));
+    apis
 }
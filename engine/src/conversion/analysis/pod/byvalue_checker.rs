@@ -14,7 +14,10 @@
 
 use crate::{
     conversion::{
-        analysis::tdef::TypedefAnalysis,
+        analysis::{
+            pod::{is_bitfield_field, union_as_struct},
+            tdef::TypedefAnalysis,
+        },
         api::{Api, TypedefKind},
     },
     types::{Namespace, QualifiedName},
@@ -115,9 +118,12 @@ impl ByValueChecker {
                         None => byvalue_checker.ingest_nonpod_type(name),
                     }
                 }
-                ApiDetail::Struct { item, analysis: _ } => {
+                ApiDetail::Struct { item, .. } => {
                     byvalue_checker.ingest_struct(&item, &api.name.get_namespace())
                 }
+                ApiDetail::Union { item, .. } => {
+                    byvalue_checker.ingest_struct(&union_as_struct(item), api.name.get_namespace())
+                }
                 ApiDetail::Enum {
                     item: _,
                     analysis: _,
@@ -163,6 +169,13 @@ impl ByValueChecker {
                 }
             }
         }
+        if Self::is_nontrivial_union(def) {
+            let reason = format!(
+                "Type {} could not be POD because it is a union with non-trivial members.",
+                tyname
+            );
+            field_safety_problem = PodState::UnsafeToBePod(reason);
+        }
         if Self::has_vtable(def) {
             let reason = format!(
                 "Type {} could not be POD because it has virtual functions.",
@@ -239,6 +252,10 @@ impl ByValueChecker {
     fn get_field_types(def: &ItemStruct) -> Vec<QualifiedName> {
         let mut results = Vec::new();
         for f in &def.fields {
+            if is_bitfield_field(f) {
+                // Plain old bytes, so no obstacle to POD-ness.
+                continue;
+            }
             let fty = &f.ty;
             if let Type::Path(p) = fty {
                 results.push(QualifiedName::from_type_path(&p));
@@ -248,7 +265,20 @@ impl ByValueChecker {
         results
     }
 
-    fn has_vtable(def: &ItemStruct) -> bool {
+    /// bindgen represents unions which it can't make into Rust unions
+    /// as a struct with this field to give the size.
+    fn is_nontrivial_union(def: &ItemStruct) -> bool {
+        def.fields.iter().any(|f| {
+            f.ident
+                .as_ref()
+                .map(|id| id == "bindgen_union_field")
+                .unwrap_or(false)
+        })
+    }
+
+    /// Whether this type has virtual functions of its own. Derived types
+    /// instead inherit the vtable pointer within their first base.
+    pub(crate) fn has_vtable(def: &ItemStruct) -> bool {
         for f in &def.fields {
             if f.ident.as_ref().map(|id| id == "vtable_").unwrap_or(false) {
                 return true;
@@ -340,4 +370,20 @@ mod tests {
         bvc.ingest_struct(&t, &Namespace::new());
         assert!(bvc.satisfy_requests(vec![t_id]).is_err());
     }
+
+    #[test]
+    fn test_with_bitfields() {
+        let mut bvc = ByValueChecker::new();
+        let t: ItemStruct = parse_quote! {
+            struct Bar {
+                pub _bitfield_align_1: [u32; 0],
+                pub _bitfield_1: root::__BindgenBitfieldUnit<[u8; 4usize]>,
+                b: i64,
+            }
+        };
+        let t_id = ty_from_ident(&t.ident);
+        bvc.ingest_struct(&t, &Namespace::new());
+        bvc.satisfy_requests(vec![t_id.clone()]).unwrap();
+        assert!(bvc.is_pod(&t_id));
+    }
 }
@@ -17,8 +17,8 @@ mod byvalue_checker;
 use std::collections::HashSet;
 
 use autocxx_parser::IncludeCppConfig;
-use byvalue_checker::ByValueChecker;
-use syn::ItemStruct;
+pub(crate) use byvalue_checker::ByValueChecker;
+use syn::{Field, Fields, ItemStruct, ItemUnion};
 
 use crate::{
     conversion::{
@@ -28,7 +28,7 @@ use crate::{
         error_reporter::convert_item_apis,
         ConvertError,
     },
-    types::{Namespace, QualifiedName},
+    types::{make_ident, Namespace, QualifiedName},
 };
 
 use super::tdef::TypedefAnalysis;
@@ -59,7 +59,14 @@ pub(crate) fn analyze_pod_apis(
     let mut type_converter = TypeConverter::new(config, &apis);
     let mut results = Vec::new();
     convert_item_apis(apis, &mut results, |api| {
-        analyze_pod_api(api, &byvalue_checker, &mut type_converter, &mut extra_apis).map(Some)
+        analyze_pod_api(
+            api,
+            &byvalue_checker,
+            &mut type_converter,
+            &mut extra_apis,
+            config,
+        )
+        .map(Some)
     });
     // Conceivably, the process of POD-analysing the first set of APIs could result
     // in us creating new APIs to concretize generic types.
@@ -70,6 +77,7 @@ pub(crate) fn analyze_pod_apis(
             &byvalue_checker,
             &mut type_converter,
             &mut more_extra_apis,
+            config,
         )
         .map(Some)
     });
@@ -82,6 +90,7 @@ fn analyze_pod_api(
     byvalue_checker: &ByValueChecker,
     type_converter: &mut TypeConverter,
     extra_apis: &mut Vec<UnanalyzedApi>,
+    config: &IncludeCppConfig,
 ) -> Result<Api<PodAnalysis>, ConvertError> {
     let ty_id = api.name();
     let mut new_deps = api.deps;
@@ -100,6 +109,7 @@ fn analyze_pod_api(
         ApiDetail::Const { const_item } => ApiDetail::Const { const_item },
         ApiDetail::Typedef { item, analysis } => ApiDetail::Typedef { item, analysis },
         ApiDetail::CType { typename } => ApiDetail::CType { typename },
+        ApiDetail::BitfieldUnit { items } => ApiDetail::BitfieldUnit { items },
         // Just changes to these two...
         ApiDetail::Enum {
             mut item,
@@ -115,6 +125,7 @@ fn analyze_pod_api(
         }
         ApiDetail::Struct {
             mut item,
+            mut details,
             analysis: _,
         } => {
             super::remove_bindgen_attrs(&mut item.attrs)?;
@@ -130,16 +141,58 @@ fn analyze_pod_api(
                 TypeKind::Pod
             } else {
                 // It's non-POD. So also, make the fields opaque...
-                make_non_pod(&mut item);
+                make_non_pod(&mut item, details.layout);
                 // ... and say we don't depend on other types.
                 new_deps.clear();
+                // Bitfield accessors and field offsets refer to the fields
+                // we've just removed.
+                details.bitfield_accessors.clear();
+                details.field_offsets.clear();
+                details.has_storage =
+                    config.is_storage_type(&ty_id.to_cpp_name()) && details.layout.is_some();
                 TypeKind::NonPod
             };
             ApiDetail::Struct {
                 item,
+                details,
                 analysis: type_kind,
             }
         }
+        ApiDetail::Union {
+            mut item,
+            mut details,
+            analysis: _,
+        } => {
+            super::remove_bindgen_attrs(&mut item.attrs)?;
+            if byvalue_checker.is_pod(&ty_id) {
+                get_struct_field_types(
+                    type_converter,
+                    api.name.get_namespace(),
+                    &union_as_struct(&item),
+                    &mut new_deps,
+                    extra_apis,
+                )?;
+                ApiDetail::Union {
+                    item,
+                    details,
+                    analysis: TypeKind::Pod,
+                }
+            } else {
+                // Rust can't do anything useful with an opaque union,
+                // so it becomes an opaque struct like any other.
+                let mut item = union_as_struct(&item);
+                make_non_pod(&mut item, details.layout);
+                new_deps.clear();
+                details.field_offsets.clear();
+                details.has_storage =
+                    config.is_storage_type(&ty_id.to_cpp_name()) && details.layout.is_some();
+                ApiDetail::Struct {
+                    item,
+                    details,
+                    analysis: TypeKind::NonPod,
+                }
+            }
+        }
         ApiDetail::IgnoredItem { err, ctx } => ApiDetail::IgnoredItem { err, ctx },
     };
     Ok(Api {
@@ -158,6 +211,15 @@ fn get_struct_field_types(
     extra_apis: &mut Vec<UnanalyzedApi>,
 ) -> Result<(), ConvertError> {
     for f in &s.fields {
+        if is_bitfield_field(f) {
+            // The storage type for bitfields is generated by bindgen
+            // rather than corresponding to any C++ type.
+            deps.insert(QualifiedName::new(
+                &Namespace::new(),
+                make_ident(BITFIELD_UNIT_NAME),
+            ));
+            continue;
+        }
         let annotated =
             type_converter.convert_type(f.ty.clone(), ns, &TypeConversionContext::CxxInnerType)?;
         extra_apis.extend(annotated.extra_apis);
@@ -165,3 +227,33 @@ fn get_struct_field_types(
     }
     Ok(())
 }
+
+/// bindgen's name for the generic storage type used for bitfields.
+pub(crate) const BITFIELD_UNIT_NAME: &str = "__BindgenBitfieldUnit";
+
+/// bindgen's name for the type used for the members of unions which
+/// it didn't represent as a Rust `union`.
+pub(crate) const UNION_FIELD_NAME: &str = "__BindgenUnionField";
+
+/// Whether this is one of the fields which bindgen generates to hold
+/// bitfields (or to ensure their alignment).
+pub(crate) fn is_bitfield_field(f: &Field) -> bool {
+    f.ident
+        .as_ref()
+        .map(|id| id.to_string().starts_with("_bitfield_"))
+        .unwrap_or(false)
+}
+
+/// A struct with the same fields as this union, for the purposes of
+/// working out whether it's POD, or making it opaque if it isn't.
+pub(crate) fn union_as_struct(u: &ItemUnion) -> ItemStruct {
+    ItemStruct {
+        attrs: u.attrs.clone(),
+        vis: u.vis.clone(),
+        struct_token: Default::default(),
+        ident: u.ident.clone(),
+        generics: u.generics.clone(),
+        fields: Fields::Named(u.fields.clone()),
+        semi_token: None,
+    }
+}
@@ -85,7 +85,25 @@ pub(crate) fn convert_typedef_targets(
                     item: item.clone(),
                     analysis: item,
                 }),
-                ApiDetail::Struct { item, analysis } => Some(ApiDetail::Struct { item, analysis }),
+                ApiDetail::Struct {
+                    item,
+                    details,
+                    analysis,
+                } => Some(ApiDetail::Struct {
+                    item,
+                    details,
+                    analysis,
+                }),
+                ApiDetail::Union {
+                    item,
+                    details,
+                    analysis,
+                } => Some(ApiDetail::Union {
+                    item,
+                    details,
+                    analysis,
+                }),
+                ApiDetail::BitfieldUnit { items } => Some(ApiDetail::BitfieldUnit { items }),
                 ApiDetail::Enum { item, analysis } => Some(ApiDetail::Enum { item, analysis }),
                 ApiDetail::CType { typename } => Some(ApiDetail::CType { typename }),
                 ApiDetail::IgnoredItem { err, ctx } => Some(ApiDetail::IgnoredItem { err, ctx }),
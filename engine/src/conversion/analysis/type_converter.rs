@@ -457,11 +457,13 @@ impl<'a> TypeConverter<'a> {
                 | ApiDetail::ConcreteType { .. }
                 | ApiDetail::Typedef { .. }
                 | ApiDetail::Enum { .. }
-                | ApiDetail::Struct { .. } => Some(api.name()),
+                | ApiDetail::Struct { .. }
+                | ApiDetail::Union { .. } => Some(api.name()),
                 ApiDetail::StringConstructor
                 | ApiDetail::Function { .. }
                 | ApiDetail::Const { .. }
                 | ApiDetail::CType { .. }
+                | ApiDetail::BitfieldUnit { .. }
                 | ApiDetail::IgnoredItem { .. } => None,
             })
             .collect()
@@ -489,6 +491,12 @@ impl<'a> TypeConverter<'a> {
                 ApiDetail::ConcreteType { cpp_definition, .. } => {
                     Some((cpp_definition.clone(), api.name()))
                 }
+                // Instantiations which the user has named take the
+                // place of our synthesized concrete types.
+                ApiDetail::Struct { details, .. } => details
+                    .instantiation_of
+                    .as_ref()
+                    .map(|cpp_definition| (cpp_definition.clone(), api.name())),
                 _ => None,
             })
             .collect()
@@ -16,7 +16,8 @@ use crate::types::QualifiedName;
 use itertools::Itertools;
 use std::collections::HashSet;
 use syn::{
-    ForeignItemFn, Ident, ImplItem, ItemConst, ItemEnum, ItemStruct, ItemType, ItemUse, Type,
+    ForeignItemFn, Ident, ImplItem, ImplItemMethod, Item, ItemConst, ItemEnum, ItemStruct,
+    ItemType, ItemUnion, ItemUse, Type,
 };
 
 use super::{convert_error::ErrorContext, ConvertError};
@@ -49,6 +50,13 @@ pub(crate) struct FuncToConvert {
     pub(crate) item: ForeignItemFn,
     pub(crate) virtual_this_type: Option<QualifiedName>,
     pub(crate) self_ty: Option<QualifiedName>,
+    /// Whether this is a synthesized function which returns a reference
+    /// to a global or static member variable of the same name.
+    pub(crate) is_static_accessor: bool,
+    /// If this is a variant of a C++ function which omits some trailing
+    /// parameters with default values, the suffix for its Rust name.
+    /// We always call such variants via a C++ wrapper.
+    pub(crate) default_args_suffix: Option<String>,
 }
 
 /// Layers of analysis which may be applied to decorate each API.
@@ -74,6 +82,62 @@ pub(crate) enum TypedefKind {
     Type(ItemType),
 }
 
+/// Size and alignment of a type, as calculated by bindgen.
+#[derive(Clone, Copy)]
+pub(crate) struct Layout {
+    pub(crate) size: usize,
+    pub(crate) align: usize,
+}
+
+/// The offset of a field within a struct, as calculated by bindgen.
+#[derive(Clone)]
+pub(crate) struct FieldOffset {
+    pub(crate) rust_name: Ident,
+    /// Candidates for the C++ name of the field.
+    pub(crate) cpp_names: Vec<String>,
+    pub(crate) offset: usize,
+}
+
+/// Things we know about a struct from parts of the bindgen output
+/// other than the struct definition itself.
+#[derive(Clone, Default)]
+pub(crate) struct StructDetails {
+    /// From bindgen's layout tests.
+    pub(crate) layout: Option<Layout>,
+    /// Offsets of each public field. Also from bindgen's layout tests.
+    pub(crate) field_offsets: Vec<FieldOffset>,
+    /// Methods which bindgen generated to get and set the
+    /// values of any bitfields.
+    pub(crate) bitfield_accessors: Vec<ImplItemMethod>,
+    /// Whether we should generate a storage type which allows this
+    /// (opaque) type to be held within Rust structs.
+    pub(crate) has_storage: bool,
+    /// If this struct was created by an `instantiate!` directive,
+    /// the C++ template instantiation which it represents.
+    pub(crate) instantiation_of: Option<String>,
+    /// Public base classes, as far as bindgen represents them (it
+    /// omits empty and virtual bases).
+    pub(crate) bases: Vec<QualifiedName>,
+    /// All unambiguous base classes, direct or indirect, to which
+    /// this type can be upcast. Filled in during function analysis.
+    pub(crate) upcasts: Vec<QualifiedName>,
+    /// Whether this is a polymorphic base of some other type, such
+    /// that we should allow downcasting from it.
+    pub(crate) has_downcasts: bool,
+    /// The polymorphic bases from which this type can be reached by
+    /// `dynamic_cast`. Filled in during function analysis.
+    pub(crate) downcasts_from: Vec<Downcast>,
+}
+
+/// Functions which dynamically cast a reference to some base class
+/// into a reference to a derived class, or return null.
+#[derive(Clone)]
+pub(crate) struct Downcast {
+    pub(crate) base: QualifiedName,
+    pub(crate) cxxbridge_name: Ident,
+    pub(crate) mut_cxxbridge_name: Ident,
+}
+
 #[derive(strum_macros::Display)]
 /// Different types of API we might encounter.
 /// This derives from [strum_macros::Display] because we want to be
@@ -116,8 +180,19 @@ pub(crate) enum ApiDetail<T: AnalysisPhase> {
     /// `bindgen` output.
     Struct {
         item: ItemStruct,
+        details: Box<StructDetails>,
+        analysis: T::TypeAnalysis,
+    },
+    /// A union encountered in the `bindgen` output, either as a Rust
+    /// `union` or reconstructed from bindgen's struct representation.
+    Union {
+        item: ItemUnion,
+        details: Box<StructDetails>,
         analysis: T::TypeAnalysis,
     },
+    /// bindgen's generic storage type for bitfields, along with its
+    /// `impl` blocks.
+    BitfieldUnit { items: Vec<Item> },
     /// A variable-length C integer type (e.g. int, unsigned long).
     CType { typename: QualifiedName },
     /// Some item which couldn't be processed by autocxx for some reason.
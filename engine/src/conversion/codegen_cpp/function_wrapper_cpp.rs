@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use crate::conversion::{
-    analysis::fun::function_wrapper::{CppConversionType, TypeConversionPolicy},
+    analysis::fun::function_wrapper::{out_param_pointee, CppConversionType, TypeConversionPolicy},
     ConvertError,
 };
 use crate::known_types::type_lacks_copy_constructor;
@@ -27,6 +27,10 @@ impl TypeConversionPolicy {
     ) -> Result<String, ConvertError> {
         match self.cpp_conversion {
             CppConversionType::FromUniquePtrToValue => self.wrapped_type(original_name_map),
+            CppConversionType::FromUniquePtrToOutParam => Ok(format!(
+                "std::unique_ptr<{}>&",
+                self.out_param_type_as_string(original_name_map)?
+            )),
             _ => self.unwrapped_type_as_string(original_name_map),
         }
     }
@@ -37,6 +41,7 @@ impl TypeConversionPolicy {
     ) -> Result<String, ConvertError> {
         match self.cpp_conversion {
             CppConversionType::FromValueToUniquePtr => self.wrapped_type(original_name_map),
+            CppConversionType::FromValueToSharedPtr => self.shared_type(original_name_map),
             _ => self.unwrapped_type_as_string(original_name_map),
         }
     }
@@ -48,6 +53,13 @@ impl TypeConversionPolicy {
         type_to_cpp(&self.unwrapped_type, original_name_map)
     }
 
+    fn out_param_type_as_string(
+        &self,
+        original_name_map: &OriginalNameMap,
+    ) -> Result<String, ConvertError> {
+        type_to_cpp(self.out_param_type().unwrap(), original_name_map)
+    }
+
     fn wrapped_type(&self, original_name_map: &OriginalNameMap) -> Result<String, ConvertError> {
         Ok(format!(
             "std::unique_ptr<{}>",
@@ -55,6 +67,13 @@ impl TypeConversionPolicy {
         ))
     }
 
+    fn shared_type(&self, original_name_map: &OriginalNameMap) -> Result<String, ConvertError> {
+        Ok(format!(
+            "std::shared_ptr<{}>",
+            self.unwrapped_type_as_string(original_name_map)?
+        ))
+    }
+
     pub(super) fn cpp_conversion(
         &self,
         var_name: &str,
@@ -74,6 +93,31 @@ impl TypeConversionPolicy {
                 self.unconverted_type(original_name_map)?,
                 var_name
             ),
+            CppConversionType::FromValueToSharedPtr => format!(
+                "std::make_shared<{}>({})",
+                self.unconverted_type(original_name_map)?,
+                var_name
+            ),
+            CppConversionType::FromUniquePtrToOutParam => {
+                let allocation = format!(
+                    "({} = std::make_unique<{}>())",
+                    var_name,
+                    self.out_param_type_as_string(original_name_map)?
+                );
+                if matches!(out_param_pointee(&self.unwrapped_type), Some((_, true))) {
+                    format!("{}.get()", allocation)
+                } else {
+                    format!("*{}", allocation)
+                }
+            }
+            CppConversionType::ConstructInOutParam => {
+                let ty = self.out_param_type_as_string(original_name_map)?;
+                if matches!(out_param_pointee(&self.unwrapped_type), Some((_, true))) {
+                    format!("new ({}) {}()", var_name, ty)
+                } else {
+                    format!("*new (&{}) {}()", var_name, ty)
+                }
+            }
         })
     }
 }
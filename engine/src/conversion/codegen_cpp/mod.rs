@@ -16,18 +16,22 @@ mod function_wrapper_cpp;
 pub(crate) mod type_to_cpp;
 
 use crate::{types::QualifiedName, CppFilePair};
-use autocxx_parser::IncludeCppConfig;
+use autocxx_parser::{IncludeCppConfig, MacroFn};
+use indoc::indoc;
 use itertools::Itertools;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use syn::Type;
-use type_to_cpp::{original_name_map_from_apis, type_to_cpp, OriginalNameMap};
+use type_to_cpp::{
+    namespaced_name_using_original_name_map, original_name_map_from_apis, type_to_cpp,
+    OriginalNameMap,
+};
 
 use super::{
     analysis::fun::{
-        function_wrapper::{FunctionWrapper, FunctionWrapperPayload},
+        function_wrapper::{CppConversionType, FunctionWrapper, FunctionWrapperPayload},
         FnAnalysis,
     },
-    api::Api,
+    api::{Api, StructDetails},
     ConvertError,
 };
 
@@ -38,6 +42,8 @@ pub(crate) enum AdditionalNeed {
     FunctionWrapper(Box<FunctionWrapper>),
     CTypeTypedef(QualifiedName),
     ConcreteTemplatedTypeTypedef(QualifiedName, Box<Type>),
+    LayoutAssertion(QualifiedName, Box<StructDetails>),
+    StorageDestructor(QualifiedName, Box<StructDetails>),
 }
 
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash)]
@@ -84,6 +90,8 @@ pub(crate) struct CppCodeGenerator<'a> {
     inclusions: String,
     original_name_map: OriginalNameMap,
     config: &'a IncludeCppConfig,
+    /// Fields for which we've already generated an offset check helper.
+    offset_check_fields: HashSet<String>,
 }
 
 impl<'a> CppCodeGenerator<'a> {
@@ -93,6 +101,9 @@ impl<'a> CppCodeGenerator<'a> {
         config: &'a IncludeCppConfig,
     ) -> Result<Option<CppFilePair>, ConvertError> {
         let mut gen = CppCodeGenerator::new(inclusions, original_name_map_from_apis(apis), config);
+        // These come first since other wrappers may call them.
+        gen.generate_macro_fns();
+        gen.generate_instantiations();
         gen.add_needs(apis.iter().filter_map(|api| api.additional_cpp()))?;
         Ok(gen.generate())
     }
@@ -107,6 +118,7 @@ impl<'a> CppCodeGenerator<'a> {
             inclusions,
             original_name_map,
             config,
+            offset_check_fields: HashSet::new(),
         }
     }
 
@@ -124,6 +136,13 @@ impl<'a> CppCodeGenerator<'a> {
                 AdditionalNeed::ConcreteTemplatedTypeTypedef(tn, def) => {
                     self.generate_typedef(&tn, type_to_cpp(&def, &self.original_name_map)?)
                 }
+                AdditionalNeed::LayoutAssertion(tn, details) => {
+                    self.generate_layout_assertion(&tn, &details)
+                }
+                AdditionalNeed::StorageDestructor(tn, details) => {
+                    self.generate_layout_assertion(&tn, &details);
+                    self.generate_storage_destructor(&tn)
+                }
             }
         }
         Ok(())
@@ -133,13 +152,13 @@ impl<'a> CppCodeGenerator<'a> {
         if self.additional_functions.is_empty() {
             None
         } else {
-            let headers: HashSet<Header> = self
+            // Sorted, so that we generate the same header each time.
+            let headers: BTreeSet<String> = self
                 .additional_functions
                 .iter()
-                .map(|x| x.headers.iter().cloned())
-                .flatten()
+                .flat_map(|x| x.headers.iter().map(|header| header.include_stmt()))
                 .collect();
-            let headers = headers.iter().map(|x| x.include_stmt()).join("\n");
+            let headers = headers.iter().join("\n");
             let type_definitions = self.concat_additional_items(|x| x.type_definition.as_ref());
             let declarations = self.concat_additional_items(|x| x.declaration.as_ref());
             let declarations = format!(
@@ -241,6 +260,16 @@ impl<'a> CppCodeGenerator<'a> {
         let arg_list = arg_list.join(", ");
         let mut underlying_function_call = match &details.payload {
             FunctionWrapperPayload::Constructor => arg_list,
+            FunctionWrapperPayload::Upcast => receiver.unwrap(),
+            FunctionWrapperPayload::Downcast => {
+                format!("dynamic_cast<{}>(&{})", ret_type, receiver.unwrap())
+            }
+            FunctionWrapperPayload::Emplace(ty) => format!(
+                "new ({}) {}({})",
+                receiver.unwrap(),
+                namespaced_name_using_original_name_map(ty, &self.original_name_map),
+                arg_list
+            ),
             FunctionWrapperPayload::FunctionCall(ns, id) => match receiver {
                 Some(receiver) => format!("{}.{}({})", receiver, id.to_string(), arg_list),
                 None => {
@@ -260,6 +289,19 @@ impl<'a> CppCodeGenerator<'a> {
                     .join("::");
                 format!("{}({})", underlying_function_call, arg_list)
             }
+            // A static member may belong to a nested class, which bindgen
+            // names differently.
+            FunctionWrapperPayload::StaticVariable(ns, Some(ty_id), var_id) => format!(
+                "{}::{}",
+                namespaced_name_using_original_name_map(
+                    &QualifiedName::new(ns, ty_id.clone()),
+                    &self.original_name_map
+                ),
+                var_id
+            ),
+            FunctionWrapperPayload::StaticVariable(ns, None, var_id) => {
+                QualifiedName::new(ns, var_id.clone()).to_cpp_name()
+            }
         };
         if let Some(ret) = &details.return_conversion {
             underlying_function_call = format!(
@@ -271,14 +313,125 @@ impl<'a> CppCodeGenerator<'a> {
             "inline {} {{ {}; }}",
             declaration, underlying_function_call,
         ));
+        let mut headers = vec![Header::system("memory")];
+        if matches!(details.payload, FunctionWrapperPayload::Emplace(_))
+            || details
+                .argument_conversion
+                .iter()
+                .any(|conv| matches!(conv.cpp_conversion, CppConversionType::ConstructInOutParam))
+        {
+            headers.push(Header::system("new"));
+        }
         self.additional_functions.push(AdditionalFunction {
             type_definition: None,
             declaration,
-            headers: vec![Header::system("memory")],
+            headers,
         });
         Ok(())
     }
 
+    fn generate_macro_fns(&mut self) {
+        for macro_fn in self.config.get_macro_fns() {
+            self.additional_functions.push(AdditionalFunction {
+                type_definition: None,
+                declaration: Some(macro_fn_definition(macro_fn)),
+                headers: Vec::new(),
+            });
+        }
+    }
+
+    fn generate_instantiations(&mut self) {
+        for instantiation in self.config.get_instantiations() {
+            self.additional_functions.push(AdditionalFunction {
+                type_definition: Some(instantiation.typedef()),
+                declaration: None,
+                headers: Vec::new(),
+            });
+        }
+    }
+
+    /// Checks that the layout which bindgen calculated, and on which
+    /// our Rust representation depends, is what the C++ compiler uses.
+    fn generate_layout_assertion(&mut self, tn: &QualifiedName, details: &StructDetails) {
+        let layout = match details.layout {
+            Some(layout) => layout,
+            None => return,
+        };
+        let cpp_name = namespaced_name_using_original_name_map(tn, &self.original_name_map);
+        let mut conditions = vec![
+            format!("sizeof({}) == {}", cpp_name, layout.size),
+            format!("alignof({}) == {}", cpp_name, layout.align),
+        ];
+        for field in &details.field_offsets {
+            for field_name in &field.cpp_names {
+                conditions.push(format!(
+                    "{}::offset_of_{}<{}, {}>::value",
+                    self.offset_check_namespace(),
+                    field_name,
+                    cpp_name,
+                    field.offset
+                ));
+                self.generate_offset_check(field_name);
+            }
+        }
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: Some(format!(
+                "static_assert({}, \"autocxx: layout of {} differs from that expected by Rust\");",
+                conditions.join(" && "),
+                cpp_name
+            )),
+            declaration: None,
+            headers: vec![Header::system("cstddef")],
+        })
+    }
+
+    /// bindgen doesn't tell us which fields are private, and C++ won't let
+    /// us ask for their offsets, so we check each offset using a template
+    /// which is vacuously true unless the field is an accessible data member.
+    /// This also means it's harmless to check a name which isn't a field
+    /// at all.
+    fn generate_offset_check(&mut self, field_name: &str) {
+        if !self.offset_check_fields.insert(field_name.to_string()) {
+            return;
+        }
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: Some(format!(
+                indoc! {"
+                namespace {ns} {{
+                template <typename T, std::size_t N, typename = void>
+                struct offset_of_{field} : std::true_type {{}};
+                template <typename T, std::size_t N>
+                struct offset_of_{field}<T, N, std::enable_if_t<std::is_member_object_pointer<decltype(&T::{field})>::value>>
+                    : std::integral_constant<bool, offsetof(T, {field}) == N> {{}};
+                }}"},
+                ns = self.offset_check_namespace(),
+                field = field_name
+            )),
+            declaration: None,
+            headers: vec![Header::system("cstddef"), Header::system("type_traits")],
+        })
+    }
+
+    fn offset_check_namespace(&self) -> String {
+        format!("autocxx_offset_checks_{}", self.config.get_mod_name())
+    }
+
+    /// Destroys an object held within a storage type, without
+    /// freeing the memory, which belongs to Rust.
+    fn generate_storage_destructor(&mut self, tn: &QualifiedName) {
+        let cpp_name = namespaced_name_using_original_name_map(tn, &self.original_name_map);
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: None,
+            declaration: Some(format!(
+                "inline void {}({}* arg0) {{ using T = {}; arg0->~T(); }}",
+                storage_destructor_name(tn),
+                cpp_name,
+                cpp_name
+            )),
+            headers: Vec::new(),
+        })
+    }
+
     fn generate_ctype_typedef(&mut self, tn: &QualifiedName) {
         let cpp_name = tn.to_cpp_name();
         self.generate_typedef(tn, cpp_name)
@@ -293,3 +446,49 @@ impl<'a> CppCodeGenerator<'a> {
         })
     }
 }
+
+/// The name of the C++ function which we generate to destroy an object
+/// held within the storage type for the given type.
+pub(crate) fn storage_destructor_name(tn: &QualifiedName) -> String {
+    format!("{}_destroy_autocxx_wrapper", tn.get_final_item())
+}
+
+/// Generates a C++ function which evaluates the given macro. This is
+/// given to bindgen, so that we bind to it like any other function,
+/// and also output as part of our generated C++.
+pub(crate) fn macro_fn_definition(macro_fn: &MacroFn) -> String {
+    let name = &macro_fn.name;
+    let ret_type = macro_fn
+        .return_type
+        .clone()
+        .unwrap_or_else(|| format!("decltype({})", name));
+    let (params, call) = match &macro_fn.param_types {
+        None => (String::new(), name.clone()),
+        Some(param_types) => (
+            param_types
+                .iter()
+                .enumerate()
+                .map(|(counter, ty)| format!("{} arg{}", ty, counter))
+                .join(", "),
+            format!(
+                "{}({})",
+                name,
+                (0..param_types.len())
+                    .map(|counter| format!("arg{}", counter))
+                    .join(", ")
+            ),
+        ),
+    };
+    let body = if ret_type == "void" {
+        call
+    } else {
+        format!("return {}", call)
+    };
+    format!(
+        "inline {} {}({}) {{ {}; }}",
+        ret_type,
+        macro_fn.wrapper_name(),
+        params,
+        body
+    )
+}
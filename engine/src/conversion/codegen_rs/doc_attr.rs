@@ -21,3 +21,14 @@ pub(super) fn get_doc_attr(attrs: &[Attribute]) -> Option<Attribute> {
         .find(|a| a.path.get_ident().iter().any(|p| *p == "doc"))
         .cloned()
 }
+
+/// Returns the attributes, such as `#[must_use]`, which we carry across
+/// to the function people call: either our Rust wrapper function, or
+/// the `cxx::bridge` function itself.
+pub(super) fn get_passthrough_attrs(attrs: &[Attribute]) -> Vec<Attribute> {
+    attrs
+        .iter()
+        .filter(|a| a.path.is_ident("must_use") || a.path.is_ident("deprecated"))
+        .cloned()
+        .collect()
+}
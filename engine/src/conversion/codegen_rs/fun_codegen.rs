@@ -16,18 +16,20 @@ use proc_macro2::TokenStream;
 use quote::quote;
 use syn::{
     parse::Parser, parse_quote, punctuated::Punctuated, token::Unsafe, Attribute, FnArg,
-    ForeignItem, Ident, ImplItem, Item, ReturnType,
+    ForeignItem, ImplItem, Item, ReturnType, Type,
 };
 
 use super::{
-    doc_attr::get_doc_attr,
+    doc_attr::{get_doc_attr, get_passthrough_attrs},
     unqualify::{unqualify_params, unqualify_ret_type},
     RsCodegenResult, Use,
 };
 use crate::{conversion::api::FuncToConvert, types::make_ident};
 use crate::{
     conversion::{
-        analysis::fun::{ArgumentAnalysis, FnAnalysisBody, FnKind, MethodKind, RustRenameStrategy},
+        analysis::fun::{
+            ArgumentAnalysis, FnAnalysisBody, FnKind, MethodKind, RustRenameStrategy, StatusCheck,
+        },
         api::ImplBlockDetails,
     },
     types::{Namespace, QualifiedName},
@@ -48,6 +50,10 @@ pub(super) fn gen_function(
     let vis = analysis.vis;
     let kind = analysis.kind;
     let doc_attr = get_doc_attr(&fun.item.attrs);
+    let mut passthrough_attrs = get_passthrough_attrs(&fun.item.attrs);
+    let generics = analysis
+        .output_lifetime
+        .map(|lifetime| quote! { < #lifetime > });
 
     let mut cpp_name_attr = Vec::new();
     let mut impl_entry = None;
@@ -56,6 +62,17 @@ pub(super) fn gen_function(
     } else {
         None
     };
+    // Raw pointers which the Rust wrapper presents safely are still raw
+    // pointers in the cxx::bridge.
+    let bridge_unsafety: Option<Unsafe> = if analysis.requires_unsafe
+        || param_details
+            .iter()
+            .any(|pd| pd.conversion.wraps_raw_pointer())
+    {
+        Some(parse_quote!(unsafe))
+    } else {
+        None
+    };
     let rust_name_attr: Vec<_> = match &analysis.rust_rename_strategy {
         RustRenameStrategy::RenameUsingRustAttr => Attribute::parse_outer
             .parse2(quote!(
@@ -77,26 +94,57 @@ pub(super) fn gen_function(
     let rust_wrapper_needed = any_param_needs_rust_conversion
         || (cxxbridge_name != rust_name && matches!(kind, FnKind::Method(..)));
     if rust_wrapper_needed {
-        if let FnKind::Method(ref type_name, ref method_kind) = kind {
+        // Attributes such as #[deprecated] belong on the wrapper which
+        // people call, rather than on the cxx::bridge function it calls.
+        let wrapper_attrs: Vec<_> = doc_attr
+            .iter()
+            .cloned()
+            .chain(passthrough_attrs.drain(..))
+            .collect();
+        let (wrapper_params, arg_list, out_params) = generate_arg_lists(
+            &param_details,
+            matches!(kind, FnKind::Method(_, MethodKind::Constructor)),
+        );
+        // The name by which the cxx::bridge exposes this function.
+        let call_name = if matches!(kind, FnKind::Function) && !rust_name_attr.is_empty() {
+            make_ident(&rust_name)
+        } else {
+            cxxbridge_name.clone()
+        };
+        let needs_unsafe_block = unsafety.is_none()
+            && (bridge_unsafety.is_some()
+                || param_details
+                    .iter()
+                    .any(|pd| pd.conversion.rust_conversion_is_unsafe()));
+        let (wrapper_ret_type, body) = generate_wrapper_body(
+            quote! { cxxbridge::#call_name ( #(#arg_list),* ) },
+            &ret_type,
+            out_params,
+            needs_unsafe_block,
+            &analysis.result_status,
+        );
+        if let FnKind::Method(ref type_name, _) = kind {
             // Method, or static method.
             impl_entry = Some(generate_method_impl(
-                &param_details,
-                matches!(method_kind, MethodKind::Constructor),
+                wrapper_params,
+                body,
                 type_name,
-                &cxxbridge_name,
                 &rust_name,
-                &ret_type,
+                &generics,
+                &wrapper_ret_type,
                 &unsafety,
-                &doc_attr,
+                &wrapper_attrs,
             ));
         } else {
             // Generate plain old function
             materialization = Use::Custom(generate_function_impl(
-                &param_details,
+                wrapper_params,
+                body,
                 &rust_name,
-                &ret_type,
+                &generics,
+                &wrapper_ret_type,
                 &unsafety,
-                &doc_attr,
+                &wrapper_attrs,
             ));
         }
     }
@@ -135,7 +183,8 @@ pub(super) fn gen_function(
         #(#rust_name_attr)*
         #(#cpp_name_attr)*
         #doc_attr
-        #vis #unsafety fn #cxxbridge_name ( #params ) #ret_type;
+        #(#passthrough_attrs)*
+        #vis #bridge_unsafety fn #cxxbridge_name #generics ( #params ) #ret_type;
     ));
     RsCodegenResult {
         extern_c_mod_item: Some(extern_c_mod_item),
@@ -147,12 +196,23 @@ pub(super) fn gen_function(
     }
 }
 
+type WrapperParams = Punctuated<FnArg, syn::Token![,]>;
+
+/// A value which C++ writes through a parameter, and which the Rust
+/// wrapper function therefore returns.
+struct OutParam {
+    ty: Type,
+    setup: TokenStream,
+    value: TokenStream,
+}
+
 fn generate_arg_lists(
     param_details: &[ArgumentAnalysis],
     is_constructor: bool,
-) -> (Punctuated<FnArg, syn::Token![,]>, Vec<TokenStream>) {
-    let mut wrapper_params: Punctuated<FnArg, syn::Token![,]> = Punctuated::new();
+) -> (WrapperParams, Vec<TokenStream>, Vec<OutParam>) {
+    let mut wrapper_params = WrapperParams::new();
     let mut arg_list = Vec::new();
+    let mut out_params = Vec::new();
 
     for pd in param_details {
         let type_name = pd.conversion.rust_wrapper_unconverted_type();
@@ -161,33 +221,113 @@ fn generate_arg_lists(
         } else {
             pd.name.clone()
         };
-        wrapper_params.push(parse_quote!(
-            #wrapper_arg_name: #type_name
-        ));
+        if let Some(ty) = pd.conversion.rust_wrapper_out_type() {
+            out_params.push(OutParam {
+                ty,
+                setup: pd.conversion.rust_out_param_setup(&wrapper_arg_name),
+                value: pd.conversion.rust_out_param_value(&wrapper_arg_name),
+            });
+        } else if pd.conversion.is_rust_wrapper_param() {
+            wrapper_params.push(parse_quote!(
+                #wrapper_arg_name: #type_name
+            ));
+        }
         arg_list.push(pd.conversion.rust_conversion(wrapper_arg_name));
     }
-    (wrapper_params, arg_list)
+    (wrapper_params, arg_list, out_params)
+}
+
+/// Generate the body of a Rust wrapper function which makes the given
+/// call into the `cxx::bridge`, and its return type, which includes the
+/// values of any out parameters: either in a tuple after the original
+/// return value, or as a `Result` if that's a status.
+fn generate_wrapper_body(
+    call: TokenStream,
+    ret_type: &ReturnType,
+    out_params: Vec<OutParam>,
+    needs_unsafe_block: bool,
+    result_status: &Option<StatusCheck>,
+) -> (ReturnType, TokenStream) {
+    let call = if needs_unsafe_block {
+        quote! { unsafe { #call } }
+    } else {
+        call
+    };
+    if out_params.is_empty() {
+        return (ret_type.clone(), call);
+    }
+    let call = match ret_type {
+        ReturnType::Default => quote! { #call; },
+        ReturnType::Type(..) => quote! { let result = #call; },
+    };
+    let out_setup = out_params.iter().map(|out_param| &out_param.setup);
+    let out_types: Vec<_> = out_params.iter().map(|out_param| &out_param.ty).collect();
+    let out_values: Vec<_> = out_params
+        .iter()
+        .map(|out_param| &out_param.value)
+        .collect();
+    let (out_type, out_value) = if out_params.len() == 1 {
+        let ty = out_types[0];
+        let value = out_values[0];
+        (quote! { #ty }, quote! { #value })
+    } else {
+        (
+            quote! { ( #(#out_types),* ) },
+            quote! { ( #(#out_values),* ) },
+        )
+    };
+    let (ret_type, ret_value) = match (ret_type, result_status) {
+        (ReturnType::Default, _) => (parse_quote! { -> #out_type }, out_value),
+        (ReturnType::Type(_, status_ty), Some(status_check)) => {
+            let is_ok = match status_check {
+                StatusCheck::True => quote! { result },
+                StatusCheck::Zero => quote! { result == 0 },
+                StatusCheck::ZeroInNewtype => quote! { result.0 == 0 },
+            };
+            (
+                parse_quote! { -> ::std::result::Result<#out_type, #status_ty> },
+                quote! {
+                    if #is_ok {
+                        Ok(#out_value)
+                    } else {
+                        Err(result)
+                    }
+                },
+            )
+        }
+        (ReturnType::Type(_, ty), None) => (
+            parse_quote! { -> ( #ty, #(#out_types),* ) },
+            quote! { ( result, #(#out_values),* ) },
+        ),
+    };
+    (
+        ret_type,
+        quote! {
+            #(#out_setup)*
+            #call
+            #ret_value
+        },
+    )
 }
 
 /// Generate an 'impl Type { methods-go-here }' item
 #[allow(clippy::too_many_arguments)] // it's true, but probably best for now
 fn generate_method_impl(
-    param_details: &[ArgumentAnalysis],
-    is_constructor: bool,
+    wrapper_params: WrapperParams,
+    body: TokenStream,
     impl_block_type_name: &QualifiedName,
-    cxxbridge_name: &Ident,
     rust_name: &str,
+    generics: &Option<TokenStream>,
     ret_type: &ReturnType,
     unsafety: &Option<Unsafe>,
-    doc_attr: &Option<Attribute>,
+    attrs: &[Attribute],
 ) -> Box<ImplBlockDetails> {
-    let (wrapper_params, arg_list) = generate_arg_lists(param_details, is_constructor);
     let rust_name = make_ident(&rust_name);
     Box::new(ImplBlockDetails {
         item: ImplItem::Method(parse_quote! {
-            #doc_attr
-            pub #unsafety fn #rust_name ( #wrapper_params ) #ret_type {
-                cxxbridge::#cxxbridge_name ( #(#arg_list),* )
+            #(#attrs)*
+            pub #unsafety fn #rust_name #generics ( #wrapper_params ) #ret_type {
+                #body
             }
         }),
         ty: impl_block_type_name.get_final_ident(),
@@ -196,18 +336,19 @@ fn generate_method_impl(
 
 /// Generate a function call wrapper
 fn generate_function_impl(
-    param_details: &[ArgumentAnalysis],
+    wrapper_params: WrapperParams,
+    body: TokenStream,
     rust_name: &str,
+    generics: &Option<TokenStream>,
     ret_type: &ReturnType,
     unsafety: &Option<Unsafe>,
-    doc_attr: &Option<Attribute>,
+    attrs: &[Attribute],
 ) -> Box<Item> {
-    let (wrapper_params, arg_list) = generate_arg_lists(param_details, false);
     let rust_name = make_ident(&rust_name);
     Box::new(Item::Fn(parse_quote! {
-        #doc_attr
-        pub #unsafety fn #rust_name ( #wrapper_params ) #ret_type {
-            cxxbridge::#rust_name ( #(#arg_list),* )
+        #(#attrs)*
+        pub #unsafety fn #rust_name #generics ( #wrapper_params ) #ret_type {
+            #body
         }
     }))
 }
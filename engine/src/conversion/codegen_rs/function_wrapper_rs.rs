@@ -16,7 +16,7 @@ use proc_macro2::TokenStream;
 use syn::{Pat, Type};
 
 use crate::conversion::analysis::fun::function_wrapper::{
-    RustConversionType, TypeConversionPolicy,
+    out_param_pointee, RustConversionType, TypeConversionPolicy,
 };
 use quote::quote;
 use syn::parse_quote;
@@ -24,15 +24,144 @@ use syn::parse_quote;
 impl TypeConversionPolicy {
     pub(super) fn rust_wrapper_unconverted_type(&self) -> Type {
         match self.rust_conversion {
-            RustConversionType::None => self.converted_rust_type(),
+            RustConversionType::None
+            | RustConversionType::FromSliceLength(_)
+            | RustConversionType::ToOutParam => self.converted_rust_type(),
             RustConversionType::FromStr => parse_quote! { impl ToCppString },
+            RustConversionType::FromOptionalReference => match &self.unwrapped_type {
+                Type::Ptr(ptr) if ptr.mutability.is_some() => {
+                    let elem = &ptr.elem;
+                    parse_quote! { Option<::std::pin::Pin<&mut #elem>> }
+                }
+                Type::Ptr(ptr) => {
+                    let elem = &ptr.elem;
+                    parse_quote! { Option<&#elem> }
+                }
+                // apply_pointer_policies only allows raw pointers.
+                _ => unreachable!(),
+            },
+            RustConversionType::FromSlice => match &self.unwrapped_type {
+                Type::Ptr(ptr) if ptr.mutability.is_some() => {
+                    let elem = &ptr.elem;
+                    parse_quote! { &mut [#elem] }
+                }
+                Type::Ptr(ptr) => {
+                    let elem = &ptr.elem;
+                    parse_quote! { &[#elem] }
+                }
+                // apply_pointer_policies only allows raw pointers.
+                _ => unreachable!(),
+            },
         }
     }
 
     pub(super) fn rust_conversion(&self, var: Pat) -> TokenStream {
-        match self.rust_conversion {
+        match &self.rust_conversion {
             RustConversionType::None => quote! { #var },
             RustConversionType::FromStr => quote! ( #var .into_cpp() ),
+            // See rust_conversion_is_unsafe.
+            RustConversionType::FromOptionalReference => {
+                if self.is_mut_pointer() {
+                    quote! {
+                        match #var {
+                            Some(r) => r.get_unchecked_mut() as *mut _,
+                            None => ::std::ptr::null_mut(),
+                        }
+                    }
+                } else {
+                    quote! {
+                        match #var {
+                            Some(r) => r as *const _,
+                            None => ::std::ptr::null(),
+                        }
+                    }
+                }
+            }
+            RustConversionType::FromSlice => {
+                if self.is_mut_pointer() {
+                    quote! { #var .as_mut_ptr() }
+                } else {
+                    quote! { #var .as_ptr() }
+                }
+            }
+            RustConversionType::FromSliceLength(slice) => quote! { #slice .len() as _ },
+            RustConversionType::ToOutParam => match out_param_pointee(&self.unwrapped_type) {
+                _ if self.is_unique_ptr_out_param() => quote! { &mut #var },
+                Some((_, true)) => quote! { #var .as_mut_ptr() },
+                _ => quote! { ::std::pin::Pin::new_unchecked(&mut *#var .as_mut_ptr()) },
+            },
+        }
+    }
+
+    /// Whether this parameter appears in the Rust wrapper function at all.
+    /// Slice lengths are calculated, and out parameters are returned.
+    pub(super) fn is_rust_wrapper_param(&self) -> bool {
+        !matches!(
+            self.rust_conversion,
+            RustConversionType::FromSliceLength(_) | RustConversionType::ToOutParam
+        )
+    }
+
+    /// If this is an out parameter, the type which the Rust wrapper function
+    /// returns for it.
+    pub(super) fn rust_wrapper_out_type(&self) -> Option<Type> {
+        match self.rust_conversion {
+            RustConversionType::ToOutParam => {
+                let ty = self.out_param_type()?;
+                Some(if self.is_unique_ptr_out_param() {
+                    parse_quote! { cxx::UniquePtr<#ty> }
+                } else {
+                    ty.clone()
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Declares the variable which receives the value of an out parameter.
+    pub(super) fn rust_out_param_setup(&self, var: &Pat) -> TokenStream {
+        if self.is_unique_ptr_out_param() {
+            quote! { let mut #var = cxx::UniquePtr::null(); }
+        } else {
+            let ty = self.out_param_type().unwrap();
+            quote! { let mut #var = ::std::mem::MaybeUninit::<#ty>::zeroed(); }
         }
     }
+
+    /// The value of an out parameter, once C++ has written to it.
+    pub(super) fn rust_out_param_value(&self, var: &Pat) -> TokenStream {
+        if self.is_unique_ptr_out_param() {
+            quote! { #var }
+        } else {
+            // The C++ wrapper constructed a value here before making the call.
+            quote! { unsafe { #var.assume_init() } }
+        }
+    }
+
+    /// Whether this is a raw pointer which the Rust wrapper function
+    /// presents safely, such that the `cxx::bridge` function itself
+    /// is still unsafe.
+    pub(super) fn wraps_raw_pointer(&self) -> bool {
+        match self.rust_conversion {
+            RustConversionType::FromOptionalReference | RustConversionType::FromSlice => true,
+            RustConversionType::ToOutParam => {
+                !self.is_unique_ptr_out_param() && matches!(self.unwrapped_type, Type::Ptr(_))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the Rust wrapper's conversion of this parameter must happen
+    /// in an unsafe context.
+    pub(super) fn rust_conversion_is_unsafe(&self) -> bool {
+        match self.rust_conversion {
+            RustConversionType::FromOptionalReference | RustConversionType::FromSlice => true,
+            RustConversionType::ToOutParam => !self.is_unique_ptr_out_param(),
+            _ => false,
+        }
+    }
+
+    fn is_mut_pointer(&self) -> bool {
+        matches!(&self.unwrapped_type, Type::Ptr(ptr) if ptr.mutability.is_some())
+    }
 }
@@ -19,6 +19,12 @@ pub(crate) fn create_impl_items(id: &Ident) -> Vec<Item> {
         Item::Impl(parse_quote! {
             impl UniquePtr<#id> {}
         }),
+        Item::Impl(parse_quote! {
+            impl SharedPtr<#id> {}
+        }),
+        Item::Impl(parse_quote! {
+            impl WeakPtr<#id> {}
+        }),
         Item::Impl(parse_quote! {
             impl CxxVector<#id> {}
         }),
@@ -17,6 +17,7 @@ mod fun_codegen;
 mod function_wrapper_rs;
 mod impl_item_creator;
 mod namespace_organizer;
+mod newtype_enum;
 mod non_pod_struct;
 mod unqualify;
 
@@ -28,23 +29,33 @@ use autocxx_parser::IncludeCppConfig;
 pub(crate) use non_pod_struct::make_non_pod;
 
 use proc_macro2::TokenStream;
-use syn::{parse_quote, ForeignItem, Ident, Item, ItemForeignMod, ItemMod};
+use syn::{
+    parse_quote, ForeignItem, Ident, ImplItem, Item, ItemForeignMod, ItemMod, ItemUnion, Type,
+};
 
-use crate::types::{make_ident, Namespace, QualifiedName};
+use crate::{
+    known_types::known_types,
+    types::{make_ident, Namespace, QualifiedName},
+};
 use impl_item_creator::create_impl_items;
 
 use self::{
     fun_codegen::gen_function,
     namespace_organizer::{HasNs, NamespaceEntries},
+    newtype_enum::generate_newtype_enum,
     non_pod_struct::new_non_pod_struct,
 };
 
+use super::codegen_cpp::storage_destructor_name;
 use super::codegen_cpp::type_to_cpp::{
     namespaced_name_using_original_name_map, original_name_map_from_apis, OriginalNameMap,
 };
 use super::{
     analysis::fun::FnAnalysis,
-    api::{AnalysisPhase, Api, ApiDetail, ImplBlockDetails, TypeKind, TypedefKind},
+    api::{
+        AnalysisPhase, Api, ApiDetail, Downcast, ImplBlockDetails, StructDetails, TypeKind,
+        TypedefKind,
+    },
 };
 use super::{convert_error::ErrorContext, ConvertError};
 use quote::quote;
@@ -63,6 +74,8 @@ enum Use {
     UsedFromCxxBridgeWithAlias(Ident),
     /// 'use' directive points to bindgen
     UsedFromBindgen,
+    /// 'use' points to bindgen with a different name
+    UsedFromBindgenWithAlias(Ident),
     /// Some kind of custom item
     Custom(Box<Item>),
 }
@@ -114,6 +127,26 @@ fn remove_nones<T>(input: Vec<Option<T>>) -> Vec<T> {
     input.into_iter().flatten().collect()
 }
 
+/// Rust requires union fields to be `Copy`, which our structs
+/// aren't, so wrap anything other than primitives in `ManuallyDrop`.
+fn make_union_fields_copy(u: &mut ItemUnion) {
+    for f in u.fields.named.iter_mut() {
+        if !is_copy(&f.ty) {
+            let ty = &f.ty;
+            f.ty = parse_quote! { ::std::mem::ManuallyDrop<#ty> };
+        }
+    }
+}
+
+fn is_copy(ty: &Type) -> bool {
+    match ty {
+        Type::Ptr(_) => true,
+        Type::Array(arr) => is_copy(&arr.elem),
+        Type::Path(typ) => known_types().is_primitive(&QualifiedName::from_type_path(typ)),
+        _ => false,
+    }
+}
+
 /// Type which handles generation of Rust code.
 /// In practice, much of the "generation" involves connecting together
 /// existing lumps of code within the Api structures.
@@ -153,7 +186,20 @@ impl<'a> RsCodeGenerator<'a> {
             .into_iter()
             .map(|api| {
                 let more_cpp_needed = api.additional_cpp().is_some();
-                let gen = self.generate_rs_for_api(&api.name, api.detail);
+                let is_type = matches!(
+                    api.detail,
+                    ApiDetail::ForwardDeclaration
+                        | ApiDetail::Typedef { .. }
+                        | ApiDetail::Struct { .. }
+                        | ApiDetail::Union { .. }
+                        | ApiDetail::Enum { .. }
+                );
+                let mut gen = self.generate_rs_for_api(&api.name, api.detail);
+                if is_type {
+                    if let Some(rust_name) = self.type_rename(&api.name) {
+                        Self::rename_type_in_output_mod(&mut gen, rust_name);
+                    }
+                }
                 ((api.name, gen), more_cpp_needed)
             })
             .unzip();
@@ -179,7 +225,9 @@ impl<'a> RsCodeGenerator<'a> {
         // And a list of global items to include at the top level.
         let mut all_items: Vec<Item> = all_items.into_iter().flatten().collect();
         // And finally any C++ we need to generate. And by "we" I mean autocxx not cxx.
-        let has_additional_cpp_needs = additional_cpp_needs.into_iter().any(std::convert::identity);
+        // Typedefs for template instantiations are always among our generated C++.
+        let has_additional_cpp_needs = additional_cpp_needs.into_iter().any(std::convert::identity)
+            || !self.config.get_instantiations().is_empty();
         extern_c_mod_items.extend(self.build_include_foreign_items(has_additional_cpp_needs));
         // We will always create an extern "C" mod even if bindgen
         // didn't generate one, e.g. because it only generated types.
@@ -267,7 +315,12 @@ impl<'a> RsCodeGenerator<'a> {
                 Use::UsedFromCxxBridge => {
                     output_items.push(Self::generate_cxx_use_stmt(name, None))
                 }
-                Use::UsedFromBindgen => output_items.push(Self::generate_bindgen_use_stmt(name)),
+                Use::UsedFromBindgen => {
+                    output_items.push(Self::generate_bindgen_use_stmt(name, None))
+                }
+                Use::UsedFromBindgenWithAlias(alias) => {
+                    output_items.push(Self::generate_bindgen_use_stmt(name, Some(alias)))
+                }
                 Use::Unused => {}
                 Use::Custom(item) => output_items.push(*item.clone()),
             };
@@ -328,17 +381,23 @@ impl<'a> RsCodeGenerator<'a> {
         ns: &Namespace,
         generate_utilities: bool,
     ) {
-        let mut impl_entries_by_type: HashMap<_, Vec<_>> = HashMap::new();
+        // Kept in the order in which we first see each type, so that our
+        // output is the same each time.
+        let mut impl_entries_by_type: Vec<(&Ident, Vec<_>)> = Vec::new();
+        let mut impl_entry_positions: HashMap<&Ident, usize> = HashMap::new();
         for item in ns_entries.entries() {
             output_items.extend(item.1.bindgen_mod_item.iter().cloned());
             if let Some(impl_entry) = &item.1.impl_entry {
-                impl_entries_by_type
-                    .entry(impl_entry.ty.clone())
-                    .or_default()
-                    .push(&impl_entry.item);
+                let pos = *impl_entry_positions
+                    .entry(&impl_entry.ty)
+                    .or_insert_with(|| {
+                        impl_entries_by_type.push((&impl_entry.ty, Vec::new()));
+                        impl_entries_by_type.len() - 1
+                    });
+                impl_entries_by_type[pos].1.push(&impl_entry.item);
             }
         }
-        for (ty, entries) in impl_entries_by_type.into_iter() {
+        for (ty, entries) in impl_entries_by_type {
             output_items.push(Item::Impl(parse_quote! {
                 impl #ty {
                     #(#entries)*
@@ -415,7 +474,11 @@ impl<'a> RsCodeGenerator<'a> {
             ApiDetail::ForwardDeclaration => RsCodegenResult {
                 extern_c_mod_item: Some(ForeignItem::Verbatim(self.generate_cxxbridge_type(name))),
                 bridge_items: Vec::new(),
-                global_items: self.generate_extern_type_impl(TypeKind::NonPod, &name),
+                global_items: self
+                    .generate_extern_type_impl(TypeKind::NonPod, &name)
+                    .into_iter()
+                    .chain(self.generate_send_sync_impls(name))
+                    .collect(),
                 bindgen_mod_item: Some(Item::Struct(new_non_pod_struct(id))),
                 impl_entry: None,
                 materialization: Use::UsedFromCxxBridge,
@@ -442,11 +505,96 @@ impl<'a> RsCodeGenerator<'a> {
                 impl_entry: None,
                 materialization: Use::UsedFromBindgen,
             },
-            ApiDetail::Struct { item, analysis } => {
-                self.generate_type(name, id, item, analysis, Item::Struct)
+            ApiDetail::Struct {
+                item,
+                details,
+                analysis,
+            } => {
+                let has_storage = details.has_storage;
+                let mut result = self.generate_type(name, id.clone(), item, analysis, Item::Struct);
+                let mut impl_items = match (analysis, details.layout) {
+                    (TypeKind::Pod, _) => {
+                        result
+                            .global_items
+                            .extend(Self::generate_layout_assertion(name, &details));
+                        let accessors = &details.bitfield_accessors;
+                        quote! {
+                            #(#accessors)*
+                        }
+                    }
+                    (_, Some(layout)) => {
+                        result
+                            .global_items
+                            .extend(Self::generate_layout_assertion(name, &details));
+                        let size = layout.size;
+                        let align = layout.align;
+                        quote! {
+                            /// The size of this type in bytes, as calculated by bindgen.
+                            pub const SIZE: usize = #size;
+                            /// The alignment of this type in bytes, as calculated by bindgen.
+                            pub const ALIGN: usize = #align;
+                        }
+                    }
+                    _ => TokenStream::new(),
+                };
+                if details.has_downcasts {
+                    impl_items.extend(Self::generate_downcast_methods());
+                }
+                if !impl_items.is_empty() {
+                    result.impl_entry = Some(Box::new(ImplBlockDetails {
+                        item: ImplItem::Verbatim(impl_items),
+                        ty: id,
+                    }));
+                }
+                if has_storage {
+                    self.add_storage_type(name, &mut result);
+                }
+                result
+                    .global_items
+                    .extend(Self::generate_as_ref_impls(name, &details.upcasts));
+                result
+                    .global_items
+                    .extend(Self::generate_downcast_impls(name, &details.downcasts_from));
+                result
+            }
+            ApiDetail::Union {
+                mut item,
+                details,
+                analysis,
+            } => {
+                make_union_fields_copy(&mut item);
+                let mut result = self.generate_type(name, id, item, analysis, Item::Union);
+                if analysis == TypeKind::Pod {
+                    result
+                        .global_items
+                        .extend(Self::generate_layout_assertion(name, &details));
+                }
+                result
             }
+            ApiDetail::BitfieldUnit { items } => RsCodegenResult {
+                global_items: Vec::new(),
+                impl_entry: None,
+                bridge_items: Vec::new(),
+                extern_c_mod_item: None,
+                bindgen_mod_item: Some(Item::Verbatim(quote! {
+                    #(#items)*
+                })),
+                materialization: Use::Unused,
+            },
             ApiDetail::Enum { item, analysis } => {
-                self.generate_type(name, id, item, analysis, Item::Enum)
+                let cpp_name = name.to_cpp_name();
+                if self.config.is_newtype_enum(&cpp_name) {
+                    let (newtype, impls) = generate_newtype_enum(
+                        item,
+                        &name.get_bindgen_path_idents(),
+                        self.config.is_bitflags_enum(&cpp_name),
+                    );
+                    let mut result = self.generate_type(name, id, newtype, analysis, Item::Struct);
+                    result.global_items.extend(impls);
+                    result
+                } else {
+                    self.generate_type(name, id, item, analysis, Item::Enum)
+                }
             }
             ApiDetail::CType { .. } => RsCodegenResult {
                 global_items: Vec::new(),
@@ -462,6 +610,23 @@ impl<'a> RsCodeGenerator<'a> {
         }
     }
 
+    /// The Rust name requested for this type by a `rename!` directive, if any.
+    fn type_rename(&self, name: &QualifiedName) -> Option<Ident> {
+        let cpp_name = namespaced_name_using_original_name_map(name, &self.original_name_map);
+        self.config.rename_for_type(&cpp_name).map(make_ident)
+    }
+
+    /// Exposes a type in the output mod under a different name. Internally,
+    /// including in the cxx::bridge and the bindgen mod, it keeps its
+    /// original name.
+    fn rename_type_in_output_mod(result: &mut RsCodegenResult, rust_name: Ident) {
+        result.materialization = match std::mem::replace(&mut result.materialization, Use::Unused) {
+            Use::UsedFromCxxBridge => Use::UsedFromCxxBridgeWithAlias(rust_name),
+            Use::UsedFromBindgen => Use::UsedFromBindgenWithAlias(rust_name),
+            other => other,
+        }
+    }
+
     fn generate_type<T, F>(
         &self,
         name: &QualifiedName,
@@ -474,7 +639,11 @@ impl<'a> RsCodeGenerator<'a> {
         F: FnOnce(T) -> Item,
     {
         RsCodegenResult {
-            global_items: self.generate_extern_type_impl(analysis, &name),
+            global_items: self
+                .generate_extern_type_impl(analysis, &name)
+                .into_iter()
+                .chain(self.generate_send_sync_impls(name))
+                .collect(),
             impl_entry: None,
             bridge_items: if analysis.can_be_instantiated() {
                 create_impl_items(&id)
@@ -487,6 +656,240 @@ impl<'a> RsCodeGenerator<'a> {
         }
     }
 
+    /// Generates a type which can hold an instance of an opaque C++ type
+    /// inline, for use as a field within Rust structs. The object within
+    /// is only ever created by constructing it in place, using the `emplace`
+    /// equivalents of its `make_unique` functions.
+    /// The type itself has the right size and alignment, since we know its
+    /// layout.
+    fn add_storage_type(&self, name: &QualifiedName, result: &mut RsCodegenResult) {
+        let id = name.get_final_ident();
+        let storage_name = QualifiedName::new(
+            name.get_namespace(),
+            make_ident(format!("{}Storage", name.get_final_item())),
+        );
+        let storage_id = storage_name.get_final_ident();
+        let destructor = make_ident(storage_destructor_name(name));
+        let storage_doc = format!(
+            "Space to hold a [`{}`] within a Rust type, without a separate heap allocation.",
+            id
+        );
+        let type_item = result.bindgen_mod_item.take();
+        result.bindgen_mod_item = Some(Item::Verbatim(quote! {
+            #type_item
+            #[doc = #storage_doc]
+            pub struct #storage_id {
+                storage: ::std::mem::MaybeUninit<#id>,
+                initialized: bool,
+                _pinned: ::std::marker::PhantomPinned,
+            }
+            impl #storage_id {
+                /// Creates empty storage.
+                pub fn new() -> Self {
+                    Self {
+                        storage: ::std::mem::MaybeUninit::uninit(),
+                        initialized: false,
+                        _pinned: ::std::marker::PhantomPinned,
+                    }
+                }
+                /// Whether an object has been constructed within this storage.
+                pub fn is_initialized(&self) -> bool {
+                    self.initialized
+                }
+                /// Constructs an object within this storage, destroying any
+                /// object which was previously there.
+                ///
+                /// # Safety
+                ///
+                /// `construct` must construct a valid object at the address it's
+                /// given, for example by calling one of the `emplace` functions.
+                pub unsafe fn emplace(
+                    self: ::std::pin::Pin<&mut Self>,
+                    construct: impl FnOnce(*mut #id),
+                ) -> ::std::pin::Pin<&mut #id> {
+                    let this = self.get_unchecked_mut();
+                    this.destroy();
+                    construct(this.storage.as_mut_ptr());
+                    this.initialized = true;
+                    ::std::pin::Pin::new_unchecked(&mut *this.storage.as_mut_ptr())
+                }
+                /// The object within this storage, if one has been constructed.
+                pub fn get(&self) -> Option<&#id> {
+                    if self.initialized {
+                        Some(unsafe { &*self.storage.as_ptr() })
+                    } else {
+                        None
+                    }
+                }
+                /// The object within this storage, if one has been constructed.
+                pub fn get_mut(self: ::std::pin::Pin<&mut Self>) -> Option<::std::pin::Pin<&mut #id>> {
+                    let this = unsafe { self.get_unchecked_mut() };
+                    if this.initialized {
+                        Some(unsafe {
+                            ::std::pin::Pin::new_unchecked(&mut *this.storage.as_mut_ptr())
+                        })
+                    } else {
+                        None
+                    }
+                }
+                fn destroy(&mut self) {
+                    if self.initialized {
+                        self.initialized = false;
+                        unsafe { cxxbridge::#destructor(self.storage.as_mut_ptr()) }
+                    }
+                }
+            }
+            impl Default for #storage_id {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+            impl Drop for #storage_id {
+                fn drop(&mut self) {
+                    self.destroy()
+                }
+            }
+        }));
+        let type_decl = result.extern_c_mod_item.take();
+        result.extern_c_mod_item = Some(ForeignItem::Verbatim(quote! {
+            #type_decl
+            unsafe fn #destructor(arg0: *mut #id);
+        }));
+        let alias = self.type_rename(name);
+        let storage_alias = alias
+            .as_ref()
+            .map(|alias| make_ident(format!("{}Storage", alias)));
+        let cxx_use = Self::generate_cxx_use_stmt(name, alias.as_ref());
+        let storage_use = Self::generate_bindgen_use_stmt(&storage_name, storage_alias.as_ref());
+        result.materialization = Use::Custom(Box::new(Item::Verbatim(quote! {
+            #cxx_use
+            #storage_use
+        })));
+    }
+
+    /// Implements `AsRef` for each base class, using the upcast functions
+    /// generated during function analysis.
+    fn generate_as_ref_impls(name: &QualifiedName, upcasts: &[QualifiedName]) -> Vec<Item> {
+        let path = name.get_bindgen_path_idents();
+        upcasts
+            .iter()
+            .map(|base| {
+                let base_path = base.get_bindgen_path_idents();
+                let upcast = make_ident(format!("as_{}", base.get_final_item()));
+                Item::Impl(parse_quote! {
+                    impl AsRef<#(#base_path)::*> for #(#path)::* {
+                        fn as_ref(&self) -> &#(#base_path)::* {
+                            self.#upcast()
+                        }
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Generic methods for a polymorphic base class, which downcast to
+    /// any type implementing `autocxx::DowncastFrom` for it.
+    fn generate_downcast_methods() -> TokenStream {
+        quote! {
+            /// Converts to a reference to the derived type `T`, if this
+            /// object is actually of that type, using `dynamic_cast`.
+            pub fn downcast_ref<T: autocxx::DowncastFrom<Self>>(&self) -> Option<&T> {
+                T::downcast_from(self)
+            }
+            /// Converts to a mutable reference to the derived type `T`, if
+            /// this object is actually of that type, using `dynamic_cast`.
+            pub fn downcast_mut<T: autocxx::DowncastFrom<Self>>(
+                self: ::std::pin::Pin<&mut Self>,
+            ) -> Option<::std::pin::Pin<&mut T>> {
+                T::downcast_from_mut(self)
+            }
+        }
+    }
+
+    /// Implements `autocxx::DowncastFrom` for each polymorphic base class,
+    /// using the downcast functions generated during function analysis.
+    fn generate_downcast_impls(name: &QualifiedName, downcasts: &[Downcast]) -> Vec<Item> {
+        let path = name.get_bindgen_path_idents();
+        downcasts
+            .iter()
+            .map(|downcast| {
+                let base_path = downcast.base.get_bindgen_path_idents();
+                let cxxbridge_name = &downcast.cxxbridge_name;
+                let mut_cxxbridge_name = &downcast.mut_cxxbridge_name;
+                Item::Impl(parse_quote! {
+                    impl autocxx::DowncastFrom<#(#base_path)::*> for #(#path)::* {
+                        fn downcast_from(base: &#(#base_path)::*) -> Option<&Self> {
+                            // dynamic_cast returns either null or a pointer
+                            // to the same object, so has the same lifetime.
+                            unsafe { cxxbridge::#cxxbridge_name(base).as_ref() }
+                        }
+                        fn downcast_from_mut(
+                            base: ::std::pin::Pin<&mut #(#base_path)::*>,
+                        ) -> Option<::std::pin::Pin<&mut Self>> {
+                            unsafe {
+                                cxxbridge::#mut_cxxbridge_name(base)
+                                    .as_mut()
+                                    .map(|derived| ::std::pin::Pin::new_unchecked(derived))
+                            }
+                        }
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Checks at compile time that a type has the size and alignment
+    /// which bindgen calculated, and on which C++ (see `CppCodeGenerator`)
+    /// also insists for POD types. We can't use `assert!` in a `const` in the Rust
+    /// versions we support, so instead a mismatch makes the calculation of
+    /// an array length overflow. Field offsets can't be calculated in a `const` context at
+    /// all, so those are checked in a test, as bindgen itself does.
+    fn generate_layout_assertion(name: &QualifiedName, details: &StructDetails) -> Vec<Item> {
+        let layout = match details.layout {
+            Some(layout) => layout,
+            None => return Vec::new(),
+        };
+        let path = name.get_bindgen_path_idents();
+        let size = layout.size;
+        let align = layout.align;
+        let mut items = vec![Item::Verbatim(quote! {
+            const _: [(); 0 - !(
+                ::std::mem::size_of::<#(#path)::*>() == #size
+                    && ::std::mem::align_of::<#(#path)::*>() == #align
+            ) as usize] = [];
+        })];
+        if !details.field_offsets.is_empty() {
+            // Double underscores are reserved in C++ identifiers, so this
+            // won't collide with the test for another type.
+            let test_name = make_ident(format!(
+                "autocxx_layout_test_{}",
+                name.ns_segment_iter()
+                    .map(|s| s.as_str())
+                    .chain(std::iter::once(name.get_final_item()))
+                    .collect::<Vec<_>>()
+                    .join("__")
+            ));
+            let fields = details.field_offsets.iter().map(|field| &field.rust_name);
+            let offsets = details.field_offsets.iter().map(|field| field.offset);
+            items.push(parse_quote! {
+                #[test]
+                #[allow(non_snake_case)]
+                fn #test_name() {
+                    let uninit = ::std::mem::MaybeUninit::<#(#path)::*>::uninit();
+                    let ptr = uninit.as_ptr();
+                    #(
+                        assert_eq!(
+                            unsafe { &(*ptr).#fields as *const _ as usize - ptr as usize },
+                            #offsets,
+                            concat!("Offset of field ", stringify!(#fields))
+                        );
+                    )*
+                }
+            });
+        }
+        items
+    }
+
     /// Generates something in the output mod that will carry a docstring
     /// explaining why a given type or function couldn't have bindings
     /// generated.
@@ -536,11 +939,16 @@ impl<'a> RsCodeGenerator<'a> {
         })
     }
 
-    fn generate_bindgen_use_stmt(name: &QualifiedName) -> Item {
+    fn generate_bindgen_use_stmt(name: &QualifiedName, alias: Option<&Ident>) -> Item {
         let segs =
             Self::find_output_mod_root(name.get_namespace()).chain(name.get_bindgen_path_idents());
-        Item::Use(parse_quote! {
-            pub use #(#segs)::*;
+        Item::Use(match alias {
+            None => parse_quote! {
+                pub use #(#segs)::*;
+            },
+            Some(alias) => parse_quote! {
+                pub use #(#segs)::* as #alias;
+            },
         })
     }
 
@@ -560,6 +968,27 @@ impl<'a> RsCodeGenerator<'a> {
         })]
     }
 
+    /// Implements `Send` and `Sync` for types which the user has asserted
+    /// are thread-safe using `send!(unsafe ...)` or `sync!(unsafe ...)`,
+    /// just as they would with an `unsafe impl`. Through cxx's own impls,
+    /// `UniquePtr`s of these types then become `Send` or `Sync` too.
+    fn generate_send_sync_impls(&self, tyname: &QualifiedName) -> Vec<Item> {
+        let cpp_name = namespaced_name_using_original_name_map(tyname, &self.original_name_map);
+        let fulltypath = tyname.get_bindgen_path_idents();
+        let mut impls = Vec::new();
+        if self.config.is_send(&cpp_name) {
+            impls.push(Item::Impl(parse_quote! {
+                unsafe impl Send for #(#fulltypath)::* {}
+            }));
+        }
+        if self.config.is_sync(&cpp_name) {
+            impls.push(Item::Impl(parse_quote! {
+                unsafe impl Sync for #(#fulltypath)::* {}
+            }));
+        }
+        impls
+    }
+
     fn generate_cxxbridge_type(&self, name: &QualifiedName) -> TokenStream {
         let ns = name.get_namespace();
         let id = name.get_final_ident();
@@ -0,0 +1,154 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use syn::{parse_quote, Attribute, Expr, Ident, ImplItem, Item, ItemEnum, ItemStruct, Type};
+
+/// Converts a bindgen-generated enum into a newtype wrapper around
+/// its underlying integer type, with an associated constant for each
+/// variant. Unlike a Rust enum, this can safely hold any value which
+/// C++ hands back to us. Returns the new struct, which belongs in
+/// the bindgen mod, and any impl blocks, which refer to the struct
+/// by its full path.
+pub(super) fn generate_newtype_enum(
+    item: ItemEnum,
+    fulltypath: &[Ident],
+    bitflags: bool,
+) -> (ItemStruct, Vec<Item>) {
+    let id = item.ident;
+    let repr = get_repr(&item.attrs);
+    let doc_attrs = item.attrs.iter().filter(|a| a.path.is_ident("doc"));
+    let newtype = parse_quote! {
+        #(#doc_attrs)*
+        #[repr(transparent)]
+        #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+        pub struct #id(pub #repr);
+    };
+    let mut next_value: Expr = parse_quote! { 0 };
+    let mut variant_ids = Vec::new();
+    let consts: Vec<ImplItem> = item
+        .variants
+        .into_iter()
+        .map(|v| {
+            let value = v.discriminant.map_or(next_value.clone(), |(_, e)| e);
+            next_value = parse_quote! { #value + 1 };
+            let variant_id = v.ident;
+            let doc_attrs = v.attrs.into_iter().filter(|a| a.path.is_ident("doc"));
+            let konst = parse_quote! {
+                #(#doc_attrs)*
+                pub const #variant_id: Self = Self(#value);
+            };
+            variant_ids.push(variant_id);
+            konst
+        })
+        .collect();
+    let mut impls = vec![
+        Item::Impl(parse_quote! {
+            #[allow(non_upper_case_globals)]
+            impl #(#fulltypath)::* {
+                #(#consts)*
+            }
+        }),
+        Item::Impl(parse_quote! {
+            impl From<#(#fulltypath)::*> for #repr {
+                fn from(value: #(#fulltypath)::*) -> Self {
+                    value.0
+                }
+            }
+        }),
+    ];
+    if bitflags {
+        // Any combination of bits is meaningful.
+        impls.push(Item::Impl(parse_quote! {
+            impl From<#repr> for #(#fulltypath)::* {
+                fn from(value: #repr) -> Self {
+                    Self(value)
+                }
+            }
+        }));
+        impls.extend(generate_bitwise_ops(fulltypath));
+    } else {
+        impls.push(generate_try_from(fulltypath, &repr, &variant_ids));
+    }
+    (newtype, impls)
+}
+
+/// Generates a `TryFrom` implementation which accepts only the values
+/// which correspond to a known enumerator, returning the original value
+/// as the error otherwise.
+fn generate_try_from(fulltypath: &[Ident], repr: &Type, variant_ids: &[Ident]) -> Item {
+    let body: Expr = if variant_ids.is_empty() {
+        parse_quote! { Err(value) }
+    } else {
+        parse_quote! {
+            match Self(value) {
+                #(Self::#variant_ids)|* => Ok(Self(value)),
+                _ => Err(value),
+            }
+        }
+    };
+    Item::Impl(parse_quote! {
+        impl std::convert::TryFrom<#repr> for #(#fulltypath)::* {
+            type Error = #repr;
+            fn try_from(value: #repr) -> Result<Self, Self::Error> {
+                #body
+            }
+        }
+    })
+}
+
+fn generate_bitwise_ops(fulltypath: &[Ident]) -> Vec<Item> {
+    vec![
+        Item::Impl(parse_quote! {
+            impl std::ops::BitOr for #(#fulltypath)::* {
+                type Output = Self;
+                fn bitor(self, other: Self) -> Self {
+                    Self(self.0 | other.0)
+                }
+            }
+        }),
+        Item::Impl(parse_quote! {
+            impl std::ops::BitOrAssign for #(#fulltypath)::* {
+                fn bitor_assign(&mut self, other: Self) {
+                    self.0 |= other.0;
+                }
+            }
+        }),
+        Item::Impl(parse_quote! {
+            impl std::ops::BitAnd for #(#fulltypath)::* {
+                type Output = Self;
+                fn bitand(self, other: Self) -> Self {
+                    Self(self.0 & other.0)
+                }
+            }
+        }),
+        Item::Impl(parse_quote! {
+            impl std::ops::BitAndAssign for #(#fulltypath)::* {
+                fn bitand_assign(&mut self, other: Self) {
+                    self.0 &= other.0;
+                }
+            }
+        }),
+    ]
+}
+
+/// bindgen annotates each enum with `#[repr(u32)]` or similar
+/// to indicate the underlying integer type.
+fn get_repr(attrs: &[Attribute]) -> Type {
+    attrs
+        .iter()
+        .filter(|a| a.path.is_ident("repr"))
+        .filter_map(|a| a.parse_args::<Ident>().ok())
+        .next()
+        .map_or_else(|| parse_quote! { i32 }, |id| parse_quote! { #id })
+}
@@ -13,8 +13,8 @@
 // limitations under the License.
 
 use super::doc_attr::get_doc_attr;
-use crate::types::make_ident;
-use proc_macro2::Ident;
+use crate::{conversion::api::Layout, types::make_ident};
+use proc_macro2::{Ident, Literal};
 use quote::quote;
 use syn::parse::Parser;
 use syn::{parse_quote, Field, GenericParam, ItemStruct};
@@ -24,12 +24,17 @@ pub(crate) fn new_non_pod_struct(id: Ident) -> ItemStruct {
         pub struct #id {
         }
     };
-    make_non_pod(&mut s);
+    make_non_pod(&mut s, None);
     s
 }
 
-pub(crate) fn make_non_pod(s: &mut ItemStruct) {
-    // Keep only doc attrs, plus add a #[repr(C,packed)].
+/// Replaces the fields of a type with opaque storage. If we know the
+/// layout which bindgen calculated, the type has that size and alignment,
+/// so that it may be held inline within Rust types. Otherwise it's
+/// zero-sized.
+pub(crate) fn make_non_pod(s: &mut ItemStruct, layout: Option<Layout>) {
+    // Keep only doc attrs, plus add a #[repr].
+    // Without a layout, that's #[repr(C,packed)].
     // Thanks to dtolnay@ for this explanation of why the following
     // is needed:
     // If the real alignment of the C++ type is smaller and a reference
@@ -38,11 +43,16 @@ pub(crate) fn make_non_pod(s: &mut ItemStruct) {
     // by Rust code
     // (see https://doc.rust-lang.org/1.47.0/reference/behavior-considered-undefined.html).
     // Rustc can use least-significant bits of the reference for other storage.
+    let repr = match layout {
+        Some(layout) => {
+            let align = Literal::usize_unsuffixed(layout.align);
+            parse_quote!(#[repr(C, align(#align))])
+        }
+        None => parse_quote!(#[repr(C, packed)]),
+    };
     let attrs = get_doc_attr(&s.attrs)
         .into_iter()
-        .chain(std::iter::once(parse_quote!(
-            #[repr(C, packed)]
-        )));
+        .chain(std::iter::once(repr));
     s.attrs = attrs.collect();
     // Now fill in fields. Usually, we just want a single field
     // but if this is a generic type we need to faff a bit.
@@ -63,12 +73,23 @@ pub(crate) fn make_non_pod(s: &mut ItemStruct) {
             }
             _ => None,
         });
+    let storage_field = layout.into_iter().map(|layout| -> Field {
+        let size = layout.size;
+        let parser = Field::parse_named;
+        parser
+            .parse2(quote! {
+                _storage: ::std::mem::MaybeUninit<[u8; #size]>
+            })
+            .unwrap()
+    });
     // See cxx's opaque::Opaque for rationale for this type... in
-    // short, it's to avoid being Send/Sync.
+    // short, it's to avoid being Send/Sync. It's within a PhantomData
+    // so as not to affect the alignment.
     s.fields = syn::Fields::Named(parse_quote! {
         {
-            do_not_attempt_to_allocate_nonpod_types: [*const u8; 0],
+            do_not_attempt_to_allocate_nonpod_types: ::std::marker::PhantomData<[*const u8; 0]>,
             _pinned: core::marker::PhantomData<core::marker::PhantomPinned>,
+            #(#storage_field,)*
             #(#generic_type_fields),*
         }
     });
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::clang_fn_info::ClangFnInfo;
 use autocxx_parser::UnsafePolicy;
 #[allow(unused_imports)]
 use syn::parse_quote;
@@ -33,8 +34,13 @@ fn do_test(input: ItemMod) {
     let tc = parse_quote! {};
     let bc = BridgeConverter::new(&[], &tc);
     let inclusions = "".into();
-    bc.convert(input, UnsafePolicy::AllFunctionsSafe, inclusions)
-        .unwrap();
+    bc.convert(
+        input,
+        UnsafePolicy::AllFunctionsSafe,
+        inclusions,
+        &ClangFnInfo::default(),
+    )
+    .unwrap();
 }
 
 // How to add a test here
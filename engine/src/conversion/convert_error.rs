@@ -35,6 +35,7 @@ pub enum ConvertError {
     UnsupportedType(String),
     UnknownType(String),
     StaticData(String),
+    UnrecognizedStaticName(String),
     InfinitelyRecursiveTypedef(QualifiedName),
     UnexpectedUseStatement(Option<Ident>),
     TemplatedTypeContainingNonPathArg(QualifiedName),
@@ -47,6 +48,9 @@ pub enum ConvertError {
     UnknownDependentType,
     IgnoredDependent,
     MoveConstructorUnsupported,
+    InvalidPointerParam(String),
+    InvalidOutParam(String),
+    UnsuitableStatusType,
 }
 
 fn format_maybe_identifier(id: &Option<Ident>) -> String {
@@ -70,10 +74,11 @@ impl Display for ConvertError {
             ConvertError::VirtualThisType(ns, fn_name) => write!(f, "Member function encountered where the 'this' type is 'void*', but we were unable to recognize which type that corresponds to. Function {}{}.", fn_name, ns.to_display_suffix())?,
             ConvertError::ConflictingTemplatedArgsWithTypedef(tn) => write!(f, "Type {} has templated arguments and so does the typedef to which it points", tn)?,
             ConvertError::UnacceptableParam(fn_name) => write!(f, "Function {} has a parameter or return type which is either on the blocklist or a forward declaration", fn_name)?,
-            ConvertError::NotOneInputReference(fn_name) => write!(f, "Function {} has a return reference parameter, but 0 or >1 input reference parameters, so the lifetime of the output reference cannot be deduced.", fn_name)?,
+            ConvertError::NotOneInputReference(fn_name) => write!(f, "Function {} has a return reference parameter, but 0 or >1 input reference parameters, so the lifetime of the output reference cannot be deduced. Use borrows_from! to say which parameter it borrows from.", fn_name)?,
             ConvertError::UnsupportedType(ty_desc) => write!(f, "Encountered type not yet supported by autocxx: {}", ty_desc)?,
             ConvertError::UnknownType(ty_desc) => write!(f, "Encountered type not yet known by autocxx: {}", ty_desc)?,
-            ConvertError::StaticData(ty_desc) => write!(f, "Encountered mutable static data, not yet supported: {}", ty_desc)?,
+            ConvertError::StaticData(ty_desc) => write!(f, "Encountered static data of pointer type, not yet supported: {}", ty_desc)?,
+            ConvertError::UnrecognizedStaticName(mangled) => write!(f, "Encountered static data whose mangled name we couldn't interpret: {}", mangled)?,
             ConvertError::InfinitelyRecursiveTypedef(tn) => write!(f, "Encountered typedef to itself - this is a known bindgen bug: {}", tn.to_cpp_name())?,
             ConvertError::UnexpectedUseStatement(maybe_ident) => write!(f, "Unexpected 'use' statement encountered: {}", format_maybe_identifier(maybe_ident))?,
             ConvertError::TemplatedTypeContainingNonPathArg(tn) => write!(f, "Type {} was parameterized over something complex which we don't yet support", tn)?,
@@ -86,6 +91,9 @@ impl Display for ConvertError {
             ConvertError::UnknownDependentType => write!(f, "This item relies on a type not known to autocxx.")?,
             ConvertError::IgnoredDependent => write!(f, "This item depends on some other type which autocxx could not generate.")?,
             ConvertError::MoveConstructorUnsupported => write!(f, "This is a move constructor, for which we currently cannot generate bindings.")?,
+            ConvertError::InvalidOutParam(param) => write!(f, "Parameter {} can't be an out parameter. It must be a non-const pointer or reference to a named type.", param)?,
+            ConvertError::UnsuitableStatusType => write!(f, "This function's out parameters can't be returned as a Result, because it doesn't return a bool or integer status.")?,
+            ConvertError::InvalidPointerParam(param) => write!(f, "Parameter {} can't be presented as requested by pointer_param!. It must be a raw pointer; slices must point to plain-old-data types, out parameters must be non-const, and a slice's length parameter must exist.", param)?,
         }
         Ok(())
     }
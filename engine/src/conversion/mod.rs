@@ -21,6 +21,7 @@ mod conversion_tests;
 mod convert_error;
 mod error_reporter;
 mod parse;
+mod summary;
 mod utilities;
 
 use analysis::fun::FnAnalyzer;
@@ -30,7 +31,7 @@ pub(crate) use convert_error::ConvertError;
 use itertools::Itertools;
 use syn::{Item, ItemMod};
 
-use crate::{CppFilePair, UnsafePolicy};
+use crate::{clang_fn_info::ClangFnInfo, CppFilePair, UnsafePolicy};
 
 use self::{
     analysis::{
@@ -43,6 +44,8 @@ use self::{
     parse::ParseBindgen,
 };
 
+pub(crate) use codegen_cpp::macro_fn_definition;
+
 const LOG_APIS: bool = true;
 
 /// Converts the bindings generated by bindgen into a form suitable
@@ -66,6 +69,7 @@ pub(crate) struct BridgeConverter<'a> {
 pub(crate) struct CodegenResults {
     pub(crate) rs: Vec<Item>,
     pub(crate) cpp: Option<CppFilePair>,
+    pub(crate) summary: String,
 }
 
 impl<'a> BridgeConverter<'a> {
@@ -97,13 +101,14 @@ impl<'a> BridgeConverter<'a> {
         mut bindgen_mod: ItemMod,
         unsafe_policy: UnsafePolicy,
         inclusions: String,
+        fn_info: &ClangFnInfo,
     ) -> Result<CodegenResults, ConvertError> {
         match &mut bindgen_mod.content {
             None => Err(ConvertError::NoContent),
             Some((_, items)) => {
                 // Parse the bindgen mod.
                 let items_to_process = items.drain(..).collect();
-                let parser = ParseBindgen::new(&self.config);
+                let parser = ParseBindgen::new(&self.config, fn_info);
                 let apis = parser.parse_items(items_to_process)?;
                 Self::dump_apis("parsing", &apis);
                 // Inside parse_results, we now have a list of APIs.
@@ -143,6 +148,8 @@ impl<'a> BridgeConverter<'a> {
                 // Determine what variably-sized C types (e.g. int) we need to include
                 analysis::ctypes::append_ctype_information(&mut analyzed_apis);
                 Self::dump_apis("GC", &analyzed_apis);
+                // Describe what we're about to generate, for humans.
+                let summary = summary::generate_summary(&analyzed_apis, self.config, fn_info);
                 // And finally pass them to the code gen phases, which outputs
                 // code suitable for cxx to consume.
                 let cpp =
@@ -153,7 +160,7 @@ impl<'a> BridgeConverter<'a> {
                     bindgen_mod,
                     &self.config,
                 );
-                Ok(CodegenResults { rs, cpp })
+                Ok(CodegenResults { rs, cpp, summary })
             }
         }
     }
@@ -0,0 +1,388 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use syn::{parse_quote, Attribute, Fields, ForeignItem, Item, Lit, Meta};
+
+/// Rewrites the doc comments which bindgen found on this item, and on its
+/// fields, variants and functions, from Doxygen into Markdown. Each item
+/// ends up with at most one `#[doc]` attribute.
+pub(super) fn convert_item_docs(item: &mut Item) {
+    match item {
+        Item::Struct(s) => {
+            convert_docs(&mut s.attrs);
+            convert_field_docs(&mut s.fields);
+        }
+        Item::Union(u) => {
+            convert_docs(&mut u.attrs);
+            u.fields
+                .named
+                .iter_mut()
+                .for_each(|f| convert_docs(&mut f.attrs));
+        }
+        Item::Enum(e) => {
+            convert_docs(&mut e.attrs);
+            e.variants
+                .iter_mut()
+                .for_each(|v| convert_docs(&mut v.attrs));
+        }
+        Item::ForeignMod(fm) => {
+            for fi in fm.items.iter_mut() {
+                if let ForeignItem::Fn(f) = fi {
+                    convert_docs(&mut f.attrs);
+                }
+            }
+        }
+        Item::Const(c) => convert_docs(&mut c.attrs),
+        Item::Type(t) => convert_docs(&mut t.attrs),
+        _ => {}
+    }
+}
+
+fn convert_field_docs(fields: &mut Fields) {
+    fields.iter_mut().for_each(|f| convert_docs(&mut f.attrs));
+}
+
+fn convert_docs(attrs: &mut Vec<Attribute>) {
+    let docs: Vec<_> = attrs.iter().filter_map(doc_text).collect();
+    if docs.is_empty() {
+        return;
+    }
+    attrs.retain(|a| !a.path.is_ident("doc"));
+    let doc = doxygen_to_markdown(&docs.join("\n"));
+    attrs.insert(0, parse_quote! { #[doc = #doc] });
+}
+
+/// Adds a paragraph to the end of this item's documentation.
+pub(super) fn append_doc_paragraph(attrs: &mut Vec<Attribute>, paragraph: &str) {
+    let paragraph = format!(" {}", paragraph);
+    match attrs.iter().position(|a| a.path.is_ident("doc")) {
+        Some(pos) => {
+            let existing = doc_text(&attrs[pos]).unwrap_or_default();
+            let doc = format!("{}\n\n{}", existing.trim_end(), paragraph);
+            attrs[pos] = parse_quote! { #[doc = #doc] };
+        }
+        None => attrs.insert(0, parse_quote! { #[doc = #paragraph] }),
+    }
+}
+
+fn doc_text(attr: &Attribute) -> Option<String> {
+    if !attr.path.is_ident("doc") {
+        return None;
+    }
+    match attr.parse_meta() {
+        Ok(Meta::NameValue(nv)) => match nv.lit {
+            Lit::Str(s) => Some(s.value()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A parameter, or a template parameter, as described by `@param`.
+struct ParamDoc {
+    name: String,
+    direction: Option<String>,
+    text: String,
+}
+
+/// Where any continuation lines following a Doxygen command belong.
+enum Section {
+    Description,
+    Param,
+    TemplateParam,
+    Returns,
+}
+
+/// Converts a comment using Doxygen commands, such as `@param` and
+/// `\brief`, into Markdown with rustdoc's conventional headings. Comments
+/// without any such commands are returned unchanged.
+fn doxygen_to_markdown(comment: &str) -> String {
+    if !comment.lines().any(|line| command(line).is_some()) {
+        return comment.to_string();
+    }
+    let mut description: Vec<String> = Vec::new();
+    let mut params: Vec<ParamDoc> = Vec::new();
+    let mut tparams: Vec<ParamDoc> = Vec::new();
+    let mut returns: Vec<String> = Vec::new();
+    let mut see_also: Vec<String> = Vec::new();
+    let mut section = Section::Description;
+    let mut in_code = false;
+    for line in comment.lines() {
+        if in_code {
+            if matches!(command(line), Some(("endcode", _))) {
+                description.push("```".into());
+                in_code = false;
+            } else {
+                description.push(line.strip_prefix(' ').unwrap_or(line).into());
+            }
+            continue;
+        }
+        let (cmd, rest) = match command(line) {
+            Some(cmd) => cmd,
+            None => {
+                let line = convert_inline_commands(line.trim());
+                match section {
+                    Section::Param if !line.is_empty() => {
+                        append_line(&mut params.last_mut().unwrap().text, &line)
+                    }
+                    Section::TemplateParam if !line.is_empty() => {
+                        append_line(&mut tparams.last_mut().unwrap().text, &line)
+                    }
+                    Section::Returns if !line.is_empty() => {
+                        append_line(returns.last_mut().unwrap(), &line)
+                    }
+                    _ => {
+                        section = Section::Description;
+                        description.push(line);
+                    }
+                }
+                continue;
+            }
+        };
+        let rest = convert_inline_commands(rest);
+        match cmd {
+            "brief" | "short" | "details" => {
+                section = Section::Description;
+                description.push(rest);
+            }
+            "param" | "tparam" => {
+                let (direction, rest) = match rest.strip_prefix('[') {
+                    Some(rest) => match rest.find(']') {
+                        Some(end) => (Some(rest[..end].replace(' ', "")), &rest[end + 1..]),
+                        None => (None, rest),
+                    },
+                    None => (None, rest.as_str()),
+                };
+                let rest = rest.trim_start();
+                let (name, text) = split_first_word(rest);
+                let param = ParamDoc {
+                    name: name.to_string(),
+                    direction,
+                    text: text.trim().to_string(),
+                };
+                if cmd == "param" {
+                    params.push(param);
+                    section = Section::Param;
+                } else {
+                    tparams.push(param);
+                    section = Section::TemplateParam;
+                }
+            }
+            "return" | "returns" | "result" => {
+                returns.push(rest);
+                section = Section::Returns;
+            }
+            "retval" => {
+                let (value, text) = split_first_word(&rest);
+                returns.push(format!("* `{}`: {}", value, text.trim()));
+                section = Section::Returns;
+            }
+            "note" | "warning" | "attention" | "pre" | "post" => {
+                let label = match cmd {
+                    "pre" => "Precondition",
+                    "post" => "Postcondition",
+                    "note" => "Note",
+                    "warning" => "Warning",
+                    _ => "Attention",
+                };
+                section = Section::Description;
+                push_paragraph(&mut description, format!("**{}:** {}", label, rest));
+            }
+            "see" | "sa" => {
+                see_also.extend(
+                    rest.split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(|s| format!("`{}`", s.trim_matches('`'))),
+                );
+                section = Section::Description;
+            }
+            "code" => {
+                push_paragraph(&mut description, "```cpp".into());
+                in_code = true;
+            }
+            _ => {
+                // Not something we understand, so leave it alone.
+                section = Section::Description;
+                description.push(line.trim().to_string());
+            }
+        }
+    }
+    if in_code {
+        description.push("```".into());
+    }
+    let mut output = Vec::new();
+    push_lines(&mut output, description);
+    push_params(&mut output, "Parameters", params);
+    push_params(&mut output, "Template parameters", tparams);
+    if !returns.is_empty() {
+        push_section(&mut output, "Returns");
+        push_lines(&mut output, returns);
+    }
+    if !see_also.is_empty() {
+        push_paragraph(&mut output, format!("See also {}.", see_also.join(", ")));
+    }
+    output
+        .iter()
+        .map(|line| format!(" {}", line).trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// If this line starts with a Doxygen command such as `@param` or
+/// `\brief`, the command and the remainder of the line.
+fn command(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    let line = line.strip_prefix('@').or_else(|| line.strip_prefix('\\'))?;
+    let end = line
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(line.len());
+    let (cmd, rest) = line.split_at(end);
+    if cmd.is_empty()
+        || (!rest.is_empty() && !rest.starts_with(|c: char| c.is_whitespace() || c == '['))
+    {
+        None
+    } else {
+        Some((cmd, rest.trim()))
+    }
+}
+
+/// Splits the first word from the remainder of this text, which keeps
+/// any leading whitespace.
+fn split_first_word(text: &str) -> (&str, &str) {
+    match text.find(char::is_whitespace) {
+        Some(end) => text.split_at(end),
+        None => (text, ""),
+    }
+}
+
+/// Converts Doxygen's commands for styling the following word, such as
+/// `\c` and `@p`, into Markdown.
+fn convert_inline_commands(text: &str) -> String {
+    let mut words = Vec::new();
+    let mut style: Option<(&str, &str)> = None;
+    for word in text.split(' ') {
+        if let Some((before, after)) = style.take() {
+            words.push(format!("{}{}{}", before, word, after));
+            continue;
+        }
+        style = match word {
+            "\\c" | "@c" | "\\p" | "@p" | "\\ref" | "@ref" => Some(("`", "`")),
+            "\\a" | "@a" | "\\e" | "@e" | "\\em" | "@em" => Some(("*", "*")),
+            "\\b" | "@b" => Some(("**", "**")),
+            _ => None,
+        };
+        if style.is_none() {
+            words.push(word.to_string());
+        }
+    }
+    words.join(" ")
+}
+
+fn append_line(text: &mut String, line: &str) {
+    if !text.is_empty() {
+        text.push(' ');
+    }
+    text.push_str(line);
+}
+
+fn push_lines(output: &mut Vec<String>, lines: Vec<String>) {
+    for line in lines {
+        if !(line.is_empty() && output.last().map(String::is_empty).unwrap_or(true)) {
+            output.push(line);
+        }
+    }
+    while output.last().map(String::is_empty).unwrap_or(false) {
+        output.pop();
+    }
+}
+
+fn push_paragraph(output: &mut Vec<String>, paragraph: String) {
+    if !output.is_empty() {
+        output.push(String::new());
+    }
+    output.push(paragraph);
+}
+
+fn push_section(output: &mut Vec<String>, heading: &str) {
+    push_paragraph(output, format!("# {}", heading));
+    output.push(String::new());
+}
+
+fn push_params(output: &mut Vec<String>, heading: &str, params: Vec<ParamDoc>) {
+    if params.is_empty() {
+        return;
+    }
+    push_section(output, heading);
+    for param in params {
+        let direction = param
+            .direction
+            .map(|direction| format!(" ({})", direction))
+            .unwrap_or_default();
+        output.push(format!("* `{}`{}: {}", param.name, direction, param.text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::doxygen_to_markdown;
+
+    #[test]
+    fn test_plain_comment_unchanged() {
+        let comment = " Adds two numbers.\n\n Really.";
+        assert_eq!(doxygen_to_markdown(comment), comment);
+    }
+
+    #[test]
+    fn test_doxygen_sections() {
+        let comment = concat!(
+            " \\brief Adds two numbers.\n",
+            " \n",
+            " Overflow wraps around, as \\c uint32_t does.\n",
+            " @param[in] a the first number\n",
+            " @param b the second number,\n",
+            "   which may be zero\n",
+            " @return The sum.\n",
+            " @see subtract, multiply",
+        );
+        assert_eq!(
+            doxygen_to_markdown(comment),
+            concat!(
+                " Adds two numbers.\n",
+                "\n",
+                " Overflow wraps around, as `uint32_t` does.\n",
+                "\n",
+                " # Parameters\n",
+                "\n",
+                " * `a` (in): the first number\n",
+                " * `b`: the second number, which may be zero\n",
+                "\n",
+                " # Returns\n",
+                "\n",
+                " The sum.\n",
+                "\n",
+                " See also `subtract`, `multiply`.",
+            )
+        );
+    }
+
+    #[test]
+    fn test_doxygen_code() {
+        let comment = " @brief Frobs.\n @code\n frob(3);\n @endcode";
+        assert_eq!(
+            doxygen_to_markdown(comment),
+            " Frobs.\n\n ```cpp\n frob(3);\n ```"
+        );
+    }
+}
@@ -12,11 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
+    clang_fn_info::ClangFnInfo,
     conversion::{
-        api::{ApiDetail, NullAnalysis, TypedefKind, UnanalyzedApi},
+        analysis::pod::{BITFIELD_UNIT_NAME, UNION_FIELD_NAME},
+        api::{
+            ApiDetail, FieldOffset, Layout, NullAnalysis, StructDetails, TypedefKind, UnanalyzedApi,
+        },
         ConvertError,
     },
     types::Namespace,
@@ -27,24 +31,38 @@ use crate::{
         convert_error::{ConvertErrorWithContext, ErrorContext},
         error_reporter::report_any_error,
     },
-    types::validate_ident_ok_for_cxx,
+    types::{make_ident, validate_ident_ok_for_cxx},
 };
 use autocxx_parser::IncludeCppConfig;
-use syn::{parse_quote, Attribute, Fields, Ident, Item, LitStr, TypePath, UseTree};
+use quote::ToTokens;
+use syn::{
+    parse_quote, punctuated::Punctuated, Attribute, Expr, ExprLit, Fields, FnArg, GenericArgument,
+    Ident, ImplItem, ImplItemMethod, Item, ItemFn, ItemStruct, ItemType, ItemUnion, Lit, LitStr,
+    Member, PathArguments, Stmt, Token, Type, TypePath, UseTree,
+};
 
+use super::super::codegen_cpp::type_to_cpp::type_to_cpp;
 use super::super::utilities::generate_utilities;
 
+use super::doxygen::convert_item_docs;
 use super::parse_foreign_mod::ParseForeignMod;
 
 /// Parses a bindgen mod in order to understand the APIs within it.
 pub(crate) struct ParseBindgen<'a> {
     config: &'a IncludeCppConfig,
+    fn_info: &'a ClangFnInfo,
     apis: Vec<UnanalyzedApi>,
     /// Here we track the last struct which bindgen told us about.
     /// Any subsequent "extern 'C'" blocks are methods belonging to that type,
     /// even if the 'this' is actually recorded as void in the
     /// function signature.
     latest_virtual_this_type: Option<QualifiedName>,
+    /// Typedefs which name template instantiations requested using
+    /// `instantiate!`. We turn these into structs once we've seen
+    /// the templates themselves.
+    instantiations: Vec<(Namespace, ItemType)>,
+    /// Layouts of template instantiations, from bindgen's layout tests.
+    instantiation_layouts: Vec<(Type, Layout)>,
 }
 
 pub(crate) fn get_bindgen_original_name_annotation(attrs: &[Attribute]) -> Option<String> {
@@ -65,11 +83,14 @@ pub(crate) fn get_bindgen_original_name_annotation(attrs: &[Attribute]) -> Optio
 }
 
 impl<'a> ParseBindgen<'a> {
-    pub(crate) fn new(config: &'a IncludeCppConfig) -> Self {
+    pub(crate) fn new(config: &'a IncludeCppConfig, fn_info: &'a ClangFnInfo) -> Self {
         ParseBindgen {
             config,
+            fn_info,
             apis: Vec::new(),
             latest_virtual_this_type: None,
+            instantiations: Vec::new(),
+            instantiation_layouts: Vec::new(),
         }
     }
 
@@ -85,6 +106,7 @@ impl<'a> ParseBindgen<'a> {
         }
         let root_ns = Namespace::new();
         self.parse_mod_items(items, root_ns);
+        self.parse_instantiations();
         self.confirm_all_generate_directives_obeyed()?;
         Ok(self.apis)
     }
@@ -114,13 +136,14 @@ impl<'a> ParseBindgen<'a> {
         // this particular mod.
         let mut mod_converter = ParseForeignMod::new(ns.clone());
         let mut more_apis = Vec::new();
-        for item in items {
+        for mut item in items {
+            convert_item_docs(&mut item);
             report_any_error(&ns, &mut more_apis, || {
                 self.parse_item(item, &mut mod_converter, &ns)
             });
         }
         self.apis.append(&mut more_apis);
-        mod_converter.finished(&mut self.apis);
+        mod_converter.finished(&mut self.apis, self.fn_info);
     }
 
     fn parse_item(
@@ -136,12 +159,31 @@ impl<'a> ParseBindgen<'a> {
                 Ok(())
             }
             Item::Struct(s) => {
-                if s.ident.to_string().ends_with("__bindgen_vtable") {
+                if s.ident.to_string().ends_with("__bindgen_vtable") || s.ident == UNION_FIELD_NAME
+                {
+                    return Ok(());
+                }
+                if s.ident == BITFIELD_UNIT_NAME {
+                    self.apis.push(UnanalyzedApi {
+                        name: QualifiedName::new(ns, s.ident.clone()),
+                        original_name: None,
+                        deps: HashSet::new(),
+                        detail: ApiDetail::BitfieldUnit {
+                            items: vec![Item::Struct(s)],
+                        },
+                    });
                     return Ok(());
                 }
+                if let Some(u) = Self::struct_as_union(&s) {
+                    return self.parse_item(Item::Union(u), mod_converter, ns);
+                }
                 let tyname = Self::qualify_name(ns, s.ident.clone())?;
                 let is_forward_declaration = Self::spot_forward_declaration(&s.fields);
                 let original_name = get_bindgen_original_name_annotation(&s.attrs);
+                let details = StructDetails {
+                    bases: Self::find_bases(&s),
+                    ..Default::default()
+                };
                 // cxx::bridge can't cope with type aliases to generic
                 // types at the moment.
                 self.parse_type(
@@ -151,12 +193,26 @@ impl<'a> ParseBindgen<'a> {
                     original_name,
                     |s| ApiDetail::Struct {
                         item: s,
+                        details: Box::new(details),
                         analysis: (),
                     },
                 );
                 self.latest_virtual_this_type = Some(tyname);
                 Ok(())
             }
+            Item::Union(u) => {
+                let tyname = Self::qualify_name(ns, u.ident.clone())?;
+                let original_name = get_bindgen_original_name_annotation(&u.attrs);
+                self.parse_type(tyname.clone(), false, u, original_name, |u| {
+                    ApiDetail::Union {
+                        item: u,
+                        details: Box::new(StructDetails::default()),
+                        analysis: (),
+                    }
+                });
+                self.latest_virtual_this_type = Some(tyname);
+                Ok(())
+            }
             Item::Enum(e) => {
                 let tyname = Self::qualify_name(ns, e.ident.clone())?;
                 let original_name = get_bindgen_original_name_annotation(&e.attrs);
@@ -166,7 +222,47 @@ impl<'a> ParseBindgen<'a> {
                 });
                 Ok(())
             }
-            Item::Impl(imp) => {
+            Item::Impl(mut imp) => {
+                let self_ty = match &*imp.self_ty {
+                    Type::Path(typ) => typ.path.segments.last().map(|seg| seg.ident.clone()),
+                    _ => None,
+                };
+                if let Some(self_ty) = self_ty {
+                    if self_ty == UNION_FIELD_NAME {
+                        // We turn such unions back into Rust unions.
+                        return Ok(());
+                    }
+                    if self_ty == BITFIELD_UNIT_NAME {
+                        // The implementation of bindgen's bitfield storage,
+                        // which we need to keep in its entirety.
+                        if let Some(ApiDetail::BitfieldUnit { items }) = self
+                            .apis
+                            .iter_mut()
+                            .rev()
+                            .map(|api| &mut api.detail)
+                            .find(|detail| matches!(detail, ApiDetail::BitfieldUnit { .. }))
+                        {
+                            items.push(Item::Impl(imp));
+                        }
+                        return Ok(());
+                    }
+                    // Bitfield accessors are the only methods which exist
+                    // purely in Rust, so we keep those.
+                    let (accessors, others): (Vec<_>, Vec<_>) = imp
+                        .items
+                        .into_iter()
+                        .partition(|item| Self::as_bitfield_accessor(item).is_some());
+                    imp.items = others;
+                    let tyname = QualifiedName::new(ns, self_ty);
+                    if let Some(details) = self.find_struct_details(&tyname) {
+                        details.bitfield_accessors.extend(
+                            accessors
+                                .iter()
+                                .filter_map(Self::as_bitfield_accessor)
+                                .map(Self::rename_bitfield_getter),
+                        );
+                    }
+                }
                 // We *mostly* ignore all impl blocks generated by bindgen.
                 // Methods also appear in 'extern "C"' blocks which
                 // we will convert instead. At that time we'll also construct
@@ -177,6 +273,18 @@ impl<'a> ParseBindgen<'a> {
                 mod_converter.convert_impl_items(imp);
                 Ok(())
             }
+            Item::Fn(f) => {
+                // These are bindgen's layout tests. We don't want to run
+                // them, but they're the only place bindgen tells us
+                // the size and alignment of each type.
+                let fn_name = f.sig.ident.to_string();
+                if fn_name.starts_with("bindgen_test_layout_")
+                    || fn_name.starts_with("__bindgen_test_layout_")
+                {
+                    self.parse_layout_test(&f, ns);
+                }
+                Ok(())
+            }
             Item::Mod(itm) => {
                 if let Some((_, items)) = itm.content {
                     let new_ns = ns.push(itm.ident.to_string());
@@ -256,8 +364,17 @@ impl<'a> ParseBindgen<'a> {
                 Ok(())
             }
             Item::Type(ity) => {
+                let name = QualifiedName::new(ns, ity.ident.clone());
+                if self
+                    .config
+                    .instantiation_named(&name.to_cpp_name())
+                    .is_some()
+                {
+                    self.instantiations.push((ns.clone(), ity));
+                    return Ok(());
+                }
                 self.apis.push(UnanalyzedApi {
-                    name: QualifiedName::new(ns, ity.ident.clone()),
+                    name,
                     original_name: get_bindgen_original_name_annotation(&ity.attrs),
                     deps: HashSet::new(),
                     detail: ApiDetail::Typedef {
@@ -274,6 +391,344 @@ impl<'a> ParseBindgen<'a> {
         }
     }
 
+    /// As we don't ask bindgen to derive `Copy`, it never generates
+    /// Rust unions. Instead, it generates a struct with a
+    /// `__BindgenUnionField<T>` for each member plus a field to give
+    /// the union the right size. Turn that back into a union.
+    fn struct_as_union(s: &ItemStruct) -> Option<ItemUnion> {
+        let fields = match &s.fields {
+            Fields::Named(fields) => fields,
+            _ => return None,
+        };
+        if !fields
+            .named
+            .iter()
+            .any(|f| f.ident.as_ref().unwrap() == "bindgen_union_field")
+        {
+            return None;
+        }
+        let mut fields = fields.clone();
+        fields.named =
+            fields
+                .named
+                .into_iter()
+                .filter(|f| f.ident.as_ref().unwrap() != "bindgen_union_field")
+                .map(|mut f| {
+                    let inner =
+                        match &f.ty {
+                            Type::Path(typ) => typ.path.segments.last().and_then(|seg| match &seg
+                                .arguments
+                            {
+                                PathArguments::AngleBracketed(ab)
+                                    if seg.ident == UNION_FIELD_NAME =>
+                                {
+                                    match ab.args.first() {
+                                        Some(GenericArgument::Type(ty)) => Some(ty.clone()),
+                                        _ => None,
+                                    }
+                                }
+                                _ => None,
+                            }),
+                            _ => None,
+                        };
+                    inner.map(|inner| {
+                        f.ty = inner;
+                        f
+                    })
+                })
+                .collect::<Option<_>>()?;
+        Some(ItemUnion {
+            attrs: s
+                .attrs
+                .iter()
+                .filter(|a| !a.path.is_ident("derive"))
+                .cloned()
+                .collect(),
+            vis: s.vis.clone(),
+            union_token: Default::default(),
+            ident: s.ident.clone(),
+            generics: s.generics.clone(),
+            fields,
+        })
+    }
+
+    /// bindgen represents each base class as a field called `_base`,
+    /// `_base_1` etc.
+    fn find_bases(s: &ItemStruct) -> Vec<QualifiedName> {
+        s.fields
+            .iter()
+            .filter(|f| {
+                f.ident
+                    .as_ref()
+                    .map(|id| id == "_base" || id.to_string().starts_with("_base_"))
+                    .unwrap_or(false)
+            })
+            .filter_map(|f| match &f.ty {
+                Type::Path(typ)
+                    if typ
+                        .path
+                        .segments
+                        .last()
+                        .map(|seg| seg.arguments.is_empty())
+                        .unwrap_or(false) =>
+                {
+                    Some(QualifiedName::from_type_path(typ))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// bindgen represents each named template instantiation as a type
+    /// alias to its generic struct. cxx can't cope with generics, so
+    /// instead we make a concrete struct, substituting the template
+    /// arguments into the fields of the generic struct.
+    fn parse_instantiations(&mut self) {
+        for (ns, ity) in std::mem::take(&mut self.instantiations) {
+            let id = ity.ident.clone();
+            let item = self
+                .instantiate_template(&ity.ty)
+                .map(|mut s| {
+                    s.ident = id.clone();
+                    s
+                })
+                .unwrap_or_else(|| {
+                    // bindgen couldn't represent the template (for example
+                    // because it has non-type parameters) so it's given us
+                    // an opaque blob of the right size.
+                    let ty = &ity.ty;
+                    parse_quote! {
+                        #[repr(C)]
+                        pub struct #id {
+                            pub _bindgen_opaque_blob: #ty,
+                        }
+                    }
+                });
+            let ty_string = ity.ty.to_token_stream().to_string();
+            let layout = self
+                .instantiation_layouts
+                .iter()
+                .find(|(ty, _)| ty.to_token_stream().to_string() == ty_string)
+                .map(|(_, layout)| *layout);
+            let details = StructDetails {
+                layout,
+                instantiation_of: type_to_cpp(&ity.ty, &HashMap::new()).ok(),
+                ..Default::default()
+            };
+            self.parse_type(QualifiedName::new(&ns, id), false, item, None, |s| {
+                ApiDetail::Struct {
+                    item: s,
+                    details: Box::new(details),
+                    analysis: (),
+                }
+            });
+        }
+    }
+
+    fn instantiate_template(&self, ty: &Type) -> Option<ItemStruct> {
+        let typ = match ty {
+            Type::Path(typ) => typ,
+            _ => return None,
+        };
+        let args = match &typ.path.segments.last()?.arguments {
+            PathArguments::AngleBracketed(ab) => ab
+                .args
+                .iter()
+                .filter_map(|arg| match arg {
+                    GenericArgument::Type(ty) => Some(ty.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            _ => return None,
+        };
+        let template_name = QualifiedName::from_type_path(typ);
+        let template = self.apis.iter().find_map(|api| match &api.detail {
+            ApiDetail::Struct { item, .. } if api.name == template_name => Some(item),
+            _ => None,
+        })?;
+        let params: Vec<_> = template
+            .generics
+            .type_params()
+            .map(|param| param.ident.clone())
+            .collect();
+        if params.len() != args.len() {
+            return None;
+        }
+        let substitutions: HashMap<_, _> = params.into_iter().zip(args).collect();
+        let mut s = template.clone();
+        s.generics = Default::default();
+        if let Fields::Named(fields) = &mut s.fields {
+            fields.named = std::mem::take(&mut fields.named)
+                .into_iter()
+                .filter(|f| {
+                    // These only exist to use otherwise-unused type parameters.
+                    !f.ident
+                        .as_ref()
+                        .map(|id| id.to_string().starts_with("_phantom_"))
+                        .unwrap_or(false)
+                })
+                .map(|mut f| {
+                    f.ty = substitute_template_params(f.ty, &substitutions);
+                    f
+                })
+                .collect();
+        }
+        Some(s)
+    }
+
+    fn find_struct_details(&mut self, tyname: &QualifiedName) -> Option<&mut StructDetails> {
+        self.apis
+            .iter_mut()
+            .rev()
+            .find(|api| &api.name == tyname)
+            .and_then(|api| match &mut api.detail {
+                ApiDetail::Struct { details, .. } | ApiDetail::Union { details, .. } => {
+                    Some(details.as_mut())
+                }
+                _ => None,
+            })
+    }
+
+    fn as_bitfield_accessor(item: &ImplItem) -> Option<&ImplItemMethod> {
+        match item {
+            ImplItem::Method(m) if m.block.to_token_stream().to_string().contains("_bitfield_") => {
+                Some(m)
+            }
+            _ => None,
+        }
+    }
+
+    /// bindgen names bitfield getters simply after the field; we
+    /// prefer `get_x` to sit alongside bindgen's `set_x`.
+    fn rename_bitfield_getter(m: &ImplItemMethod) -> ImplItemMethod {
+        let mut m = m.clone();
+        let is_getter = matches!(
+            m.sig.inputs.first(),
+            Some(FnArg::Receiver(r)) if r.mutability.is_none()
+        );
+        if is_getter {
+            m.sig.ident = make_ident(format!("get_{}", m.sig.ident));
+        }
+        m
+    }
+
+    /// Spots the size, alignment and field offset assertions in a bindgen
+    /// layout test and records them against the relevant struct.
+    fn parse_layout_test(&mut self, f: &ItemFn, ns: &Namespace) {
+        let mut size = None;
+        let mut align = None;
+        let mut tyname = None;
+        let mut ty = None;
+        let mut field_offsets = Vec::new();
+        for stmt in &f.block.stmts {
+            let mac = match stmt {
+                Stmt::Semi(Expr::Macro(m), _) => &m.mac,
+                Stmt::Expr(Expr::Macro(m)) => &m.mac,
+                _ => continue,
+            };
+            if !mac.path.is_ident("assert_eq") {
+                continue;
+            }
+            let args = match mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated) {
+                Ok(args) => args,
+                Err(_) => continue,
+            };
+            let mut args = args.into_iter();
+            let (func, value) = match (args.next(), args.next()) {
+                (
+                    Some(Expr::Unsafe(offset_expr)),
+                    Some(Expr::Lit(ExprLit {
+                        lit: Lit::Int(i), ..
+                    })),
+                ) => {
+                    let field = offset_expr.block.stmts.last().and_then(|stmt| match stmt {
+                        Stmt::Expr(e) => Self::find_field_in_offset_expr(e),
+                        _ => None,
+                    });
+                    if let (Some(field), Ok(offset)) = (field, i.base10_parse::<usize>()) {
+                        field_offsets.push((field, offset));
+                    }
+                    continue;
+                }
+                (
+                    Some(Expr::Call(call)),
+                    Some(Expr::Lit(ExprLit {
+                        lit: Lit::Int(i), ..
+                    })),
+                ) => (call.func, i.base10_parse::<usize>().ok()),
+                _ => continue,
+            };
+            let seg = match &*func {
+                Expr::Path(p) => p.path.segments.last(),
+                _ => None,
+            };
+            if let Some(seg) = seg {
+                if let PathArguments::AngleBracketed(ab) = &seg.arguments {
+                    if let Some(GenericArgument::Type(Type::Path(typ))) = ab.args.first() {
+                        tyname = typ.path.segments.last().map(|s| s.ident.clone());
+                        ty = Some(Type::Path(typ.clone()));
+                    }
+                }
+                if seg.ident == "size_of" {
+                    size = value;
+                } else if seg.ident == "align_of" {
+                    align = value;
+                }
+            }
+        }
+        if f.sig.ident.to_string().ends_with("_instantiation") {
+            // This tests a template instantiation rather than the
+            // named type.
+            if let (Some(ty), Some(size), Some(align)) = (ty, size, align) {
+                self.instantiation_layouts
+                    .push((ty, Layout { size, align }));
+            }
+            return;
+        }
+        if let (Some(tyname), Some(size), Some(align)) = (tyname, size, align) {
+            let tyname = QualifiedName::new(ns, tyname);
+            let (fields, details) = match self
+                .apis
+                .iter_mut()
+                .rev()
+                .find(|api| api.name == tyname)
+                .map(|api| &mut api.detail)
+            {
+                Some(ApiDetail::Struct { item, details, .. }) => {
+                    (item.fields.iter().collect::<Vec<_>>(), details)
+                }
+                Some(ApiDetail::Union { item, details, .. }) => {
+                    (item.fields.named.iter().collect(), details)
+                }
+                _ => return,
+            };
+            details.layout = Some(Layout { size, align });
+            details.field_offsets = field_offsets
+                .into_iter()
+                .filter(|(field, _)| fields.iter().any(|f| f.ident.as_ref() == Some(field)))
+                .map(|(field, offset)| FieldOffset {
+                    cpp_names: cpp_field_names(&field.to_string()),
+                    rust_name: field,
+                    offset,
+                })
+                .collect();
+        }
+    }
+
+    /// Finds `field` within bindgen's
+    /// `&(*(::std::ptr::null::<T>())).field as *const _ as usize`.
+    fn find_field_in_offset_expr(e: &Expr) -> Option<Ident> {
+        match e {
+            Expr::Cast(c) => Self::find_field_in_offset_expr(&c.expr),
+            Expr::Reference(r) => Self::find_field_in_offset_expr(&r.expr),
+            Expr::Field(f) => match &f.member {
+                Member::Named(id) => Some(id.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     fn qualify_name(ns: &Namespace, id: Ident) -> Result<QualifiedName, ConvertErrorWithContext> {
         match validate_ident_ok_for_cxx(&id.to_string()) {
             Err(e) => {
@@ -336,3 +791,66 @@ impl<'a> ParseBindgen<'a> {
         Ok(())
     }
 }
+
+/// Replaces any template parameters within this type with the
+/// corresponding template arguments.
+fn substitute_template_params(ty: Type, substitutions: &HashMap<Ident, Type>) -> Type {
+    match ty {
+        Type::Path(mut typ) => {
+            if typ.qself.is_none() {
+                if let Some(id) = typ.path.get_ident() {
+                    if let Some(replacement) = substitutions.get(id) {
+                        return replacement.clone();
+                    }
+                }
+            }
+            for seg in typ.path.segments.iter_mut() {
+                if let PathArguments::AngleBracketed(ab) = &mut seg.arguments {
+                    for arg in ab.args.iter_mut() {
+                        if let GenericArgument::Type(inner) = arg {
+                            *inner = substitute_template_params(inner.clone(), substitutions);
+                        }
+                    }
+                }
+            }
+            Type::Path(typ)
+        }
+        Type::Array(mut arr) => {
+            *arr.elem = substitute_template_params(*arr.elem, substitutions);
+            Type::Array(arr)
+        }
+        Type::Ptr(mut ptr) => {
+            *ptr.elem = substitute_template_params(*ptr.elem, substitutions);
+            Type::Ptr(ptr)
+        }
+        Type::Reference(mut r) => {
+            *r.elem = substitute_template_params(*r.elem, substitutions);
+            Type::Reference(r)
+        }
+        _ => ty,
+    }
+}
+
+/// Rust keywords and primitive types, to whose names bindgen appends
+/// an underscore when they're used as field names.
+const RENAMED_FIELD_NAMES: &[&str] = &[
+    "abstract", "alignof", "as", "async", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "offsetof", "override", "priv", "proc",
+    "pub", "pure", "ref", "return", "Self", "self", "sizeof", "static", "struct", "super", "trait",
+    "true", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+    "str", "bool", "f32", "f64", "usize", "isize", "u128", "i128", "u64", "i64", "u32", "i32",
+    "u16", "i16", "u8", "i8",
+];
+
+/// The possible C++ names of a field. bindgen appends an underscore to
+/// field names which are Rust keywords, but a C++ field may also simply
+/// be called `type_`, so in that case we don't know which it was.
+fn cpp_field_names(rust_name: &str) -> Vec<String> {
+    match rust_name.strip_suffix('_') {
+        Some(name) if RENAMED_FIELD_NAMES.contains(&name) => {
+            vec![name.to_string(), rust_name.to_string()]
+        }
+        _ => vec![rust_name.to_string()],
+    }
+}
@@ -17,15 +17,21 @@ use crate::conversion::{
     api::{FuncToConvert, UnanalyzedApi},
     convert_error::ConvertErrorWithContext,
     convert_error::ErrorContext,
+    parse::doxygen::append_doc_paragraph,
     parse::parse_bindgen::get_bindgen_original_name_annotation,
 };
 use crate::{
+    clang_fn_info::{get_mangled_name, ClangFnInfo, InstantiatedMethod},
     conversion::api::ApiDetail,
     conversion::ConvertError,
-    types::{Namespace, QualifiedName},
+    types::{make_ident, Namespace, QualifiedName},
 };
+use quote::quote;
 use std::collections::{HashMap, HashSet};
-use syn::{Block, Expr, ExprCall, ForeignItem, Ident, ImplItem, ItemImpl, Stmt, Type};
+use syn::{
+    parse_quote, Block, Expr, ExprCall, FnArg, ForeignItem, ForeignItemStatic, Ident, ImplItem,
+    ItemImpl, Pat, PatType, Stmt, Type,
+};
 
 /// Parses a given bindgen-generated 'mod' into suitable
 /// [Api]s. In bindgen output, a given mod concerns
@@ -82,13 +88,12 @@ impl ParseForeignMod {
                     item,
                     virtual_this_type: virtual_this_type.clone(),
                     self_ty: None,
+                    is_static_accessor: false,
+                    default_args_suffix: None,
                 });
                 Ok(())
             }
-            ForeignItem::Static(item) => Err(ConvertErrorWithContext(
-                ConvertError::StaticData(item.ident.to_string()),
-                Some(ErrorContext::Item(item.ident)),
-            )),
+            ForeignItem::Static(item) => self.parse_static(item),
             _ => Err(ConvertErrorWithContext(
                 ConvertError::UnexpectedForeignItem,
                 None,
@@ -96,6 +101,92 @@ impl ParseForeignMod {
         }
     }
 
+    /// cxx can't refer to global variables or static members directly,
+    /// so instead we synthesize a function which returns a pointer to
+    /// the variable, as if bindgen had found a C++ function returning
+    /// a reference. Later phases generate a C++ accessor function for it.
+    fn parse_static(&mut self, item: ForeignItemStatic) -> Result<(), ConvertErrorWithContext> {
+        let ForeignItemStatic {
+            attrs,
+            vis,
+            mutability,
+            ident,
+            ty,
+            ..
+        } = item;
+        if matches!(*ty, Type::Ptr(_)) {
+            return Err(ConvertErrorWithContext(
+                ConvertError::StaticData(ident.to_string()),
+                Some(ErrorContext::Item(ident)),
+            ));
+        }
+        // bindgen names static members {class}_{member}, so we can only
+        // tell them apart from globals using the mangled name. Globals
+        // outside any namespace, and those with C linkage, aren't mangled.
+        let mangled = get_mangled_name(&attrs)
+            .filter(|name| name.starts_with("_Z") || name.starts_with("__Z"));
+        let member_of = match mangled {
+            None => None,
+            Some(mangled) => match self.find_static_owner(&mangled) {
+                Some(member_of) => member_of,
+                None => {
+                    return Err(ConvertErrorWithContext(
+                        ConvertError::UnrecognizedStaticName(mangled),
+                        Some(ErrorContext::Item(ident)),
+                    ))
+                }
+            },
+        };
+        let (self_ty, original_name_attr) = match member_of {
+            Some((class, member)) => (
+                Some(class),
+                Some(quote! { #[bindgen_original_name(#member)] }),
+            ),
+            None => (None, None),
+        };
+        let doc_attrs = attrs.into_iter().filter(|a| a.path.is_ident("doc"));
+        let ptr_type: Type = match mutability {
+            Some(_) => parse_quote! { *mut #ty },
+            None => parse_quote! { *const #ty },
+        };
+        self.funcs_to_convert.push(FuncToConvert {
+            item: parse_quote! {
+                #(#doc_attrs)*
+                #original_name_attr
+                #[bindgen_ret_type_reference]
+                #vis fn #ident() -> #ptr_type;
+            },
+            virtual_this_type: None,
+            self_ty,
+            is_static_accessor: true,
+            default_args_suffix: None,
+        });
+        Ok(())
+    }
+
+    /// Works out from the mangled name of a static in this namespace
+    /// whether it's a global (`Some(None)`) or a static member, in which
+    /// case we return its class and its name within that class. Nested
+    /// classes are named as bindgen names them, joined by underscores.
+    /// Returns `None` if we can't interpret the name.
+    fn find_static_owner(&self, mangled: &str) -> Option<Option<(QualifiedName, String)>> {
+        let mut components = demangle_simple_name(mangled)?;
+        let ns_segments: Vec<_> = self.ns.iter().cloned().collect();
+        if !components.starts_with(&ns_segments) {
+            return None;
+        }
+        let mut scopes = components.split_off(ns_segments.len());
+        let member = scopes.pop()?;
+        Some(if scopes.is_empty() {
+            None
+        } else {
+            Some((
+                QualifiedName::new(&self.ns, make_ident(scopes.join("_"))),
+                member,
+            ))
+        })
+    }
+
     /// Record information from impl blocks encountered in bindgen
     /// output.
     pub(crate) fn convert_impl_items(&mut self, imp: ItemImpl) {
@@ -124,22 +215,109 @@ impl ParseForeignMod {
     /// Indicate that all foreign mods and all impl blocks have been
     /// fed into us, and we should process that information to generate
     /// the resulting APIs.
-    pub(crate) fn finished(mut self, apis: &mut Vec<UnanalyzedApi>) {
+    /// Functions with default arguments also result in variants which
+    /// omit some or all of those arguments. Deprecated functions are
+    /// marked as such, and each function's documentation mentions its
+    /// original C++ signature.
+    pub(crate) fn finished(mut self, apis: &mut Vec<UnanalyzedApi>, fn_info: &ClangFnInfo) {
         apis.append(&mut self.ignored_apis);
         while !self.funcs_to_convert.is_empty() {
             let mut fun = self.funcs_to_convert.remove(0);
-            fun.self_ty = self.method_receivers.get(&fun.item.sig.ident).cloned();
-            apis.push(UnanalyzedApi {
-                name: QualifiedName::new(&self.ns, fun.item.sig.ident.clone()),
-                original_name: get_bindgen_original_name_annotation(&fun.item.attrs),
-                deps: HashSet::new(), // filled in later - TODO make compile-time safe
-                detail: ApiDetail::Function {
-                    fun: Box::new(fun),
-                    analysis: (),
-                },
-            })
+            if let Some(method) = fn_info.instantiated_method(&fun.item.sig.ident) {
+                Self::convert_instantiated_method(&mut fun, method);
+            }
+            if let Some(self_ty) = self.method_receivers.get(&fun.item.sig.ident) {
+                fun.self_ty = Some(self_ty.clone());
+            }
+            // bindgen tells us about [[nodiscard]] using #[must_use], but
+            // not about [[deprecated]].
+            match fn_info.deprecation_for(&fun.item.attrs) {
+                Some("") => fun.item.attrs.push(parse_quote! { #[deprecated] }),
+                Some(note) => fun
+                    .item
+                    .attrs
+                    .push(parse_quote! { #[deprecated(note = #note)] }),
+                None => {}
+            }
+            if let Some(signature) = fn_info.signature_for(&fun.item.attrs) {
+                append_doc_paragraph(
+                    &mut fun.item.attrs,
+                    &format!("C++ signature: `{}`", signature),
+                );
+            }
+            let num_defaults = fn_info.count_defaults_for(&fun.item.attrs);
+            let variants: Vec<_> = (1..=num_defaults)
+                .filter_map(|omitted| Self::omit_default_args(&fun, omitted, num_defaults))
+                .collect();
+            for fun in std::iter::once(fun).chain(variants) {
+                apis.push(UnanalyzedApi {
+                    name: QualifiedName::new(&self.ns, fun.item.sig.ident.clone()),
+                    original_name: get_bindgen_original_name_annotation(&fun.item.attrs),
+                    deps: HashSet::new(), // filled in later - TODO make compile-time safe
+                    detail: ApiDetail::Function {
+                        fun: Box::new(fun),
+                        analysis: (),
+                    },
+                })
+            }
         }
     }
+
+    /// Turns a function which we declared for bindgen's benefit into
+    /// the method of a template instantiation for which it stands.
+    fn convert_instantiated_method(fun: &mut FuncToConvert, method: &InstantiatedMethod) {
+        let name = &method.method;
+        fun.item
+            .attrs
+            .push(parse_quote! { #[bindgen_original_name(#name)] });
+        append_doc_paragraph(
+            &mut fun.item.attrs,
+            &format!("C++ signature: `{}`", method.signature),
+        );
+        if method.is_static {
+            fun.self_ty = Some(method.self_ty.clone());
+        } else if let Some(FnArg::Typed(PatType { pat, .. })) = fun.item.sig.inputs.first_mut() {
+            *pat = parse_quote! { this };
+        }
+    }
+
+    /// A copy of this function without the last few parameters, which we'll
+    /// call through a C++ wrapper such that C++ fills in their defaults.
+    /// It's named after the last parameter it does take, unless it takes
+    /// none of those with defaults.
+    fn omit_default_args(
+        fun: &FuncToConvert,
+        omitted: usize,
+        num_defaults: usize,
+    ) -> Option<FuncToConvert> {
+        let mut fun = fun.clone();
+        let num_params = fun.item.sig.inputs.len();
+        if omitted > num_params {
+            return None;
+        }
+        fun.item.sig.inputs = fun
+            .item
+            .sig
+            .inputs
+            .into_iter()
+            .take(num_params - omitted)
+            .collect();
+        fun.item
+            .attrs
+            .retain(|attr| !attr.path.is_ident("link_name"));
+        fun.default_args_suffix = Some(if omitted == num_defaults {
+            "_with_defaults".to_string()
+        } else {
+            match fun.item.sig.inputs.last()? {
+                FnArg::Typed(PatType { pat, .. }) => match pat.as_ref() {
+                    Pat::Ident(pat) => format!("_with_{}", pat.ident),
+                    _ => return None,
+                },
+                FnArg::Receiver(_) => return None,
+            }
+        });
+        Some(fun)
+    }
 }
 
 /// bindgen sometimes generates an impl fn called a which calls
@@ -156,8 +334,42 @@ fn get_called_function(block: &Block) -> Option<&Ident> {
     }
 }
 
+/// The components of a variable's name, mangled according to the
+/// Itanium C++ ABI, e.g. `_ZN2ns4Anna3BOBE` gives `ns`, `Anna` and `BOB`.
+/// Only handles the simple names given to non-templated variables within
+/// namespaces and classes.
+fn demangle_simple_name(mangled: &str) -> Option<Vec<String>> {
+    // Some platforms prefix all symbols with an extra underscore.
+    let mut rest = mangled.trim_start_matches('_').strip_prefix('Z')?;
+    let nested = rest.starts_with('N');
+    if nested {
+        rest = &rest[1..];
+    }
+    let mut components = Vec::new();
+    loop {
+        if nested && rest == "E" && !components.is_empty() {
+            return Some(components);
+        }
+        // Variables with internal linkage are marked with 'L'.
+        rest = rest.strip_prefix('L').unwrap_or(rest);
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let len: usize = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        components.push(rest.get(..len)?.to_string());
+        rest = &rest[len..];
+        if !nested {
+            return if rest.is_empty() {
+                Some(components)
+            } else {
+                None
+            };
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use super::demangle_simple_name;
     use super::get_called_function;
     use syn::parse_quote;
     use syn::Block;
@@ -171,4 +383,20 @@ mod test {
         };
         assert_eq!(get_called_function(&b).unwrap().to_string(), "call_foo");
     }
+
+    #[test]
+    fn test_demangle_simple_name() {
+        assert_eq!(
+            demangle_simple_name("_ZN2ns4Anna3BOBE").unwrap(),
+            vec!["ns", "Anna", "BOB"]
+        );
+        assert_eq!(demangle_simple_name("_ZL3BOB").unwrap(), vec!["BOB"]);
+        assert_eq!(
+            demangle_simple_name("__ZN2nsL7COUNTERE").unwrap(),
+            vec!["ns", "COUNTER"]
+        );
+        assert!(demangle_simple_name("_ZN2ns4AnnaIiE3BOBE").is_none());
+        assert!(demangle_simple_name("_ZN2ns").is_none());
+        assert!(demangle_simple_name("COUNTER").is_none());
+    }
 }
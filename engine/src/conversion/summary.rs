@@ -0,0 +1,328 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use autocxx_parser::IncludeCppConfig;
+use quote::ToTokens;
+
+use crate::{clang_fn_info::ClangFnInfo, types::QualifiedName};
+
+use super::{
+    analysis::fun::{FnAnalysis, FnAnalysisBody, FnKind},
+    api::{Api, ApiDetail, FuncToConvert, TypeKind},
+    codegen_cpp::type_to_cpp::{
+        namespaced_name_using_original_name_map, original_name_map_from_apis, OriginalNameMap,
+    },
+    convert_error::ErrorContext,
+};
+
+/// Everything we generated within a single C++ namespace.
+#[derive(Default)]
+struct NamespaceSummary {
+    types: BTreeMap<String, TypeSummary>,
+    functions: Vec<String>,
+    ignored: Vec<String>,
+}
+
+#[derive(Default)]
+struct TypeSummary {
+    rust_name: String,
+    kind: Option<&'static str>,
+    methods: Vec<String>,
+    ignored: Vec<String>,
+}
+
+/// Generates a Markdown document describing the bindings we've generated:
+/// for each C++ namespace, its types and their methods, its functions, and
+/// anything we've had to ignore, and why. This is intended for humans
+/// reviewing what has changed when the C++ headers change.
+pub(crate) fn generate_summary(
+    apis: &[Api<FnAnalysis>],
+    config: &IncludeCppConfig,
+    fn_info: &ClangFnInfo,
+) -> String {
+    let mod_name = config.get_mod_name().to_string();
+    let original_name_map = original_name_map_from_apis(apis);
+    let mut namespaces: BTreeMap<String, NamespaceSummary> = BTreeMap::new();
+    for api in apis {
+        let ns = namespaces
+            .entry(api.name.get_namespace().to_string())
+            .or_default();
+        let type_kind = match &api.detail {
+            ApiDetail::Struct { analysis, .. } | ApiDetail::Union { analysis, .. } => {
+                type_kind_description(analysis)
+            }
+            ApiDetail::Enum { .. } => "enum",
+            ApiDetail::Typedef { .. } => "typedef",
+            ApiDetail::ForwardDeclaration => "forward declaration",
+            ApiDetail::ConcreteType { .. } => "template instantiation",
+            ApiDetail::Function { fun, analysis } => {
+                let description = describe_function(fun, analysis, fn_info);
+                match &analysis.kind {
+                    FnKind::Method(self_ty, _) => ns
+                        .types
+                        .entry(self_ty.get_final_item().to_string())
+                        .or_default()
+                        .methods
+                        .push(description),
+                    FnKind::Function => ns.functions.push(description),
+                }
+                continue;
+            }
+            ApiDetail::IgnoredItem { err, ctx } => {
+                match ctx {
+                    ErrorContext::Method { self_ty, method } => ns
+                        .types
+                        .entry(self_ty.to_string())
+                        .or_default()
+                        .ignored
+                        .push(format!("* `{}`: {}", method, err)),
+                    ErrorContext::Item(id) => ns.ignored.push(format!("* `{}`: {}", id, err)),
+                }
+                continue;
+            }
+            ApiDetail::StringConstructor
+            | ApiDetail::Const { .. }
+            | ApiDetail::BitfieldUnit { .. }
+            | ApiDetail::CType { .. } => continue,
+        };
+        add_type(ns, &api.name, config, &original_name_map, type_kind);
+    }
+
+    let mut output = String::new();
+    writeln!(output, "# Bindings in `{}`", mod_name).unwrap();
+    if !config.inclusions.is_empty() {
+        writeln!(output, "\nGenerated from:\n").unwrap();
+        for inclusion in &config.inclusions {
+            writeln!(output, "* `#include \"{}\"`", inclusion).unwrap();
+        }
+    }
+    for (ns_name, ns) in namespaces {
+        if ns.types.is_empty() && ns.functions.is_empty() && ns.ignored.is_empty() {
+            continue;
+        }
+        if ns_name.is_empty() {
+            writeln!(output, "\n## Global namespace").unwrap();
+        } else {
+            writeln!(output, "\n## Namespace `{}`", ns_name).unwrap();
+        }
+        let prefix = if ns_name.is_empty() {
+            String::new()
+        } else {
+            format!("{}::", ns_name)
+        };
+        for (type_name, ty) in ns.types {
+            writeln!(output, "\n### `{}{}`\n", prefix, type_name).unwrap();
+            let rust_name = if ty.rust_name.is_empty() {
+                &type_name
+            } else {
+                &ty.rust_name
+            };
+            write!(output, "Rust type: `{}::{}{}`", mod_name, prefix, rust_name).unwrap();
+            match ty.kind {
+                Some(kind) => writeln!(output, " ({})", kind).unwrap(),
+                None => writeln!(output).unwrap(),
+            }
+            write_list(&mut output, "Methods", &ty.methods);
+            write_list(&mut output, "Ignored methods", &ty.ignored);
+        }
+        if !ns.functions.is_empty() || !ns.ignored.is_empty() {
+            writeln!(output, "\n### Other items").unwrap();
+            write_list(&mut output, "Functions", &ns.functions);
+            write_list(&mut output, "Ignored", &ns.ignored);
+        }
+    }
+    output
+}
+
+fn add_type(
+    ns: &mut NamespaceSummary,
+    name: &QualifiedName,
+    config: &IncludeCppConfig,
+    original_name_map: &OriginalNameMap,
+    kind: &'static str,
+) {
+    let ty = ns
+        .types
+        .entry(name.get_final_item().to_string())
+        .or_default();
+    ty.kind = Some(kind);
+    // Look up renames just as codegen does, by the type's C++ name.
+    let cpp_name = namespaced_name_using_original_name_map(name, original_name_map);
+    ty.rust_name = config
+        .rename_for_type(&cpp_name)
+        .unwrap_or_else(|| name.get_final_item())
+        .to_string();
+}
+
+fn type_kind_description(kind: &TypeKind) -> &'static str {
+    match kind {
+        TypeKind::Pod => "POD",
+        TypeKind::NonPod => "non-POD",
+        TypeKind::Abstract => "abstract",
+    }
+}
+
+/// Describes a function by its C++ signature, if we know it, and the
+/// signature of the function in the `cxx::bridge`.
+fn describe_function(
+    fun: &FuncToConvert,
+    analysis: &FnAnalysisBody,
+    fn_info: &ClangFnInfo,
+) -> String {
+    let name = &analysis.rust_name;
+    let params = tidy_tokens(analysis.params.to_token_stream().to_string());
+    let ret = tidy_tokens(analysis.ret_type.to_token_stream().to_string());
+    let rust_signature = format!("fn {}({}) {}", name, params, ret);
+    match fn_info.signature_for(&fun.item.attrs) {
+        Some(cpp_signature) => format!(
+            "* `{}`: `{}` as `{}`",
+            name,
+            cpp_signature,
+            rust_signature.trim_end()
+        ),
+        None => format!("* `{}`", rust_signature.trim_end()),
+    }
+}
+
+/// Removes some of the spaces which `proc_macro2` puts between tokens,
+/// so that types look more like a human would write them.
+fn tidy_tokens(tokens: String) -> String {
+    [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" < ", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" , ", ", "),
+        (" : ", ": "),
+        ("& ", "&"),
+        ("* mut ", "*mut "),
+        ("* const ", "*const "),
+        ("( ", "("),
+        (" )", ")"),
+    ]
+    .iter()
+    .fold(tokens, |tokens, (from, to)| tokens.replace(from, to))
+}
+
+fn write_list(output: &mut String, heading: &str, items: &[String]) {
+    if !items.is_empty() {
+        writeln!(output, "\n{}:\n", heading).unwrap();
+        for item in items {
+            writeln!(output, "{}", item).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use autocxx_parser::IncludeCppConfig;
+    use proc_macro2::Span;
+    use syn::{parse_quote, Token};
+
+    use super::{generate_summary, tidy_tokens};
+    use crate::{
+        clang_fn_info::ClangFnInfo,
+        conversion::{
+            analysis::fun::FnAnalysis,
+            api::{Api, ApiDetail, TypeKind},
+            convert_error::{ConvertError, ErrorContext},
+        },
+        types::{make_ident, QualifiedName},
+    };
+
+    fn make_api(name: &str, detail: ApiDetail<FnAnalysis>) -> Api<FnAnalysis> {
+        Api {
+            name: QualifiedName::new_from_cpp_name(name),
+            original_name: None,
+            deps: HashSet::new(),
+            detail,
+        }
+    }
+
+    #[test]
+    fn test_tidy_tokens() {
+        assert_eq!(
+            tidy_tokens("a : & mut std :: vector < u32 , * const c_char >".into()),
+            "a: &mut std::vector<u32, *const c_char>"
+        );
+        assert_eq!(
+            tidy_tokens("( self : Pin < & mut Foo > )".into()),
+            "(self: Pin<&mut Foo>)"
+        );
+    }
+
+    #[test]
+    fn test_generate_summary() {
+        let hexathorpe = Token![#](Span::call_site());
+        let config: IncludeCppConfig = parse_quote! {
+            #hexathorpe include "widgets.h"
+            safety!(unsafe_ffi)
+            generate_all!()
+            rename!("ns::Widget", "Gadget")
+        };
+        let apis = vec![
+            make_api(
+                "ns::Widget",
+                ApiDetail::Struct {
+                    item: parse_quote! { pub struct Widget { pub a: u32 } },
+                    details: Box::new(Default::default()),
+                    analysis: TypeKind::Pod,
+                },
+            ),
+            make_api(
+                "ns::Color",
+                ApiDetail::Enum {
+                    item: parse_quote! { pub enum Color { Red } },
+                    analysis: TypeKind::Pod,
+                },
+            ),
+            make_api(
+                "ns::frobnicate",
+                ApiDetail::IgnoredItem {
+                    err: ConvertError::UnexpectedForeignItem,
+                    ctx: ErrorContext::Method {
+                        self_ty: make_ident("Widget"),
+                        method: make_ident("frobnicate"),
+                    },
+                },
+            ),
+            make_api(
+                "ns::bad",
+                ApiDetail::IgnoredItem {
+                    err: ConvertError::UnexpectedOuterItem,
+                    ctx: ErrorContext::Item(make_ident("bad")),
+                },
+            ),
+            make_api("Opaque", ApiDetail::ForwardDeclaration),
+        ];
+        let summary = generate_summary(&apis, &config, &ClangFnInfo::default());
+        assert!(summary.starts_with("# Bindings in `ffi`\n"));
+        assert!(summary.contains("* `#include \"widgets.h\"`"));
+        assert!(summary.contains("## Global namespace"));
+        assert!(summary.contains("### `Opaque`\n\nRust type: `ffi::Opaque` (forward declaration)"));
+        assert!(summary.contains("## Namespace `ns`"));
+        assert!(summary.contains("### `ns::Widget`\n\nRust type: `ffi::ns::Gadget` (POD)"));
+        assert!(summary.contains("Rust type: `ffi::ns::Color` (enum)"));
+        assert!(summary.contains("Ignored methods:\n\n* `frobnicate`: "));
+        assert!(summary.contains("### Other items\n\nIgnored:\n\n* `bad`: "));
+        // Namespaces, and types within them, are in alphabetical order.
+        assert!(summary.find("Global namespace") < summary.find("Namespace `ns`"));
+        assert!(summary.find("ns::Color") < summary.find("ns::Widget"));
+    }
+}
@@ -283,6 +283,7 @@ where
         &extra_clang_args,
         Some(target_dir.clone()),
         None,
+        None,
     )
     .map_err(TestError::AutoCxx)?;
     let mut b = build_results.0;
@@ -1194,6 +1195,89 @@ fn test_make_up_with_args() {
     run_test(cxx, hdr, rs, &["take_bob", "Bob"], &[]);
 }
 
+#[test]
+fn test_make_shared() {
+    let cxx = indoc! {"
+        Bob::Bob(uint32_t a0, uint32_t b0)
+           : a(a0), b(b0) {}
+        uint32_t take_bob(const Bob& a) {
+            return a.a;
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Bob {
+            Bob(uint32_t a, uint32_t b);
+            uint32_t a;
+            uint32_t b;
+        };
+        uint32_t take_bob(const Bob& a);
+    "};
+    let rs = quote! {
+        let a = ffi::Bob::make_shared(12, 13);
+        let b = a.clone();
+        assert_eq!(ffi::take_bob(a.as_ref().unwrap()), 12);
+        assert_eq!(ffi::take_bob(b.as_ref().unwrap()), 12);
+    };
+    run_test(cxx, hdr, rs, &["take_bob", "Bob"], &[]);
+}
+
+#[test]
+fn test_shared_ptr_return_and_weak_ptr() {
+    let cxx = indoc! {"
+        std::shared_ptr<Bob> make_bob(uint32_t a) {
+            return std::make_shared<Bob>(Bob { a });
+        }
+        uint32_t take_bob(std::shared_ptr<Bob> a) {
+            return a->a;
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        struct Bob {
+            uint32_t a;
+        };
+        std::shared_ptr<Bob> make_bob(uint32_t a);
+        uint32_t take_bob(std::shared_ptr<Bob> a);
+    "};
+    let rs = quote! {
+        let a = ffi::make_bob(3);
+        let weak = a.downgrade();
+        assert_eq!(ffi::take_bob(weak.upgrade()), 3);
+        drop(a);
+        assert!(weak.upgrade().is_null());
+    };
+    run_test(cxx, hdr, rs, &["make_bob", "take_bob"], &["Bob"]);
+}
+
+#[test]
+fn test_enable_shared_from_this() {
+    let cxx = indoc! {"
+        Bob::Bob(uint32_t a0) : a(a0) {}
+        uint32_t count_owners(const Bob& bob) {
+            // Subtract the temporary owner we create here.
+            return bob.shared_from_this().use_count() - 1;
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        class Bob : public std::enable_shared_from_this<Bob> {
+        public:
+            Bob(uint32_t a);
+            uint32_t a;
+        };
+        uint32_t count_owners(const Bob& bob);
+    "};
+    let rs = quote! {
+        let a = ffi::Bob::make_shared(7);
+        let b = a.clone();
+        assert_eq!(ffi::count_owners(b.as_ref().unwrap()), 2);
+    };
+    run_test(cxx, hdr, rs, &["Bob", "count_owners"], &[]);
+}
+
 #[test]
 #[ignore] // because we don't support unique_ptrs to primitives
 fn test_make_up_int() {
@@ -1257,6 +1341,122 @@ fn test_enum_no_funcs() {
     run_test(cxx, hdr, rs, &["Bob"], &[]);
 }
 
+#[test]
+fn test_enum_bitflags() {
+    let cxx = indoc! {"
+        Options combine(Options a, Options b) {
+            return static_cast<Options>(a | b);
+        }
+        bool has_b(Options o) {
+            return (o & OPTION_B) != 0;
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        enum Options {
+            OPTION_A = 1,
+            OPTION_B = 2,
+            OPTION_C = 4,
+        };
+        Options combine(Options a, Options b);
+        bool has_b(Options o);
+    "};
+    let rs = quote! {
+        let o = ffi::combine(ffi::Options::OPTION_A, ffi::Options::OPTION_B);
+        assert_eq!(o, ffi::Options::OPTION_A | ffi::Options::OPTION_B);
+        assert_eq!(o.0, 3);
+        assert!(ffi::has_b(o));
+        assert_eq!((o & ffi::Options::OPTION_C).0, 0);
+        let mut p = ffi::Options::OPTION_C;
+        p |= o;
+        assert_eq!(p.0, 7);
+    };
+    run_test_ex(
+        cxx,
+        hdr,
+        rs,
+        &["combine", "has_b"],
+        &[],
+        Some(quote! { bitflags!("Options") }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_enum_newtype() {
+    let cxx = indoc! {"
+        Protocol give_unknown_protocol() {
+            return static_cast<Protocol>(42);
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        enum class Protocol : uint8_t {
+            HTTP,
+            FTP = 5,
+            GOPHER,
+        };
+        Protocol give_unknown_protocol();
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::Protocol::HTTP.0, 0);
+        assert_eq!(ffi::Protocol::GOPHER.0, 6);
+        let p = ffi::give_unknown_protocol();
+        assert_eq!(u8::from(p), 42u8);
+        assert!(p != ffi::Protocol::FTP);
+        use std::convert::TryFrom;
+        assert_eq!(ffi::Protocol::try_from(5u8), Ok(ffi::Protocol::FTP));
+        assert_eq!(ffi::Protocol::try_from(42u8), Err(42u8));
+    };
+    run_test_ex(
+        cxx,
+        hdr,
+        rs,
+        &["give_unknown_protocol"],
+        &[],
+        Some(quote! { newtype_enum!("Protocol") }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_newtype_all_enums() {
+    let cxx = indoc! {"
+        Protocol give_unknown_protocol() {
+            return static_cast<Protocol>(42);
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        enum Protocol {
+            HTTP,
+            FTP,
+        };
+        enum Color {
+            RED,
+            GREEN,
+        };
+        Protocol give_unknown_protocol();
+    "};
+    let rs = quote! {
+        let p = ffi::give_unknown_protocol();
+        assert_eq!(p.0, 42);
+        assert!(ffi::Color::RED != ffi::Color::GREEN);
+    };
+    run_test_ex(
+        cxx,
+        hdr,
+        rs,
+        &["give_unknown_protocol", "Color"],
+        &[],
+        Some(quote! { newtype_all_enums!() }),
+        &[],
+        None,
+    );
+}
+
 #[test] // works, but causes compile warnings
 fn test_take_pod_class_by_value() {
     let cxx = indoc! {"
@@ -1352,6 +1552,150 @@ fn test_define_str() {
     run_test(cxx, hdr, rs, &["BOB"], &[]);
 }
 
+#[test]
+fn test_define_float() {
+    let hdr = indoc! {"
+        #define BOB 3.5
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::BOB, 3.5);
+    };
+    run_test("", hdr, rs, &["BOB"], &[]);
+}
+
+#[test]
+fn test_define_char() {
+    let hdr = indoc! {"
+        #define BOB 'b'
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::BOB, b'b');
+    };
+    run_test("", hdr, rs, &["BOB"], &[]);
+}
+
+#[test]
+fn test_define_float_expression() {
+    let hdr = indoc! {"
+        constexpr double BASE = 1.5;
+        #define HALF_BASE (BASE / 2)
+        #define SMALL (0.5f * 3)
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::HALF_BASE, 0.75);
+        assert_eq!(ffi::SMALL, 1.5);
+    };
+    run_test("", hdr, rs, &["HALF_BASE", "SMALL"], &[]);
+}
+
+#[test]
+fn test_define_char_expression() {
+    let hdr = indoc! {"
+        constexpr char FIRST = 'a';
+        #define SECOND (char)(FIRST + 1)
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::SECOND, b'b');
+    };
+    run_test("", hdr, rs, &["SECOND"], &[]);
+}
+
+#[test]
+fn test_define_enum_expression() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace A {
+            enum class Color : uint32_t { Red, Green };
+        }
+        #define DEFAULT_COLOR A::Color::Green
+    "};
+    let rs = quote! {
+        assert!(matches!(ffi::DEFAULT_COLOR, ffi::A::Color::Green));
+    };
+    run_test("", hdr, rs, &["DEFAULT_COLOR"], &["A::Color"]);
+}
+
+#[test]
+fn test_define_expression_generate_all() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        enum class Color : uint32_t { Red, Green };
+        #define DEFAULT_COLOR Color::Red
+        #define THIRD (1.0 / 3)
+        #define EXTERN_C_BEGIN extern \"C\" {
+        #define EXTERN_C_END }
+    "};
+    let rs = quote! {
+        assert!(matches!(ffi::DEFAULT_COLOR, ffi::Color::Red));
+        assert!(ffi::THIRD > 0.33 && ffi::THIRD < 0.34);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &[],
+        &[],
+        Some(quote! {
+            generate_all!()
+        }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_macro_fn() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #define ADD_ONE(x) ((x) + 1)
+        #define SQUARE(x) ((x) * (x))
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::ADD_ONE(3), 4);
+        assert_eq!(ffi::SQUARE(2.5), 6.25);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &[],
+        &[],
+        Some(quote! {
+            macro_fn!("ADD_ONE", "int32_t(int32_t)")
+            macro_fn!("SQUARE", "double(double)")
+        }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_macro_fn_expression() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        enum class Color : uint32_t { Red, Green };
+        #define DEFAULT_COLOR Color::Green
+        #define MAX_SIZE (sizeof(uint64_t) * 4)
+    "};
+    let rs = quote! {
+        assert!(matches!(ffi::DEFAULT_COLOR(), ffi::Color::Green));
+        assert_eq!(ffi::MAX_SIZE(), 32);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["Color"],
+        &[],
+        Some(quote! {
+            macro_fn!("DEFAULT_COLOR")
+            macro_fn!("MAX_SIZE", "uint32_t")
+        }),
+        &[],
+        None,
+    );
+}
+
 #[test]
 fn test_i32_const() {
     let cxx = indoc! {"
@@ -2183,8 +2527,14 @@ fn test_overload_functions() {
 }
 
 #[test]
-#[ignore] // At present, bindgen generates two separate 'daft1'
-          // functions here, and there's not much we can do about that.
+// This stays ignored. bindgen names the second overload of `daft` as
+// `daft1`, which is also the name of the unrelated C++ `daft1`, so its
+// output contains two functions named `daft1`. We receive both under the
+// same name, and choose Rust names only after bindgen has run, so neither
+// `overloads_by_signature!` nor `rename!` can tell them apart. Headers
+// which avoid such clashes get stable names: see
+// `test_overload_by_signature`.
+#[ignore]
 fn test_overload_numeric_functions() {
     // Because bindgen deals with conflicting overloaded functions by
     // appending a numeric suffix, let's see if we can cope.
@@ -2272,31 +2622,208 @@ fn test_overload_methods() {
 }
 
 #[test]
-fn test_ns_constructor() {
+fn test_overload_by_signature() {
     let cxx = indoc! {"
-        A::Bob::Bob() {}
+        void daft(uint32_t) {}
+        void daft(uint8_t) {}
+        void daft(std::string) {}
+        void daft(Fred) {}
+        void Bob::daft(uint32_t) const {}
+        void Bob::daft(uint8_t) const {}
+        void undaft(uint32_t) {}
     "};
     let hdr = indoc! {"
         #include <cstdint>
-        #include <memory>
-        namespace A {
-            struct Bob {
-                Bob();
-                uint32_t a;
-                uint32_t b;
-            };
-        }
+        #include <string>
+        struct Fred {
+            uint32_t a;
+        };
+        struct Bob {
+            uint32_t a;
+            void daft(uint32_t) const;
+            void daft(uint8_t) const;
+        };
+        void daft(uint32_t);
+        void daft(uint8_t);
+        void daft(std::string);
+        void daft(Fred);
+        void undaft(uint32_t);
     "};
     let rs = quote! {
-        ffi::A::Bob::make_unique();
+        use ffi::ToCppString;
+        ffi::daft_uint32_t(32);
+        ffi::daft_uint8_t(8);
+        ffi::daft_string("hello".into_cpp());
+        ffi::daft_Fred(ffi::Fred { a: 3 });
+        ffi::undaft(4);
+        let a = ffi::Bob { a: 12 };
+        a.daft_uint32_t(32);
+        a.daft_uint8_t(8);
     };
-    run_test(cxx, hdr, rs, &["A::Bob"], &[]);
-}
-
-#[test]
-fn test_ns_up_direct() {
-    let cxx = indoc! {"
-        std::unique_ptr<A::Bob> A::get_bob() {
+    run_test_ex(
+        cxx,
+        hdr,
+        rs,
+        &["daft", "daft1", "daft2", "daft3", "undaft"],
+        &["Fred", "Bob"],
+        Some(quote! { overloads_by_signature!() }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_rename_overloads() {
+    let cxx = indoc! {"
+        void daft(uint32_t) {}
+        void daft(uint8_t) {}
+        Bob::Bob() : a(0) {}
+        Bob::Bob(uint32_t a) : a(a) {}
+        uint32_t Bob::get(uint32_t) const { return a; }
+        uint32_t Bob::get(uint8_t b) const { return a + b; }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Bob {
+            Bob();
+            Bob(uint32_t a);
+            uint32_t get(uint32_t) const;
+            uint32_t get(uint8_t b) const;
+            uint32_t a;
+        };
+        void daft(uint32_t);
+        void daft(uint8_t);
+    "};
+    let rs = quote! {
+        ffi::daft(32);
+        ffi::daft_from_byte(8);
+        let b = ffi::Bob::make_unique();
+        let c = ffi::Bob::make_unique_from_value(3);
+        assert_eq!(c.get(1), 3);
+        assert_eq!(c.get_plus_byte(1), 4);
+        assert_eq!(b.get(0), 0);
+    };
+    run_test_ex(
+        cxx,
+        hdr,
+        rs,
+        &["daft", "daft1", "Bob"],
+        &[],
+        Some(quote! {
+            rename!("daft(uint8_t)", "daft_from_byte")
+            rename!("Bob::Bob(uint32_t)", "from_value")
+            rename!("Bob::get(uint8_t)", "get_plus_byte")
+        }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_rename_overload_taking_nested_type() {
+    // Renames refer to parameter types by their C++ names, even where
+    // bindgen has flattened them.
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Outer {
+            struct Inner {
+                uint32_t a;
+            };
+        };
+        inline uint32_t daft(uint32_t a) { return a; }
+        inline uint32_t daft(const Outer::Inner& b) { return b.a + 1; }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::daft(3), 3);
+        let _ = ffi::daft_from_inner;
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["daft", "Outer"],
+        &[],
+        Some(quote! {
+            rename!("daft(const Outer::Inner &)", "daft_from_inner")
+        }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_rename_types_and_methods() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace ns {
+            struct widget_t {
+                widget_t() : a(0) {}
+                uint32_t GetValue() const { return a; }
+                void SetValue(uint32_t value) { a = value; }
+                uint32_t GetHTTPStatus() const { return 200; }
+                uint32_t a;
+            };
+            enum class color_t { Red, Green };
+            inline color_t default_color() { return color_t::Green; }
+            inline uint32_t MakeTotal(const widget_t& w) { return w.a; }
+        }
+    "};
+    let rs = quote! {
+        let mut w = ffi::ns::Widget::make_unique();
+        w.pin_mut().set_value(3);
+        assert_eq!(w.get_value(), 3);
+        assert_eq!(w.get_http_status(), 200);
+        assert_eq!(ffi::ns::total(&w), 3);
+        assert!(matches!(ffi::ns::default_color(), ffi::ns::Color::Green));
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &[
+            "ns::widget_t",
+            "ns::color_t",
+            "ns::default_color",
+            "ns::MakeTotal",
+        ],
+        &[],
+        Some(quote! {
+            snake_case_methods!()
+            rename!("ns::widget_t", "Widget")
+            rename!("ns::color_t", "Color")
+            rename!("ns::MakeTotal", "total")
+        }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_ns_constructor() {
+    let cxx = indoc! {"
+        A::Bob::Bob() {}
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        namespace A {
+            struct Bob {
+                Bob();
+                uint32_t a;
+                uint32_t b;
+            };
+        }
+    "};
+    let rs = quote! {
+        ffi::A::Bob::make_unique();
+    };
+    run_test(cxx, hdr, rs, &["A::Bob"], &[]);
+}
+
+#[test]
+fn test_ns_up_direct() {
+    let cxx = indoc! {"
+        std::unique_ptr<A::Bob> A::get_bob() {
             A::Bob b;
             b.a = 2;
             b.b = 3;
@@ -2406,6 +2933,311 @@ fn test_return_reference() {
     run_test(cxx, hdr, rs, &["give_bob"], &["Bob"]);
 }
 
+#[test]
+fn test_return_reference_from_method_with_reference_params() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        #include <map>
+        struct Key {
+            uint32_t id;
+        };
+        class Registry {
+        public:
+            Registry() {
+                names[1] = \"one\";
+            }
+            const std::string& name(const Key& key) const { return names.at(key.id); }
+        private:
+            std::map<uint32_t, std::string> names;
+        };
+        inline const std::string& lookup(const Registry& registry, const Key& key) {
+            return registry.name(key);
+        }
+    "};
+    let rs = quote! {
+        let registry = ffi::Registry::make_unique();
+        let key = ffi::Key { id: 1 };
+        let name = registry.name(&key);
+        drop(key);
+        assert_eq!(name.to_str().unwrap(), "one");
+        let key = ffi::Key { id: 1 };
+        assert_eq!(ffi::lookup(&registry, &key).to_str().unwrap(), "one");
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["Registry", "lookup"],
+        &["Key"],
+        Some(quote! {
+            borrows_from!("lookup", "registry")
+        }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_pointer_params() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Point {
+            int32_t x;
+            int32_t y;
+        };
+        inline int32_t x_or_default(const Point* point) {
+            return point ? point->x : -1;
+        }
+        inline void reset(Point* point) {
+            if (point) {
+                point->x = 0;
+            }
+        }
+        inline uint32_t sum(const uint32_t* values, uint32_t count) {
+            uint32_t total = 0;
+            for (uint32_t i = 0; i < count; i++) {
+                total += values[i];
+            }
+            return total;
+        }
+        inline void fill(uint32_t len, uint32_t* buf, uint32_t value) {
+            for (uint32_t i = 0; i < len; i++) {
+                buf[i] = value;
+            }
+        }
+        inline bool divide(uint32_t a, uint32_t b, uint32_t* quotient, uint32_t* remainder) {
+            if (b == 0) {
+                return false;
+            }
+            *quotient = a / b;
+            *remainder = a % b;
+            return true;
+        }
+    "};
+    let rs = quote! {
+        let mut point = ffi::Point { x: 3, y: 4 };
+        assert_eq!(ffi::x_or_default(Some(&point)), 3);
+        assert_eq!(ffi::x_or_default(None), -1);
+        ffi::reset(Some(std::pin::Pin::new(&mut point)));
+        ffi::reset(None);
+        assert_eq!(point.x, 0);
+        assert_eq!(ffi::sum(&[1, 2, 3]), 6);
+        let mut buf = [0u32; 4];
+        ffi::fill(&mut buf, 7);
+        assert_eq!(buf, [7, 7, 7, 7]);
+        assert_eq!(ffi::divide(7, 2), (true, 3, 1));
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["x_or_default", "reset", "sum", "fill", "divide"],
+        &["Point"],
+        Some(quote! {
+            pointer_param!("x_or_default", "point", nullable)
+            pointer_param!("reset", "point", nullable)
+            pointer_param!("sum", "values", slice("count"))
+            pointer_param!("fill", "buf", slice("len"))
+            pointer_param!("divide", "quotient", out)
+            pointer_param!("divide", "remainder", out)
+        }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_out_params() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        struct Point {
+            int32_t x;
+            int32_t y;
+        };
+        class Record {
+        public:
+            Record() : id(0) {}
+            uint32_t get_id() const { return id; }
+            uint32_t id;
+            std::string name;
+        };
+        inline bool parse_point(uint32_t packed, Point& result) {
+            result.x = packed >> 16;
+            result.y = packed & 0xffff;
+            return packed != 0;
+        }
+        inline int32_t find_record(uint32_t id, Record* record) {
+            if (id == 0) {
+                return -1;
+            }
+            record->id = id;
+            return 0;
+        }
+        inline void split(uint32_t input, uint32_t& out_high, uint32_t& out_low) {
+            out_high = input >> 16;
+            out_low = input & 0xffff;
+        }
+        inline bool origin_if(bool wanted, Point& out_point) {
+            return wanted;
+        }
+    "};
+    let rs = quote! {
+        let (ok, point) = ffi::parse_point(0x30004);
+        assert!(ok);
+        assert_eq!(point.x, 3);
+        assert_eq!(point.y, 4);
+        let record = ffi::find_record(7).unwrap();
+        assert_eq!(record.get_id(), 7);
+        assert_eq!(ffi::find_record(0).err(), Some(-1));
+        assert_eq!(ffi::split(0x50006), (5, 6));
+        let (_, point) = ffi::origin_if(false);
+        assert_eq!(point.x, 0);
+        assert_eq!(point.y, 0);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["parse_point", "find_record", "split", "origin_if", "Record"],
+        &["Point"],
+        Some(quote! {
+            out_param!("parse_point", "result")
+            out_param!("find_record", "record")
+            out_params_as_result!("find_record")
+            detect_out_params!()
+        }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_out_params_as_result_c_int_status() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline int read_value(uint32_t key, uint32_t& value) {
+            if (key == 0) {
+                return -1;
+            }
+            value = key * 2;
+            return 0;
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::read_value(3).unwrap(), 6);
+        assert_eq!(ffi::read_value(0).err(), Some(autocxx::c_int(-1)));
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["read_value"],
+        &[],
+        Some(quote! {
+            out_param!("read_value", "value")
+            out_params_as_result!("read_value")
+        }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_const_overloads() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Holder {
+        public:
+            Holder() : value(3) {}
+            const uint32_t& get() const { return value; }
+            uint32_t& get() { return value; }
+            uint32_t get(uint32_t extra) const { return value + extra; }
+        private:
+            uint32_t value;
+        };
+    "};
+    let rs = quote! {
+        let mut holder = ffi::Holder::make_unique();
+        assert_eq!(*holder.get(), 3);
+        *ffi::Holder::get_mut(holder.pin_mut()) = 4;
+        assert_eq!(*holder.get(), 4);
+        assert_eq!(holder.get1(1), 5);
+    };
+    run_test("", hdr, rs, &["Holder"], &[]);
+}
+
+#[test]
+fn test_nodiscard_and_deprecated() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        [[nodiscard]] inline uint32_t checked_add(uint32_t a, uint32_t b) { return a + b; }
+        [[deprecated(\"use checked_add\")]] inline uint32_t old_add(uint32_t a, uint32_t b) {
+            return a + b;
+        }
+        class Calculator {
+        public:
+            Calculator() {}
+            [[nodiscard]] uint32_t add(uint32_t a, uint32_t b) const { return a + b; }
+        };
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::checked_add(1, 2), 3);
+        #[allow(deprecated)]
+        let sum = ffi::old_add(1, 2);
+        assert_eq!(sum, 3);
+        let calculator = ffi::Calculator::make_unique();
+        assert_eq!(calculator.add(1, 2), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["checked_add", "old_add", "Calculator"],
+        &[],
+        None,
+        &[],
+        Some(Box::new(|f| {
+            // The attributes go on our Rust wrapper function if there is
+            // one, or otherwise on the cxx::bridge function.
+            fn attrs_of(items: &[Item], name: &str) -> Option<Vec<syn::Attribute>> {
+                items.iter().find_map(|i| match i {
+                    Item::Fn(f) if f.sig.ident == name => Some(f.attrs.clone()),
+                    Item::Impl(imp) => imp.items.iter().find_map(|ii| match ii {
+                        syn::ImplItem::Method(m) if m.sig.ident == name => Some(m.attrs.clone()),
+                        _ => None,
+                    }),
+                    Item::ForeignMod(fm) => fm.items.iter().find_map(|fi| match fi {
+                        syn::ForeignItem::Fn(f) if f.sig.ident == name => Some(f.attrs.clone()),
+                        _ => None,
+                    }),
+                    Item::Mod(m) => attrs_of(&m.content.as_ref()?.1, name),
+                    _ => None,
+                })
+            }
+            let items = find_ffi_items(f)?;
+            let has_attr = |name: &str, attr: &str| {
+                attrs_of(&items, name)
+                    .map(|attrs| {
+                        attrs
+                            .iter()
+                            .any(|a| a.to_token_stream().to_string().contains(attr))
+                    })
+                    .unwrap_or(false)
+            };
+            if has_attr("checked_add", "must_use")
+                && has_attr("old_add", "use checked_add")
+                && has_attr("add", "must_use")
+            {
+                Ok(())
+            } else {
+                Err(TestError::RsCodeExaminationFail)
+            }
+        })),
+    );
+}
+
 #[test]
 fn test_destructor() {
     let hdr = indoc! {"
@@ -2871,7 +3703,6 @@ fn test_pod_constant_harmless_inside_type() {
 }
 
 #[test]
-#[ignore] // https://github.com/google/autocxx/issues/93
 fn test_pod_constant() {
     let hdr = indoc! {"
         #include <cstdint>
@@ -2881,7 +3712,7 @@ fn test_pod_constant() {
         const Bob BOB = Bob { 10 };
     "};
     let rs = quote! {
-        let a = &ffi::BOB;
+        let a: &'static ffi::Bob = ffi::BOB();
         assert_eq!(a.a, 10);
     };
     run_test("", hdr, rs, &["BOB"], &["Bob"]);
@@ -2890,7 +3721,6 @@ fn test_pod_constant() {
 #[test]
 fn test_pod_static_harmless_inside_type() {
     // Check that the presence of this constant doesn't break anything.
-    // Remove this test when the following one is enabled.
     let hdr = indoc! {"
         #include <cstdint>
         struct Bob {
@@ -2907,7 +3737,6 @@ fn test_pod_static_harmless_inside_type() {
 }
 
 #[test]
-#[ignore] // https://github.com/google/autocxx/issues/93
 fn test_pod_static() {
     let hdr = indoc! {"
         #include <cstdint>
@@ -2915,34 +3744,257 @@ fn test_pod_static() {
             uint32_t a;
         };
         static Bob BOB = Bob { 10 };
+        inline uint32_t get_bob_a() { return BOB.a; }
     "};
     let rs = quote! {
-        let a = &ffi::BOB;
+        let a = unsafe { ffi::BOB() };
         assert_eq!(a.a, 10);
+        unsafe { ffi::BOB() }.a = 12;
+        assert_eq!(ffi::get_bob_a(), 12);
     };
-    run_test("", hdr, rs, &["BOB"], &["Bob"]);
+    run_test("", hdr, rs, &["BOB", "get_bob_a"], &["Bob"]);
+}
+
+#[test]
+fn test_mutable_global_primitive() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        static uint32_t COUNTER;
+        inline uint32_t get_counter() { return COUNTER; }
+    "};
+    let rs = quote! {
+        assert_eq!(*unsafe { ffi::COUNTER() }, 0);
+        *unsafe { ffi::COUNTER() } = 4;
+        assert_eq!(ffi::get_counter(), 4);
+    };
+    run_test("", hdr, rs, &["COUNTER", "get_counter"], &[]);
+}
+
+#[test]
+fn test_pod_static_member() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Bob {
+            uint32_t a;
+        };
+        struct Anna {
+            uint32_t a;
+            static Bob BOB;
+        };
+        Bob Anna::BOB = Bob { 10 };
+    "};
+    let rs = quote! {
+        let a = unsafe { ffi::Anna::BOB() };
+        assert_eq!(a.a, 10);
+    };
+    run_test("", hdr, rs, &[], &["Anna", "Bob"]);
+}
+
+#[test]
+fn test_static_member_in_namespace() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace A {
+            struct Anna {
+                uint32_t a;
+                static uint32_t COUNT;
+                struct Inner {
+                    static uint32_t LIMIT;
+                };
+            };
+            uint32_t Anna::COUNT = 4;
+            uint32_t Anna::Inner::LIMIT = 6;
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(*unsafe { ffi::A::Anna::COUNT() }, 4);
+        assert_eq!(*unsafe { ffi::A::Anna_Inner::LIMIT() }, 6);
+    };
+    run_test("", hdr, rs, &[], &["A::Anna", "A::Anna_Inner"]);
+}
+
+#[test]
+fn test_global_named_like_static_member() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace A {
+            struct Anna {
+                uint32_t a;
+            };
+            uint32_t Anna_COUNT = 4;
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(*unsafe { ffi::A::Anna_COUNT() }, 4);
+    };
+    run_test("", hdr, rs, &["A::Anna_COUNT"], &["A::Anna"]);
 }
 
 #[test]
-#[ignore] // this probably requires code generation on the C++
-          // side. It's not at all clear how best to handle this.
 fn test_non_pod_constant() {
     let hdr = indoc! {"
         #include <cstdint>
         #include <string>
         struct Bob {
             std::string a;
-            std::string get() { return a };
+            std::string get() const { return a; };
         };
         const Bob BOB = Bob { \"hello\" };
     "};
     let rs = quote! {
-        let a = ffi::BOB;
-        // following line assumes that 'a' is a &Bob
-        // but who knows how we'll really do this.
+        let a: &'static ffi::Bob = ffi::BOB();
         assert_eq!(a.get().as_ref().unwrap().to_str().unwrap(), "hello");
     };
-    run_test("", hdr, rs, &["BOB"], &[]);
+    run_test("", hdr, rs, &["BOB", "Bob"], &[]);
+}
+
+#[test]
+fn test_pod_layout_assertions() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct A {
+            uint8_t a;
+            uint32_t type;
+            uint16_t c;
+        };
+        inline A make_a() { return A { 1, 2, 3 }; }
+    "};
+    let rs = quote! {
+        let a = ffi::make_a();
+        assert_eq!(a.type_, 2);
+        assert_eq!(a.c, 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["make_a"],
+        &["A"],
+        None,
+        &[],
+        Some(Box::new(|f| {
+            let items = find_ffi_items(f)?;
+            let has_size_check = items.iter().any(|i| {
+                matches!(i, Item::Const(c) if c.ident == "_" &&
+                    c.ty.to_token_stream().to_string().contains("size_of"))
+            });
+            let has_offset_test = items.iter().any(|i| {
+                matches!(i, Item::Fn(f) if f.sig.ident == "autocxx_layout_test_A" &&
+                    f.block.to_token_stream().to_string().contains("type_"))
+            });
+            if has_size_check && has_offset_test {
+                Ok(())
+            } else {
+                Err(TestError::RsCodeExaminationFail)
+            }
+        })),
+    );
+}
+
+#[test]
+fn test_pod_layout_assertions_same_name() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace a {
+            struct Foo {
+                uint32_t x;
+            };
+        }
+        namespace b {
+            struct Foo {
+                uint8_t y;
+                uint16_t z;
+            };
+        }
+        inline uint32_t total(a::Foo f, b::Foo g) { return f.x + g.y + g.z; }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::total(ffi::a::Foo { x: 1 }, ffi::b::Foo { y: 2, z: 3 }), 6);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["total"],
+        &["a::Foo", "b::Foo"],
+        None,
+        &[],
+        Some(Box::new(|f| {
+            let items = find_ffi_items(f)?;
+            let has_test = |name: &str| {
+                items
+                    .iter()
+                    .any(|i| matches!(i, Item::Fn(f) if f.sig.ident == name))
+            };
+            if has_test("autocxx_layout_test_a__Foo") && has_test("autocxx_layout_test_b__Foo") {
+                Ok(())
+            } else {
+                Err(TestError::RsCodeExaminationFail)
+            }
+        })),
+    );
+}
+
+#[test]
+fn test_pod_layout_assertions_private_fields() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class A {
+        public:
+            A() : type_(2), secret(3) {}
+            uint32_t get_secret() const { return secret; }
+            uint32_t type_;
+        private:
+            uint32_t secret;
+        };
+        inline A make_a() { return A(); }
+    "};
+    let rs = quote! {
+        let a = ffi::make_a();
+        assert_eq!(a.type_, 2);
+        assert_eq!(a.get_secret(), 3);
+    };
+    run_test("", hdr, rs, &["make_a"], &["A"]);
+}
+
+#[test]
+fn test_bitfields() {
+    let cxx = indoc! {"
+        Reg make_reg() {
+            Reg r;
+            r.enable = 1;
+            r.mode = 5;
+            r.count = 1000;
+            r.other = 7;
+            return r;
+        }
+        uint32_t get_mode(const Reg& r) {
+            return r.mode;
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Reg {
+            uint32_t enable:1;
+            uint32_t mode:3;
+            uint32_t count:12;
+            uint16_t other;
+        };
+        Reg make_reg();
+        uint32_t get_mode(const Reg& r);
+    "};
+    let rs = quote! {
+        let mut r = ffi::make_reg();
+        assert_eq!(r.get_enable(), 1);
+        assert_eq!(r.get_mode(), 5);
+        assert_eq!(r.get_count(), 1000);
+        assert_eq!(r.other, 7);
+        r.set_mode(2);
+        assert_eq!(r.get_mode(), 2);
+        assert_eq!(r.get_count(), 1000);
+        assert_eq!(ffi::get_mode(&r), 2);
+    };
+    run_test(cxx, hdr, rs, &["make_reg", "get_mode"], &["Reg"]);
 }
 
 #[test]
@@ -3730,49 +4782,215 @@ fn test_nested_type() {
 }
 
 #[test]
-fn test_nested_type_in_namespace() {
-    // Test that we can import APIs that use nested types in a namespace.
-    // We can't make this part of the previous test as autocxx drops the
-    // namespace, so `A::B` and `N::A::B` would be imported as the same
-    // type.
+fn test_nested_type_in_namespace() {
+    // Test that we can import APIs that use nested types in a namespace.
+    // We can't make this part of the previous test as autocxx drops the
+    // namespace, so `A::B` and `N::A::B` would be imported as the same
+    // type.
+    let hdr = indoc! {"
+        namespace N {
+            struct A {
+                A() {}
+                struct B {
+                    B() {}
+                };
+            };
+        };
+        void take_A_B(N::A::B);
+    "};
+    let rs = quote! {};
+    run_test("", hdr, rs, &["take_A_B"], &[]);
+}
+
+#[test]
+fn test_generic_type() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        template<typename TY>
+        struct Container {
+            Container(TY a_) : a(a_) {}
+            TY a;
+        };
+        struct Secondary {
+            Secondary() {}
+            void take_a(const Container<char>) const {}
+            void take_b(const Container<uint16_t>) const {}
+            uint16_t take_c(std::string a) const { return 10 + a.size(); }
+        };
+    "};
+    let rs = quote! {
+        use ffi::ToCppString;
+        let item = ffi::Secondary::make_unique();
+        assert_eq!(item.take_c("hello".into_cpp()), 15)
+    };
+    run_test("", hdr, rs, &["Secondary"], &[]);
+}
+
+#[test]
+fn test_instantiate_pod() {
+    let cxx = indoc! {"
+        float sum(Point<float> p) {
+            return p.x + p.y;
+        }
+        Point<float> make_point(float x, float y) {
+            Point<float> p;
+            p.x = x;
+            p.y = y;
+            return p;
+        }
+    "};
+    let hdr = indoc! {"
+        template<typename T>
+        struct Point {
+            T x;
+            T y;
+        };
+        float sum(Point<float> p);
+        Point<float> make_point(float x, float y);
+    "};
+    let rs = quote! {
+        let p = ffi::PointF { x: 1.0, y: 2.0 };
+        assert_eq!(ffi::sum(p), 3.0);
+        let p = ffi::make_point(3.0, 4.0);
+        assert_eq!(p.y, 4.0);
+    };
+    run_test_ex(
+        cxx,
+        hdr,
+        rs,
+        &["sum", "make_point"],
+        &["PointF"],
+        Some(quote! { instantiate!("Point<float>", "PointF") }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_instantiate_opaque() {
+    let hdr = indoc! {"
+        namespace ns {
+        template<typename T, int N>
+        struct Vector {
+            Vector() {}
+            T data[N];
+        };
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::Vector3u::SIZE, 12);
+        assert_eq!(ffi::Vector3u::ALIGN, 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &[],
+        &[],
+        Some(quote! { instantiate!("ns::Vector<uint32_t, 3>", "Vector3u") }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_instantiate_methods() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace geom {
+        template<typename T>
+        struct Point {
+            typedef T value_type;
+            T x;
+            T y;
+            T sum(T scale) const { return (x + y) * scale; }
+            void set_x(value_type value) { x = value; }
+            Point<T> doubled() const { return Point<T> { x * 2, y * 2 }; }
+            static Point origin() { return Point { 0, 0 }; }
+        private:
+            void hidden() {}
+        };
+        }
+    "};
+    let rs = quote! {
+        let p = ffi::geom::PointU { x: 1, y: 2 };
+        assert_eq!(p.sum(2), 6);
+        let p2 = p.doubled();
+        assert_eq!(p2.y, 4);
+        let mut p = Box::pin(p);
+        p.as_mut().set_x(5);
+        assert_eq!(p.x, 5);
+        assert_eq!(ffi::geom::PointU::origin().x, 0);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &[],
+        &["geom::PointU"],
+        Some(quote! { instantiate!("geom::Point<uint32_t>", "geom::PointU") }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_instantiate_namespaced() {
     let hdr = indoc! {"
-        namespace N {
-            struct A {
-                A() {}
-                struct B {
-                    B() {}
-                };
-            };
+        #include <cstdint>
+        namespace geom {
+        template<typename T>
+        struct Point {
+            T x;
+            T y;
         };
-        void take_A_B(N::A::B);
+        inline uint32_t manhattan(Point<uint32_t> p) { return p.x + p.y; }
+        }
     "};
-    let rs = quote! {};
-    run_test("", hdr, rs, &["take_A_B"], &[]);
+    let rs = quote! {
+        let p = ffi::geom::PointU { x: 1, y: 2 };
+        assert_eq!(ffi::geom::manhattan(p), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["geom::manhattan"],
+        &["geom::PointU"],
+        Some(quote! { instantiate!("geom::Point<uint32_t>", "geom::PointU") }),
+        &[],
+        None,
+    );
 }
 
 #[test]
-fn test_generic_type() {
+fn test_instantiate_with_generate_all() {
     let hdr = indoc! {"
         #include <cstdint>
-        #include <string>
-        template<typename TY>
-        struct Container {
-            Container(TY a_) : a(a_) {}
-            TY a;
-        };
-        struct Secondary {
-            Secondary() {}
-            void take_a(const Container<char>) const {}
-            void take_b(const Container<uint16_t>) const {}
-            uint16_t take_c(std::string a) const { return 10 + a.size(); }
+        template<typename T>
+        struct Point {
+            T x;
+            T y;
         };
+        inline uint32_t manhattan(Point<uint32_t> p) { return p.x + p.y; }
     "};
     let rs = quote! {
-        use ffi::ToCppString;
-        let item = ffi::Secondary::make_unique();
-        assert_eq!(item.take_c("hello".into_cpp()), 15)
+        assert_eq!(ffi::PointU::SIZE, 8);
     };
-    run_test("", hdr, rs, &["Secondary"], &[]);
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &[],
+        &[],
+        Some(quote! {
+            generate_all!()
+            instantiate!("Point<uint32_t>", "PointU")
+        }),
+        &[],
+        None,
+    );
 }
 
 #[test]
@@ -3854,7 +5072,6 @@ fn test_const_virtual_fns() {
 }
 
 #[test]
-#[ignore] // https://github.com/google/autocxx/issues/197
 fn test_virtual_fns_inheritance() {
     let hdr = indoc! {"
         #include <cstdint>
@@ -3878,6 +5095,120 @@ fn test_virtual_fns_inheritance() {
     run_test("", hdr, rs, &["B"], &[]);
 }
 
+#[test]
+fn test_inheritance_upcast() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class A {
+        public:
+            A() : a(1) {}
+            uint32_t get_a() const { return a; }
+            uint32_t describe() const { return 1; }
+            uint32_t a;
+        };
+        class B : public A {
+        public:
+            B() : b(2) {}
+            uint32_t get_b() const { return b; }
+            uint32_t describe() const { return 2; }
+            void set_a(uint32_t val) { a = val; }
+            uint32_t b;
+        };
+        class C : public B {
+        public:
+            C() : c(3) {}
+            uint32_t c;
+        };
+        inline uint32_t take_a(const A& a) { return a.describe() + a.get_a(); }
+        inline void increment_a(A& a) { a.a++; }
+    "};
+    let rs = quote! {
+        let mut c = ffi::C::make_unique();
+        // Methods of both bases are available, with B's hiding A's.
+        assert_eq!(c.get_a(), 1);
+        assert_eq!(c.get_b(), 2);
+        assert_eq!(c.describe(), 2);
+        c.pin_mut().set_a(4);
+        // Upcasts to any base.
+        assert_eq!(ffi::take_a(c.as_A()), 5);
+        assert_eq!(c.as_B().describe(), 2);
+        assert_eq!(c.as_A().describe(), 1);
+        ffi::increment_a(c.pin_mut().as_A_mut());
+        let a: &ffi::A = c.as_ref().unwrap().as_ref();
+        assert_eq!(a.get_a(), 5);
+    };
+    run_test("", hdr, rs, &["A", "B", "C", "take_a", "increment_a"], &[]);
+}
+
+#[test]
+fn test_default_args() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline uint32_t add(uint32_t a, uint32_t b = 2, uint32_t c = 3) { return a + b + c; }
+        class Adder {
+        public:
+            Adder(uint32_t start = 10) : total(start) {}
+            uint32_t increment(uint32_t val = 1) { total += val; return total; }
+            uint32_t total;
+        };
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::add(1, 1, 1), 3);
+        assert_eq!(ffi::add_with_b(1, 1), 5);
+        assert_eq!(ffi::add_with_defaults(1), 6);
+        let mut adder = ffi::Adder::make_unique_with_defaults();
+        assert_eq!(adder.pin_mut().increment_with_defaults(), 11);
+        assert_eq!(adder.pin_mut().increment(4), 15);
+        let mut adder = ffi::Adder::make_unique(5);
+        assert_eq!(adder.pin_mut().increment(0), 5);
+    };
+    run_test("", hdr, rs, &["add", "Adder"], &[]);
+}
+
+#[test]
+fn test_downcast() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Node {
+        public:
+            virtual ~Node() {}
+            virtual uint32_t kind() const { return 0; }
+            uint32_t id = 1;
+        };
+        class Leaf : public Node {
+        public:
+            uint32_t kind() const override { return 1; }
+            uint32_t value = 2;
+        };
+        class Branch : public Node {
+        public:
+            uint32_t kind() const override { return 2; }
+        };
+        inline void set_value(Leaf& leaf, uint32_t value) { leaf.value = value; }
+        inline uint32_t get_value(const Leaf& leaf) { return leaf.value; }
+    "};
+    let rs = quote! {
+        let mut leaf = ffi::Leaf::make_unique();
+        let node: &ffi::Node = leaf.as_Node();
+        assert_eq!(node.kind(), 1);
+        assert!(node.downcast_ref::<ffi::Branch>().is_none());
+        let downcast = node.downcast_ref::<ffi::Leaf>().unwrap();
+        assert_eq!(ffi::get_value(downcast), 2);
+        let node = leaf.pin_mut().as_Node_mut();
+        ffi::set_value(node.downcast_mut::<ffi::Leaf>().unwrap(), 3);
+        assert_eq!(ffi::get_value(&leaf), 3);
+        let branch = ffi::Branch::make_unique();
+        assert!(branch.as_Node().downcast_ref::<ffi::Leaf>().is_none());
+    };
+    run_test(
+        "",
+        hdr,
+        rs,
+        &["Node", "Leaf", "Branch", "set_value", "get_value"],
+        &[],
+    );
+}
+
 #[test]
 fn test_vector_cycle_up() {
     let hdr = indoc! {"
@@ -4354,7 +5685,110 @@ fn test_take_array() {
 }
 
 #[test]
-fn test_union_ignored() {
+fn test_union_pod() {
+    let cxx = indoc! {"
+        A make_a() {
+            A a;
+            a.a = 3;
+            return a;
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        union A {
+            uint32_t a;
+            float b;
+        };
+        A make_a();
+    "};
+    let rs = quote! {
+        let mut a = ffi::make_a();
+        assert_eq!(unsafe { a.a }, 3);
+        a.b = 1.5;
+        assert_eq!(unsafe { a.b }, 1.5);
+    };
+    run_test(cxx, hdr, rs, &["make_a"], &["A"]);
+}
+
+#[test]
+fn test_union_in_pod_struct() {
+    let cxx = indoc! {"
+        B make_b() {
+            B b;
+            b.tag = 1;
+            b.val.b = 2.0f;
+            return b;
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        union A {
+            uint32_t a;
+            float b;
+        };
+        struct B {
+            uint32_t tag;
+            A val;
+        };
+        B make_b();
+    "};
+    let rs = quote! {
+        let b = ffi::make_b();
+        assert_eq!(b.tag, 1);
+        assert_eq!(unsafe { b.val.b }, 2.0);
+    };
+    run_test(cxx, hdr, rs, &["make_b"], &["B"]);
+}
+
+#[test]
+fn test_union_with_struct_member() {
+    let cxx = indoc! {"
+        A make_a() {
+            A a;
+            a.b.x = 4;
+            return a;
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct B {
+            uint32_t x;
+        };
+        union A {
+            uint32_t a;
+            B b;
+        };
+        A make_a();
+    "};
+    let rs = quote! {
+        let a = ffi::make_a();
+        assert_eq!(unsafe { a.b.x }, 4);
+    };
+    run_test(cxx, hdr, rs, &["make_a"], &["A"]);
+}
+
+#[test]
+fn test_union_nontrivial() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        union A {
+            uint32_t a;
+            std::string b;
+            A() : a(3) {}
+            ~A() {}
+            uint32_t get_a() const { return a; }
+        };
+    "};
+    let rs = quote! {
+        let a = ffi::A::make_unique();
+        assert_eq!(a.get_a(), 3);
+    };
+    run_test("", hdr, rs, &["A"], &[]);
+}
+
+#[test]
+fn test_union_nonpod() {
     let hdr = indoc! {"
     #include <cstdint>
     union A {
@@ -4377,6 +5811,102 @@ fn test_union_ignored() {
     run_test("", hdr, rs, &["B"], &[]);
 }
 
+#[test]
+fn test_send_sync() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <atomic>
+        namespace ns {
+            class Service {
+            public:
+                Service() : calls(0) {}
+                uint32_t call() { return ++calls; }
+            private:
+                std::atomic<uint32_t> calls;
+            };
+        }
+    "};
+    let rs = quote! {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<ffi::ns::Service>();
+        let mut service = ffi::ns::Service::make_unique();
+        let handle = std::thread::spawn(move || service.pin_mut().call());
+        assert_eq!(handle.join().unwrap(), 1);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["ns::Service"],
+        &[],
+        Some(quote! {
+            send!(unsafe "ns::Service")
+            sync!(unsafe "ns::Service")
+        }),
+        &[],
+        None,
+    );
+}
+
+#[test]
+fn test_storage() {
+    let cxx = indoc! {"
+        uint32_t destroyed = 0;
+        A::A(uint32_t a) : a(a), b(\"hello\") {}
+        A::~A() { destroyed++; }
+        uint32_t get_destroyed() { return destroyed; }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        class A {
+        public:
+            A(uint32_t a);
+            ~A();
+            uint32_t get_a() const { return a; }
+        private:
+            uint32_t a;
+            std::string b;
+        };
+        uint32_t get_destroyed();
+    "};
+    let rs = quote! {
+        struct Holder {
+            a: ffi::AStorage,
+            _b: u32,
+        }
+        assert!(ffi::A::SIZE >= std::mem::size_of::<u32>());
+        assert!(ffi::A::ALIGN >= std::mem::align_of::<u32>());
+        assert_eq!(std::mem::size_of::<ffi::A>(), ffi::A::SIZE);
+        assert_eq!(std::mem::align_of::<ffi::A>(), ffi::A::ALIGN);
+        assert!(std::mem::size_of::<ffi::AStorage>() > ffi::A::SIZE);
+        let mut holder = Box::pin(Holder {
+            a: ffi::AStorage::new(),
+            _b: 2,
+        });
+        let mut storage = unsafe { holder.as_mut().map_unchecked_mut(|h| &mut h.a) };
+        assert!(!storage.is_initialized());
+        let a = unsafe { storage.as_mut().emplace(|this| ffi::A::emplace(this, 3)) };
+        assert_eq!(a.get_a(), 3);
+        assert_eq!(storage.get().unwrap().get_a(), 3);
+        unsafe { storage.as_mut().emplace(|this| ffi::A::emplace(this, 4)) };
+        assert_eq!(storage.get().unwrap().get_a(), 4);
+        assert_eq!(ffi::get_destroyed(), 1);
+        drop(holder);
+        assert_eq!(ffi::get_destroyed(), 2);
+    };
+    run_test_ex(
+        cxx,
+        hdr,
+        rs,
+        &["A", "get_destroyed"],
+        &[],
+        Some(quote! { storage!("A") }),
+        &[],
+        None,
+    );
+}
+
 #[test]
 fn test_double_underscores_ignored() {
     let hdr = indoc! {"
@@ -4437,6 +5967,47 @@ fn test_typedef_to_ptr_is_marked_unsafe() {
     run_test("", hdr, rs, &["duplocalefoo"], &[]);
 }
 
+#[test]
+fn test_safety_overrides() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <cstring>
+        namespace ns {
+            struct Widget {
+                Widget() : a(1) {}
+                uint32_t get() const { return a; }
+                uint32_t a;
+            };
+            inline uint32_t add(uint32_t a, uint32_t b) { return a + b; }
+            inline void raw_copy(Widget& dest, const Widget& src) {
+                memcpy(&dest, &src, sizeof(Widget));
+            }
+        }
+    "};
+    let rs = quote! {
+        let w = ffi::ns::Widget::make_unique();
+        assert_eq!(w.get(), 1);
+        assert_eq!(unsafe { ffi::ns::add(1, 2) }, 3);
+        let mut w2 = ffi::ns::Widget::make_unique();
+        unsafe { ffi::ns::raw_copy(w2.pin_mut(), &w) };
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["ns::Widget", "ns::add", "ns::raw_copy"],
+        &[],
+        Some(quote! {
+            unsafe_fn!("ns")
+            safe!(unsafe "ns::Widget")
+        }),
+        &[],
+        Some(make_string_finder(
+            ["unsafe fn add", "unsafe fn raw_copy"].to_vec(),
+        )),
+    );
+}
+
 #[test]
 fn test_issue_264() {
     let hdr = indoc! {"
@@ -4926,6 +6497,49 @@ fn test_doc_passthru() {
     );
 }
 
+#[test]
+fn test_doc_doxygen() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        /// \\brief A calculator.
+        class Calculator {
+        public:
+            /// Makes a calculator.
+            /// @param start the initial value
+            Calculator(uint32_t start) : value(start) {}
+            /// Adds two numbers to the initial value.
+            /// @param[in] a the first number
+            /// @param b the second number
+            /// @return the total
+            uint32_t add(uint32_t a, uint32_t b) const { return value + a + b; }
+        private:
+            uint32_t value;
+        };
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        &["Calculator"],
+        &[],
+        None,
+        &[],
+        Some(make_string_finder(
+            [
+                "A calculator.",
+                "Makes a calculator.",
+                "# Parameters",
+                "* `start`: the initial value",
+                "* `a` (in): the first number",
+                "# Returns",
+                "C++ signature: `uint32_t Calculator::add(uint32_t a, uint32_t b) const`",
+            ]
+            .to_vec(),
+        )),
+    );
+}
+
 #[test]
 fn test_closure() {
     // Ensuring presence of this closure doesn't break other things
@@ -5329,8 +6943,6 @@ fn test_manual_bridge() {
 // - Out param pointers
 // - ExcludeUtilities
 // - Struct fields which are typedefs
-// Stuff which requires much more thought:
-// - Shared pointers
 // Negative tests:
 // - Private methods
 // - Private fields
@@ -149,12 +149,14 @@ impl TypeDatabase {
     /// unique_ptr, bindgen would normally give us std_unique_ptr
     /// as opposed to std_unique_ptr<T>.)
     pub(crate) fn get_prelude(&self) -> String {
-        itertools::join(
-            self.by_rs_name
-                .values()
-                .filter_map(|t| t.get_prelude_entry()),
-            "\n",
-        )
+        // Sorted, so that bindgen sees the same header each time.
+        let mut entries: Vec<_> = self
+            .by_rs_name
+            .values()
+            .filter_map(|t| t.get_prelude_entry())
+            .collect();
+        entries.sort();
+        entries.join("\n")
     }
 
     /// Types which are known to be safe (or unsafe) to hold and pass by
@@ -229,6 +231,27 @@ impl TypeDatabase {
             .unwrap_or(false)
     }
 
+    /// Whether this is one of our newtype wrappers around a variable
+    /// length C integer, such as `c_int`.
+    pub(crate) fn is_c_integer_newtype(&self, ty: &QualifiedName) -> bool {
+        self.get(ty)
+            .map(|td| matches!(td.behavior, Behavior::CVariableLengthByValue))
+            .unwrap_or(false)
+    }
+
+    /// Whether this is a primitive type, as found in bindgen output,
+    /// and is therefore `Copy`.
+    pub(crate) fn is_primitive(&self, ty: &QualifiedName) -> bool {
+        self.get(ty)
+            .map(|td| {
+                matches!(
+                    td.behavior,
+                    Behavior::CByValue | Behavior::CVariableLengthByValue
+                )
+            })
+            .unwrap_or(false)
+    }
+
     /// Whether this is a generic type acceptable to cxx. Otherwise,
     /// if we encounter a generic, we'll replace it with a synthesized concrete
     /// type.
@@ -285,6 +308,12 @@ fn create_type_database() -> TypeDatabase {
         Behavior::CxxContainerByValueSafe,
         None,
     ));
+    db.insert(TypeDetails::new(
+        "cxx::WeakPtr",
+        "std::weak_ptr",
+        Behavior::CxxContainerByValueSafe,
+        None,
+    ));
     db.insert(TypeDetails::new(
         "cxx::CxxString",
         "std::string",
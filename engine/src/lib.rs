@@ -16,6 +16,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod clang_fn_info;
+mod clang_macro_consts;
 mod conversion;
 mod cxxbridge;
 mod known_types;
@@ -30,8 +32,10 @@ mod builder;
 #[cfg(test)]
 mod integration_tests;
 
-use autocxx_parser::{IncludeCppConfig, UnsafePolicy};
-use conversion::BridgeConverter;
+use autocxx_parser::{IncludeCppConfig, Instantiation, UnsafePolicy};
+use clang_fn_info::{ClangFnInfo, InstantiatedMethod};
+use clang_macro_consts::add_macro_consts;
+use conversion::{macro_fn_definition, BridgeConverter};
 use parse_callbacks::AutocxxParseCallbacks;
 use parse_file::CppBuildable;
 use proc_macro2::TokenStream as TokenStream2;
@@ -65,8 +69,12 @@ use log::info;
 use autocxx_bindgen as bindgen;
 
 #[cfg(any(test, feature = "build"))]
-pub use builder::{build, expect_build, BuilderBuild, BuilderError, BuilderResult, BuilderSuccess};
+pub use builder::{
+    build, build_with_summary, expect_build, BuilderBuild, BuilderError, BuilderResult,
+    BuilderSuccess,
+};
 pub use parse_file::{parse_file, ParseError, ParsedFile};
+pub use rust_pretty_printer::pretty_print;
 
 pub use cxx_gen::HEADER;
 
@@ -127,6 +135,7 @@ struct GenerationResults {
     item_mod: ItemMod,
     cpp: Option<CppFilePair>,
     inc_dirs: Vec<PathBuf>,
+    summary: String,
 }
 enum State {
     NotGenerated,
@@ -289,7 +298,8 @@ impl IncludeCppEngine {
             })
             .enable_cxx_namespaces()
             .generate_inline_functions(true)
-            .layout_tests(false); // TODO revisit later
+            .enable_function_attribute_detection() // for [[nodiscard]]
+            .layout_tests(true); // we parse these to find type layouts
         for item in known_types().get_initial_blocklist() {
             builder = builder.blocklist_item(item);
         }
@@ -324,6 +334,12 @@ impl IncludeCppEngine {
         format!("{}.rs", id)
     }
 
+    /// The name of the file to which we write the summary of these
+    /// bindings, named after their mod.
+    pub fn get_summary_filename(&self) -> String {
+        format!("{}.md", self.config.get_mod_name())
+    }
+
     /// Generate the Rust bindings. Call `generate` first.
     pub fn generate_rs(&self) -> TokenStream2 {
         match &self.state {
@@ -333,6 +349,17 @@ impl IncludeCppEngine {
         }
     }
 
+    /// Generate a Markdown document describing the bindings: the types,
+    /// methods and functions in each C++ namespace, and anything which
+    /// couldn't be bound, and why. Call `generate` first.
+    pub fn generate_summary(&self) -> String {
+        match &self.state {
+            State::NotGenerated => panic!("Generate first"),
+            State::Generated(gen_results) => gen_results.summary.clone(),
+            State::ParseOnly => String::new(),
+        }
+    }
+
     /// Returns the name of the mod which this `include_cpp!` will generate.
     /// Can and should be used to ensure multiple mods in a file don't conflict.
     pub fn get_mod_name(&self) -> String {
@@ -378,16 +405,77 @@ impl IncludeCppEngine {
         }
         let header_contents = self.build_header();
         self.dump_header_if_so_configured(&header_contents, &inc_dirs, &extra_clang_args);
-        let header_and_prelude = format!("{}\n\n{}", known_types().get_prelude(), header_contents);
-        builder = builder.header_contents("example.hpp", &header_and_prelude);
+        let macro_fns = self
+            .config
+            .get_macro_fns()
+            .iter()
+            .map(macro_fn_definition)
+            .join("\n");
+        let instantiations = self
+            .config
+            .get_instantiations()
+            .iter()
+            .map(Instantiation::typedef)
+            .join("\n");
+        let header_and_prelude = format!(
+            "{}\n\n{}\n{}\n{}",
+            known_types().get_prelude(),
+            header_contents,
+            macro_fns,
+            instantiations
+        );
+        let header_name = "example.hpp";
+        let clang_args: Vec<_> = make_clang_args(&inc_dirs, extra_clang_args).collect();
+        // bindgen doesn't tell us about the methods of templates, so we
+        // declare functions for it which stand in for the methods of any
+        // instantiations.
+        let instantiated_methods = InstantiatedMethod::find_all(
+            &header_and_prelude,
+            header_name,
+            &clang_args,
+            self.config.get_instantiations(),
+        );
+        let header_and_prelude = format!(
+            "{}\n{}",
+            header_and_prelude,
+            instantiated_methods
+                .iter()
+                .map(InstantiatedMethod::declaration)
+                .join("\n")
+        );
+        if self.config.bindgen_allowlist().is_some() {
+            for method in &instantiated_methods {
+                builder = builder.allowlist_function(method.declaration_name());
+            }
+        }
+        builder = builder.header_contents(header_name, &header_and_prelude);
 
         let bindings = builder.generate().map_err(Error::Bindgen)?;
-        let bindings = self.parse_bindings(bindings)?;
+        let mut bindings = self.parse_bindings(bindings)?;
+        add_macro_consts(
+            &mut bindings,
+            &header_and_prelude,
+            header_name,
+            &clang_args,
+            &self.config,
+        );
+        let fn_info = ClangFnInfo::find(
+            &bindings,
+            &header_and_prelude,
+            header_name,
+            &clang_args,
+            instantiated_methods,
+        );
 
         let converter = BridgeConverter::new(&self.config.inclusions, &self.config);
 
         let conversion = converter
-            .convert(bindings, self.config.unsafe_policy.clone(), header_contents)
+            .convert(
+                bindings,
+                self.config.unsafe_policy.clone(),
+                header_contents,
+                &fn_info,
+            )
             .map_err(Error::Conversion)?;
         let mut items = conversion.rs;
         let mut new_bindings: ItemMod = parse_quote! {
@@ -407,6 +495,7 @@ impl IncludeCppEngine {
             item_mod: new_bindings,
             cpp: conversion.cpp,
             inc_dirs,
+            summary: conversion.summary,
         }));
         Ok(())
     }
@@ -21,9 +21,13 @@ enum Error {
     Write(std::io::Error),
     Utf8(std::string::FromUtf8Error),
     Wait(std::io::Error),
+    Failed,
 }
 
-pub(crate) fn pretty_print(ts: &TokenStream) -> String {
+/// Formats some Rust code using `rustfmt`, so that it's readable, and so
+/// that differences between two versions are meaningful. If `rustfmt`
+/// isn't available, or fails, returns the code unformatted.
+pub fn pretty_print(ts: &TokenStream) -> String {
     reformat_or_else(ts.to_string())
 }
 
@@ -36,11 +40,16 @@ fn reformat_or_else(text: impl std::fmt::Display) -> String {
 
 fn reformat(text: impl std::fmt::Display) -> Result<String, Error> {
     let mut rustfmt = Command::new("rustfmt")
+        .arg("--edition")
+        .arg("2018")
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
         .map_err(Error::Run)?;
     write!(rustfmt.stdin.take().unwrap(), "{}", text).map_err(Error::Write)?;
     let output = rustfmt.wait_with_output().map_err(Error::Wait)?;
+    if !output.status.success() {
+        return Err(Error::Failed);
+    }
     String::from_utf8(output.stdout).map_err(Error::Utf8)
 }
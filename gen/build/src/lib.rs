@@ -13,8 +13,8 @@
 // limitations under the License.
 
 use autocxx_engine::{
-    build as engine_build, expect_build as engine_expect_build, BuilderBuild, BuilderError,
-    RebuildDependencyRecorder,
+    build as engine_build, build_with_summary as engine_build_with_summary,
+    expect_build as engine_expect_build, BuilderBuild, BuilderError, RebuildDependencyRecorder,
 };
 use std::{collections::HashSet, io::Write, sync::Mutex};
 use std::{ffi::OsStr, path::Path};
@@ -43,6 +43,32 @@ where
     .map(|r| r.0)
 }
 
+/// Like [build], but also writes a Markdown summary of the bindings
+/// generated by each `include_cpp!` into the given directory, named after
+/// its mod, for example `ffi.md`.
+pub fn build_with_summary<P1, P2, I, T>(
+    rs_file: P1,
+    autocxx_incs: I,
+    extra_clang_args: &[&str],
+    summary_dir: P2,
+) -> Result<BuilderBuild, BuilderError>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+    I: IntoIterator<Item = T>,
+    T: AsRef<OsStr>,
+{
+    setup_logging();
+    engine_build_with_summary(
+        rs_file,
+        autocxx_incs,
+        extra_clang_args,
+        summary_dir,
+        Some(Box::new(CargoRebuildDependencyRecorder::new())),
+    )
+    .map(|r| r.0)
+}
+
 /// Builds successfully, or exits the process displaying a suitable
 /// message.
 pub fn expect_build<P1, I, T>(
@@ -79,6 +79,81 @@ fn test_gen_fixed_num() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn test_gen_summary() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    let demo_code_dir = tmp_dir.path().join("demo");
+    std::fs::create_dir(&demo_code_dir).unwrap();
+    write_to_file(&demo_code_dir, "input.h", INPUT_H.as_bytes());
+    write_to_file(&demo_code_dir, "main.rs", MAIN_RS.as_bytes());
+    let demo_rs = demo_code_dir.join("main.rs");
+    let summary_path = tmp_dir.path().join("summary.md");
+    let mut cmd = Command::cargo_bin("autocxx-gen")?;
+    cmd.arg("-I")
+        .arg(demo_code_dir.to_str().unwrap())
+        .arg(demo_rs)
+        .arg("--outdir")
+        .arg(tmp_dir.path().to_str().unwrap())
+        .arg("--summary")
+        .arg(summary_path.to_str().unwrap())
+        .assert()
+        .success();
+    let summary = std::fs::read_to_string(summary_path)?;
+    assert!(summary.contains("# Bindings in `ffi`"));
+    assert!(summary.contains("DoMath"));
+    Ok(())
+}
+
+#[test]
+fn test_gen_summary_needs_file_name() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    let demo_code_dir = tmp_dir.path().join("demo");
+    std::fs::create_dir(&demo_code_dir).unwrap();
+    write_to_file(&demo_code_dir, "input.h", INPUT_H.as_bytes());
+    write_to_file(&demo_code_dir, "main.rs", MAIN_RS.as_bytes());
+    let demo_rs = demo_code_dir.join("main.rs");
+    let mut cmd = Command::cargo_bin("autocxx-gen")?;
+    cmd.arg("-I")
+        .arg(demo_code_dir.to_str().unwrap())
+        .arg(demo_rs)
+        .arg("--outdir")
+        .arg(tmp_dir.path().to_str().unwrap())
+        .arg("--summary")
+        .arg("..")
+        .assert()
+        .failure();
+    Ok(())
+}
+
+#[test]
+fn test_gen_rs_stable() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    let demo_code_dir = tmp_dir.path().join("demo");
+    std::fs::create_dir(&demo_code_dir).unwrap();
+    write_to_file(&demo_code_dir, "input.h", INPUT_H.as_bytes());
+    write_to_file(&demo_code_dir, "main.rs", MAIN_RS.as_bytes());
+    let demo_rs = demo_code_dir.join("main.rs");
+    let mut outputs = Vec::new();
+    for outdir_name in &["out1", "out2"] {
+        let outdir = tmp_dir.path().join(outdir_name);
+        std::fs::create_dir(&outdir).unwrap();
+        let mut cmd = Command::cargo_bin("autocxx-gen")?;
+        cmd.arg("-I")
+            .arg(demo_code_dir.to_str().unwrap())
+            .arg(&demo_rs)
+            .arg("--outdir")
+            .arg(outdir.to_str().unwrap())
+            .arg("--gen-rs-include")
+            .arg("--fix-rs-include-name")
+            .assert()
+            .success();
+        outputs.push(std::fs::read_to_string(outdir.join("gen0.include.rs"))?);
+    }
+    assert_eq!(outputs[0], outputs[1]);
+    assert!(outputs[0].lines().count() > 1);
+    Ok(())
+}
+
 fn write_to_file(dir: &Path, filename: &str, content: &[u8]) {
     let path = dir.join(filename);
     let mut f = File::create(&path).expect("Unable to create file");
@@ -15,11 +15,12 @@
 #[cfg(test)]
 mod cmd_test;
 
-use autocxx_engine::parse_file;
+use autocxx_engine::{parse_file, pretty_print};
 use clap::{crate_authors, crate_version, App, Arg, ArgGroup};
 use indoc::indoc;
 use proc_macro2::TokenStream;
 use quote::ToTokens;
+use std::ffi::OsString;
 use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::{fs::File, path::Path};
@@ -40,7 +41,10 @@ that's you, read on.
 This tool has three modes: generate the C++; generate a new Rust file where
 the include_cpp! directive is *replaced* with bindings, or generate
 a Rust file which can be included by the autocxx_macro. You may specify
-multiple modes, or of course, invoke the tool multiple times.
+multiple modes, or of course, invoke the tool multiple times. You can also
+use --summary to write a Markdown description of the bindings, listing
+each C++ type and function and anything which couldn't be bound, which
+may be useful when reviewing changes to the C++ headers.
 
 In any mode, you'll need to pass the source Rust file name and the C++
 include path.
@@ -126,12 +130,24 @@ fn main() {
                 .long("gen-rs-include")
                 .help("whether to generate Rust files for inclusion using autocxx_macro (suffix will be .include.rs)")
         )
+        .arg(
+            Arg::with_name("summary")
+                .long("summary")
+                .value_name("PATH")
+                .help("write a Markdown summary of the generated bindings to this file")
+                .takes_value(true)
+                .validator_os(|path| match Path::new(path).file_name() {
+                    Some(_) => Ok(()),
+                    None => Err(OsString::from("must be a file, not a directory")),
+                }),
+        )
         .group(ArgGroup::with_name("mode")
             .required(true)
             .multiple(true)
             .arg("gen-cpp")
             .arg("gen-rs-complete")
             .arg("gen-rs-include")
+            .arg("summary")
         )
         .arg(
             Arg::with_name("cxx-gen")
@@ -204,7 +220,7 @@ fn main() {
         write_to_file(
             &outdir,
             "gen.complete.rs".to_string(),
-            ts.to_string().as_bytes(),
+            pretty_print(&ts).as_bytes(),
         );
     }
     if matches.is_present("gen-rs-include") {
@@ -217,11 +233,29 @@ fn main() {
             } else {
                 include_cxx.get_rs_filename()
             };
-            write_to_file(&outdir, fname, ts.to_string().as_bytes());
+            write_to_file(&outdir, fname, pretty_print(&ts).as_bytes());
             counter += 1;
         }
         write_placeholders(&outdir, counter, desired_number, "include.rs");
     }
+    if let Some(summary_path) = matches.value_of_os("summary") {
+        let summary = parsed_file
+            .get_rs_buildables()
+            .map(|include_cxx| include_cxx.generate_summary())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let summary_path = PathBuf::from(summary_path);
+        // Our validator has checked that there's a file name.
+        write_to_file(
+            summary_path.parent().unwrap_or_else(|| Path::new("")),
+            summary_path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+            summary.as_bytes(),
+        );
+    }
 }
 
 fn write_placeholders(
@@ -93,6 +93,240 @@ impl Default for Allowlist {
     }
 }
 
+/// A preprocessor macro which should be made available as a function,
+/// as requested by a `macro_fn!` directive.
+#[derive(Hash, Debug, PartialEq)]
+pub struct MacroFn {
+    /// The name of the macro.
+    pub name: String,
+    /// The C++ return type. If `None`, this is deduced from the
+    /// expression to which an object-like macro expands.
+    pub return_type: Option<String>,
+    /// The C++ parameter types. If `None`, this is an object-like
+    /// macro which should not be invoked with parentheses.
+    pub param_types: Option<Vec<String>>,
+}
+
+impl MacroFn {
+    fn new(name: LitStr, signature: Option<LitStr>) -> ParseResult<Self> {
+        let (return_type, param_types) = match signature {
+            None => (None, None),
+            Some(signature) => {
+                let (return_type, param_types) =
+                    parse_macro_fn_signature(&signature.value()).ok_or_else(|| {
+                        syn::Error::new(
+                            signature.span(),
+                            "expected a C++ function signature such as \"int(int, double)\", or a type such as \"double\"",
+                        )
+                    })?;
+                (Some(return_type), param_types)
+            }
+        };
+        Ok(MacroFn {
+            name: name.value(),
+            return_type,
+            param_types,
+        })
+    }
+
+    /// The name of the C++ function we generate to evaluate this macro.
+    /// It can't be the name of the macro itself, since that would
+    /// be expanded by the preprocessor.
+    pub fn wrapper_name(&self) -> String {
+        format!("autocxx_macro_{}", self.name)
+    }
+}
+
+/// Splits a signature such as `int(int, std::map<int, int>)` into its
+/// return type and parameter types. A signature without parentheses
+/// is just a type, which is returned without any parameter types.
+fn parse_macro_fn_signature(signature: &str) -> Option<(String, Option<Vec<String>>)> {
+    let signature = signature.trim();
+    let open = match signature.find('(') {
+        None if !signature.is_empty() => return Some((signature.to_string(), None)),
+        None => return None,
+        Some(open) => open,
+    };
+    let return_type = signature[..open].trim();
+    if return_type.is_empty() {
+        return None;
+    }
+    let param_types = parse_param_types(&signature[open + 1..])?;
+    Some((return_type.to_string(), Some(param_types)))
+}
+
+/// Splits a parameter list such as `int, std::map<int, int>)` (that is,
+/// everything after the opening parenthesis) into its types.
+fn parse_param_types(params: &str) -> Option<Vec<String>> {
+    let params = params.strip_suffix(')')?.trim();
+    let mut param_types = Vec::new();
+    if !params.is_empty() && params != "void" {
+        let mut depth = 0;
+        let mut current = String::new();
+        for c in params.chars() {
+            match c {
+                '<' | '(' => depth += 1,
+                '>' | ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    param_types.push(std::mem::take(&mut current));
+                    continue;
+                }
+                _ => {}
+            }
+            current.push(c);
+        }
+        param_types.push(current);
+    }
+    let param_types: Vec<String> = param_types.iter().map(|t| t.trim().to_string()).collect();
+    if param_types.iter().any(String::is_empty) {
+        return None;
+    }
+    Some(param_types)
+}
+
+/// Reduces a C++ type to a canonical spelling for comparison purposes,
+/// without insignificant whitespace or namespace qualifiers.
+fn normalize_cpp_type(ty: &str) -> String {
+    let mut result = String::new();
+    let mut pending_space = false;
+    for c in ty.chars() {
+        if c.is_whitespace() {
+            pending_space = true;
+            continue;
+        }
+        let is_ident_char = c.is_alphanumeric() || c == '_';
+        let follows_ident_char =
+            matches!(result.chars().last(), Some(last) if last.is_alphanumeric() || last == '_');
+        if pending_space && is_ident_char && follows_ident_char {
+            result.push(' ');
+        }
+        pending_space = false;
+        result.push(c);
+        if result.ends_with("::") {
+            // Drop the qualifier, which is the identifier before the '::'.
+            result.truncate(result.len() - 2);
+            let qualifier_start = result
+                .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .map_or(0, |pos| pos + 1);
+            result.truncate(qualifier_start);
+        }
+    }
+    result
+}
+
+/// A Rust name to use for a given C++ type, function or method, as
+/// requested by a `rename!` directive.
+#[derive(Hash, Debug, PartialEq)]
+pub struct Rename {
+    /// The qualified C++ name, e.g. `ns::Type`, `ns::foo` or `ns::Type::method`.
+    pub cpp_name: String,
+    /// The C++ parameter types, to select one of several overloads.
+    /// If `None`, this applies to all overloads.
+    pub param_types: Option<Vec<String>>,
+    /// The name to use in Rust.
+    pub rust_name: String,
+}
+
+impl Rename {
+    fn new(cpp_name: LitStr, rust_name: LitStr) -> ParseResult<Self> {
+        let full_name = cpp_name.value();
+        let (cpp_name, param_types) = match full_name.find('(') {
+            None => (full_name.trim().to_string(), None),
+            Some(open) => {
+                let param_types = parse_param_types(&full_name[open + 1..]).ok_or_else(|| {
+                    syn::Error::new(
+                        cpp_name.span(),
+                        "expected a C++ name, optionally with parameter types such as \"ns::foo(int, double)\"",
+                    )
+                })?;
+                (full_name[..open].trim().to_string(), Some(param_types))
+            }
+        };
+        Ok(Rename {
+            cpp_name,
+            param_types,
+            rust_name: rust_name.value(),
+        })
+    }
+
+    /// Whether this applies to a function of the given name and parameter
+    /// types. Types are compared ignoring whitespace and namespaces.
+    fn matches(&self, cpp_name: &str, param_types: &[String]) -> bool {
+        self.cpp_name == cpp_name
+            && match &self.param_types {
+                None => true,
+                Some(wanted) => {
+                    wanted.len() == param_types.len()
+                        && wanted.iter().zip(param_types).all(|(wanted, actual)| {
+                            normalize_cpp_type(wanted) == normalize_cpp_type(actual)
+                        })
+                }
+            }
+    }
+}
+
+/// How a raw pointer parameter should be presented in Rust, as requested
+/// by a `pointer_param!` directive.
+#[derive(Hash, Debug, PartialEq, Clone)]
+pub enum PointerPolicy {
+    /// The pointer may be null: `Option<&T>` or `Option<Pin<&mut T>>`.
+    Nullable,
+    /// The pointer refers to an array whose length is given by the named
+    /// parameter: `&[T]` or `&mut [T]`.
+    Slice { length_param: String },
+    /// The function writes its result through the pointer, so it's
+    /// returned from the Rust function instead.
+    Out,
+}
+
+impl Parse for PointerPolicy {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        let ident: syn::Ident = input.parse()?;
+        if ident == "nullable" {
+            Ok(PointerPolicy::Nullable)
+        } else if ident == "out" {
+            Ok(PointerPolicy::Out)
+        } else if ident == "slice" {
+            let args;
+            syn::parenthesized!(args in input);
+            let length_param: syn::LitStr = args.parse()?;
+            Ok(PointerPolicy::Slice {
+                length_param: length_param.value(),
+            })
+        } else {
+            Err(syn::Error::new(
+                ident.span(),
+                "expected nullable, slice(\"length_param\") or out",
+            ))
+        }
+    }
+}
+
+/// A concrete instantiation of a C++ template which should be given
+/// a name, as requested by an `instantiate!` directive.
+#[derive(Hash, Debug, PartialEq)]
+pub struct Instantiation {
+    /// The C++ type, for example `ns::Matrix<float, 4>`.
+    pub cpp_type: String,
+    /// The name by which this type should be known, in both
+    /// C++ and Rust.
+    pub name: String,
+}
+
+impl Instantiation {
+    /// The C++ typedef we generate to give this instantiation its name,
+    /// within the namespace of that name, if any.
+    pub fn typedef(&self) -> String {
+        let mut segments: Vec<_> = self.name.split("::").collect();
+        let id = segments.pop().unwrap();
+        let mut typedef = format!("typedef {} {};", self.cpp_type, id);
+        for ns in segments.into_iter().rev() {
+            typedef = format!("namespace {} {{ {} }}", ns, typedef);
+        }
+        typedef
+    }
+}
+
 #[derive(Hash, Debug)]
 pub struct IncludeCppConfig {
     pub inclusions: Vec<String>,
@@ -103,6 +337,23 @@ pub struct IncludeCppConfig {
     blocklist: Vec<String>,
     exclude_utilities: bool,
     mod_name: Option<Ident>,
+    bitflags_enums: Vec<String>,
+    newtype_enums: Vec<String>,
+    newtype_all_enums: bool,
+    macro_fns: Vec<MacroFn>,
+    storage_types: Vec<String>,
+    send_types: Vec<String>,
+    sync_types: Vec<String>,
+    instantiations: Vec<Instantiation>,
+    renames: Vec<Rename>,
+    overloads_by_signature: bool,
+    snake_case_methods: bool,
+    safety_overrides: Vec<(String, bool)>,
+    borrows_from: Vec<(String, String)>,
+    pointer_params: Vec<(String, String, PointerPolicy)>,
+    out_params: Vec<(String, String)>,
+    detect_out_params: bool,
+    out_params_as_result: Vec<String>,
 }
 
 impl Parse for IncludeCppConfig {
@@ -120,6 +371,23 @@ impl Parse for IncludeCppConfig {
         let mut pod_requests = Vec::new();
         let mut exclude_utilities = false;
         let mut mod_name = None;
+        let mut bitflags_enums = Vec::new();
+        let mut newtype_enums = Vec::new();
+        let mut newtype_all_enums = false;
+        let mut macro_fns = Vec::new();
+        let mut storage_types = Vec::new();
+        let mut send_types = Vec::new();
+        let mut sync_types = Vec::new();
+        let mut instantiations = Vec::new();
+        let mut renames = Vec::new();
+        let mut overloads_by_signature = false;
+        let mut snake_case_methods = false;
+        let mut safety_overrides = Vec::new();
+        let mut borrows_from = Vec::new();
+        let mut pointer_params = Vec::new();
+        let mut out_params = Vec::new();
+        let mut detect_out_params = false;
+        let mut out_params_as_result = Vec::new();
 
         while !input.is_empty() {
             let has_hexathorpe = input.parse::<Option<syn::Token![#]>>()?.is_some();
@@ -171,10 +439,115 @@ impl Parse for IncludeCppConfig {
                     let args;
                     syn::parenthesized!(args in input);
                     unsafe_policy = args.parse()?;
+                } else if ident == "safe" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let cpp_name = parse_unsafe_assertion(&args, &ident)?;
+                    safety_overrides.push((cpp_name.value(), true));
+                } else if ident == "unsafe_fn" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let cpp_name: syn::LitStr = args.parse()?;
+                    safety_overrides.push((cpp_name.value(), false));
+                } else if ident == "borrows_from" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let cpp_name: syn::LitStr = args.parse()?;
+                    args.parse::<syn::Token![,]>()?;
+                    let param: syn::LitStr = args.parse()?;
+                    borrows_from.push((cpp_name.value(), param.value()));
+                } else if ident == "pointer_param" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let cpp_name: syn::LitStr = args.parse()?;
+                    args.parse::<syn::Token![,]>()?;
+                    let param: syn::LitStr = args.parse()?;
+                    args.parse::<syn::Token![,]>()?;
+                    let policy: PointerPolicy = args.parse()?;
+                    pointer_params.push((cpp_name.value(), param.value(), policy));
+                } else if ident == "out_param" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let cpp_name: syn::LitStr = args.parse()?;
+                    args.parse::<syn::Token![,]>()?;
+                    let param: syn::LitStr = args.parse()?;
+                    out_params.push((cpp_name.value(), param.value()));
+                } else if ident == "detect_out_params" {
+                    detect_out_params = true;
+                    swallow_parentheses(&input, &ident)?;
+                } else if ident == "out_params_as_result" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let cpp_name: syn::LitStr = args.parse()?;
+                    out_params_as_result.push(cpp_name.value());
+                } else if ident == "bitflags" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let bitflags: syn::LitStr = args.parse()?;
+                    bitflags_enums.push(bitflags.value());
+                } else if ident == "newtype_enum" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let newtype_enum: syn::LitStr = args.parse()?;
+                    newtype_enums.push(newtype_enum.value());
+                } else if ident == "newtype_all_enums" {
+                    newtype_all_enums = true;
+                    swallow_parentheses(&input, &ident)?;
+                } else if ident == "macro_fn" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let name: syn::LitStr = args.parse()?;
+                    let signature = if args.parse::<Option<syn::Token![,]>>()?.is_some() {
+                        Some(args.parse::<syn::LitStr>()?)
+                    } else {
+                        None
+                    };
+                    macro_fns.push(MacroFn::new(name, signature)?);
+                } else if ident == "storage" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let storage: syn::LitStr = args.parse()?;
+                    storage_types.push(storage.value());
+                } else if ident == "send" || ident == "sync" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let ty = parse_unsafe_assertion(&args, &ident)?;
+                    if ident == "send" {
+                        send_types.push(ty.value());
+                    } else {
+                        sync_types.push(ty.value());
+                    }
+                } else if ident == "instantiate" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let cpp_type: syn::LitStr = args.parse()?;
+                    args.parse::<syn::Token![,]>()?;
+                    let name: syn::LitStr = args.parse()?;
+                    instantiations.push(Instantiation {
+                        cpp_type: cpp_type.value(),
+                        name: name.value(),
+                    });
+                } else if ident == "rename" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let cpp_name: syn::LitStr = args.parse()?;
+                    args.parse::<syn::Token![,]>()?;
+                    let rust_name: syn::LitStr = args.parse()?;
+                    renames.push(Rename::new(cpp_name, rust_name)?);
+                } else if ident == "overloads_by_signature" {
+                    overloads_by_signature = true;
+                    swallow_parentheses(&input, &ident)?;
+                } else if ident == "snake_case_methods" {
+                    snake_case_methods = true;
+                    swallow_parentheses(&input, &ident)?;
                 } else {
                     return Err(syn::Error::new(
                         ident.span(),
-                        "expected generate, generate_pod, nested_type, safety or exclude_utilities",
+                        format!(
+                            "expected {} or {}",
+                            DIRECTIVES[..DIRECTIVES.len() - 1].join(", "),
+                            DIRECTIVES[DIRECTIVES.len() - 1]
+                        ),
                     ));
                 }
             }
@@ -192,10 +565,59 @@ impl Parse for IncludeCppConfig {
             blocklist,
             exclude_utilities,
             mod_name,
+            bitflags_enums,
+            newtype_enums,
+            newtype_all_enums,
+            macro_fns,
+            storage_types,
+            send_types,
+            sync_types,
+            instantiations,
+            renames,
+            overloads_by_signature,
+            snake_case_methods,
+            safety_overrides,
+            borrows_from,
+            pointer_params,
+            out_params,
+            detect_out_params,
+            out_params_as_result,
         })
     }
 }
 
+/// The directives which may appear within `include_cpp!`, other than
+/// `#include`, for our error messages.
+const DIRECTIVES: &[&str] = &[
+    "generate",
+    "generate_pod",
+    "generate_all",
+    "pod",
+    "block",
+    "parse_only",
+    "name",
+    "exclude_utilities",
+    "safety",
+    "safe",
+    "unsafe_fn",
+    "borrows_from",
+    "pointer_param",
+    "out_param",
+    "detect_out_params",
+    "out_params_as_result",
+    "bitflags",
+    "newtype_enum",
+    "newtype_all_enums",
+    "macro_fn",
+    "storage",
+    "send",
+    "sync",
+    "instantiate",
+    "rename",
+    "overloads_by_signature",
+    "snake_case_methods",
+];
+
 fn swallow_parentheses(input: &ParseStream, latest_ident: &Ident) -> ParseResult<()> {
     let args;
     syn::parenthesized!(args in input);
@@ -209,6 +631,22 @@ fn swallow_parentheses(input: &ParseStream, latest_ident: &Ident) -> ParseResult
     }
 }
 
+/// Parses the argument of a directive which asserts that some C++ is
+/// safe, or thread-safe. Like an `unsafe` block or `unsafe impl`, these
+/// must be written with `unsafe`, e.g. `safe!(unsafe "ns::add")`.
+fn parse_unsafe_assertion(args: ParseStream, latest_ident: &Ident) -> ParseResult<LitStr> {
+    if args.parse::<Option<Token![unsafe]>>()?.is_none() {
+        return Err(syn::Error::new(
+            latest_ident.span(),
+            format!(
+                "{} is a safety assertion, so must be written {}!(unsafe \"...\")",
+                latest_ident, latest_ident
+            ),
+        ));
+    }
+    args.parse()
+}
+
 impl IncludeCppConfig {
     pub fn get_pod_requests(&self) -> &[String] {
         &self.pod_requests
@@ -230,28 +668,46 @@ impl IncludeCppConfig {
     /// Items which the user has explicitly asked us to generate;
     /// we should raise an error if we weren't able to do so.
     pub fn must_generate_list(&self) -> Box<dyn Iterator<Item = String> + '_> {
-        if let Allowlist::Specific(items) = &self.allowlist {
-            Box::new(items.iter().chain(self.pod_requests.iter()).cloned())
-        } else {
-            Box::new(self.pod_requests.iter().cloned())
-        }
+        Box::new(
+            self.specific_allowlist()
+                .iter()
+                .cloned()
+                .chain(self.implied_allowlist()),
+        )
     }
 
     /// The allowlist of items to be passed into bindgen, if any.
     pub fn bindgen_allowlist(&self) -> Option<Box<dyn Iterator<Item = String> + '_>> {
         match &self.allowlist {
             Allowlist::All => None,
-            Allowlist::Specific(items) => Some(Box::new(
-                items
+            _ => Some(Box::new(
+                self.specific_allowlist()
                     .iter()
-                    .chain(self.pod_requests.iter())
                     .cloned()
+                    .chain(self.implied_allowlist())
                     .chain(self.active_utilities()),
             )),
-            Allowlist::Unspecified => unreachable!(),
         }
     }
 
+    /// Items which the user has listed using `generate!`.
+    fn specific_allowlist(&self) -> &[String] {
+        match &self.allowlist {
+            Allowlist::Specific(items) => items,
+            _ => &[],
+        }
+    }
+
+    /// Items which we need to generate because of directives other than
+    /// `generate!`, whether or not the user also asked for `generate_all!`.
+    fn implied_allowlist(&self) -> impl Iterator<Item = String> + '_ {
+        self.pod_requests
+            .iter()
+            .cloned()
+            .chain(self.macro_fns.iter().map(MacroFn::wrapper_name))
+            .chain(self.instantiations.iter().map(|item| item.name.clone()))
+    }
+
     fn active_utilities(&self) -> Vec<String> {
         if self.exclude_utilities {
             Vec::new()
@@ -274,6 +730,7 @@ impl IncludeCppConfig {
             Some(mut items) => {
                 items.any(|item| item == cpp_name)
                     || self.active_utilities().iter().any(|item| *item == cpp_name)
+                    || self.macro_fns.iter().any(|item| item.name == cpp_name)
             }
         }
     }
@@ -286,6 +743,151 @@ impl IncludeCppConfig {
         self.blocklist.iter()
     }
 
+    /// Whether this enum should be represented as a newtype wrapper
+    /// around its underlying integer, with bitwise operators.
+    pub fn is_bitflags_enum(&self, cpp_name: &str) -> bool {
+        self.bitflags_enums.iter().any(|item| item == cpp_name)
+    }
+
+    /// Whether this enum should be represented as a newtype wrapper
+    /// around its underlying integer, rather than a Rust enum.
+    /// This is also true of any enum represented as bitflags.
+    pub fn is_newtype_enum(&self, cpp_name: &str) -> bool {
+        self.newtype_all_enums
+            || self.is_bitflags_enum(cpp_name)
+            || self.newtype_enums.iter().any(|item| item == cpp_name)
+    }
+
+    /// Whether this opaque type should get a storage type, so that it
+    /// can be held inline within Rust structs.
+    pub fn is_storage_type(&self, cpp_name: &str) -> bool {
+        self.storage_types.iter().any(|item| item == cpp_name)
+    }
+
+    /// Whether the user has asserted that this type may be sent
+    /// between threads.
+    pub fn is_send(&self, cpp_name: &str) -> bool {
+        self.send_types.iter().any(|item| item == cpp_name)
+    }
+
+    /// Whether the user has asserted that this type may be shared
+    /// between threads.
+    pub fn is_sync(&self, cpp_name: &str) -> bool {
+        self.sync_types.iter().any(|item| item == cpp_name)
+    }
+
+    /// Template instantiations which should be given names.
+    pub fn get_instantiations(&self) -> &[Instantiation] {
+        &self.instantiations
+    }
+
+    /// If this is the name given to a template instantiation, returns
+    /// the details of that instantiation.
+    pub fn instantiation_named(&self, name: &str) -> Option<&Instantiation> {
+        self.instantiations.iter().find(|item| item.name == name)
+    }
+
+    /// Macros which should be made available as functions.
+    pub fn get_macro_fns(&self) -> &[MacroFn] {
+        &self.macro_fns
+    }
+
+    /// If this is the name of a C++ function which we generate to
+    /// evaluate a macro, returns the details of that macro.
+    pub fn macro_fn_for_wrapper(&self, cpp_name: &str) -> Option<&MacroFn> {
+        self.macro_fns
+            .iter()
+            .find(|item| item.wrapper_name() == cpp_name)
+    }
+
+    /// The Rust name requested for a C++ function or method with the
+    /// given qualified name and parameter types, if any.
+    pub fn rename_for(&self, cpp_name: &str, param_types: &[String]) -> Option<&str> {
+        self.renames
+            .iter()
+            .find(|item| item.matches(cpp_name, param_types))
+            .map(|item| item.rust_name.as_str())
+    }
+
+    /// The Rust name requested for a C++ type, if any.
+    pub fn rename_for_type(&self, cpp_name: &str) -> Option<&str> {
+        self.renames
+            .iter()
+            .find(|item| item.param_types.is_none() && item.cpp_name == cpp_name)
+            .map(|item| item.rust_name.as_str())
+    }
+
+    /// Whether overloaded functions should be named after their
+    /// parameter types, rather than numbered in the order we find them.
+    pub fn overloads_by_signature(&self) -> bool {
+        self.overloads_by_signature
+    }
+
+    /// Whether a function should be safe or `unsafe` according to a
+    /// `safe!` or `unsafe_fn!` directive naming it, its type or any of its
+    /// enclosing namespaces. The most specific directive wins. If there's
+    /// none, returns `None` and the overall [`UnsafePolicy`] applies.
+    pub fn safety_override_for(&self, cpp_name: &str) -> Option<bool> {
+        let mut name = cpp_name;
+        loop {
+            if let Some((_, is_safe)) = self
+                .safety_overrides
+                .iter()
+                .rev()
+                .find(|(item, _)| item == name)
+            {
+                return Some(*is_safe);
+            }
+            name = &name[..name.rfind("::")?];
+        }
+    }
+
+    /// The parameter from which the reference returned by this function
+    /// borrows, as specified by a `borrows_from!` directive.
+    pub fn borrows_from(&self, cpp_name: &str) -> Option<&str> {
+        self.borrows_from
+            .iter()
+            .find(|(item, _)| item == cpp_name)
+            .map(|(_, param)| param.as_str())
+    }
+
+    /// How a raw pointer parameter of this function should be presented
+    /// in Rust, as specified by a `pointer_param!` directive.
+    pub fn pointer_policy(&self, cpp_name: &str, param: &str) -> Option<&PointerPolicy> {
+        self.pointer_params
+            .iter()
+            .find(|(item, item_param, _)| item == cpp_name && item_param == param)
+            .map(|(_, _, policy)| policy)
+    }
+
+    /// Whether this parameter of this function is one through which C++
+    /// returns a value, as specified by an `out_param!` directive.
+    pub fn is_out_param(&self, cpp_name: &str, param: &str) -> bool {
+        self.out_params
+            .iter()
+            .any(|(item, item_param)| item == cpp_name && item_param == param)
+    }
+
+    /// Whether non-const reference parameters named `out_*` should be
+    /// treated as out parameters without needing an `out_param!` directive.
+    pub fn detect_out_params(&self) -> bool {
+        self.detect_out_params
+    }
+
+    /// Whether the status returned by this function, alongside its out
+    /// parameters, should be converted into a `Result`.
+    pub fn out_params_as_result(&self, cpp_name: &str) -> bool {
+        self.out_params_as_result
+            .iter()
+            .any(|item| item == cpp_name)
+    }
+
+    /// Whether C++ `CamelCase` method names should become Rust
+    /// `snake_case` names.
+    pub fn snake_case_methods(&self) -> bool {
+        self.snake_case_methods
+    }
+
     pub fn get_makestring_name(&self) -> String {
         format!(
             "autocxx_make_string_{}",
@@ -299,7 +901,7 @@ impl IncludeCppConfig {
 
 #[cfg(test)]
 mod parse_tests {
-    use crate::config::UnsafePolicy;
+    use crate::config::{Allowlist, IncludeCppConfig, MacroFn, PointerPolicy, UnsafePolicy};
     use syn::parse_quote;
     #[test]
     fn test_safety_unsafe() {
@@ -322,4 +924,302 @@ mod parse_tests {
         let us: UnsafePolicy = parse_quote! {};
         assert_eq!(us, UnsafePolicy::AllFunctionsUnsafe)
     }
+
+    #[test]
+    fn test_enum_representations() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            bitflags!("ns::Options")
+            newtype_enum!("Protocol")
+        };
+        assert!(config.is_bitflags_enum("ns::Options"));
+        assert!(config.is_newtype_enum("ns::Options"));
+        assert!(!config.is_bitflags_enum("Protocol"));
+        assert!(config.is_newtype_enum("Protocol"));
+        assert!(!config.is_newtype_enum("Options"));
+    }
+
+    #[test]
+    fn test_storage() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::A")
+            storage!("ns::A")
+        };
+        assert!(config.is_storage_type("ns::A"));
+        assert!(!config.is_storage_type("A"));
+    }
+
+    #[test]
+    fn test_send_sync() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::Service")
+            generate!("ns::Counter")
+            send!(unsafe "ns::Service")
+            sync!(unsafe "ns::Service")
+            send!(unsafe "ns::Counter")
+        };
+        assert!(config.is_send("ns::Service"));
+        assert!(config.is_sync("ns::Service"));
+        assert!(config.is_send("ns::Counter"));
+        assert!(!config.is_sync("ns::Counter"));
+        assert!(!config.is_send("Service"));
+    }
+
+    #[test]
+    fn test_instantiate() {
+        let config: IncludeCppConfig = parse_quote! {
+            instantiate!("ns::Matrix<float, 4>", "Matrix4f")
+        };
+        let instantiation = config.instantiation_named("Matrix4f").unwrap();
+        assert_eq!(instantiation.cpp_type, "ns::Matrix<float, 4>");
+        assert_eq!(
+            instantiation.typedef(),
+            "typedef ns::Matrix<float, 4> Matrix4f;"
+        );
+        assert!(config.is_on_allowlist("Matrix4f"));
+    }
+
+    #[test]
+    fn test_instantiate_namespaced() {
+        let config: IncludeCppConfig = parse_quote! {
+            instantiate!("ns::Matrix<float, 4>", "ns::detail::Matrix4f")
+        };
+        let instantiation = config.instantiation_named("ns::detail::Matrix4f").unwrap();
+        assert_eq!(
+            instantiation.typedef(),
+            "namespace ns { namespace detail { typedef ns::Matrix<float, 4> Matrix4f; } }"
+        );
+    }
+
+    #[test]
+    fn test_instantiate_with_generate_all() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            instantiate!("Point<float>", "PointF")
+        };
+        assert!(config.bindgen_allowlist().is_none());
+        assert!(config.must_generate_list().any(|item| item == "PointF"));
+        let config: IncludeCppConfig = parse_quote! {
+            instantiate!("Point<float>", "PointF")
+            generate_all!()
+        };
+        assert!(config.bindgen_allowlist().is_none());
+    }
+
+    #[test]
+    fn test_newtype_all_enums() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            newtype_all_enums!()
+        };
+        assert!(config.is_newtype_enum("Protocol"));
+        assert!(!config.is_bitflags_enum("Protocol"));
+    }
+
+    #[test]
+    fn test_macro_fn() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("foo")
+            macro_fn!("MAX", "int(int, std::pair<int, int>)")
+            macro_fn!("GET_PI", "double()")
+            macro_fn!("PI", "double")
+            macro_fn!("DEFAULT_COLOR")
+        };
+        let expected = [
+            MacroFn {
+                name: "MAX".into(),
+                return_type: Some("int".into()),
+                param_types: Some(vec!["int".into(), "std::pair<int, int>".into()]),
+            },
+            MacroFn {
+                name: "GET_PI".into(),
+                return_type: Some("double".into()),
+                param_types: Some(Vec::new()),
+            },
+            MacroFn {
+                name: "PI".into(),
+                return_type: Some("double".into()),
+                param_types: None,
+            },
+            MacroFn {
+                name: "DEFAULT_COLOR".into(),
+                return_type: None,
+                param_types: None,
+            },
+        ];
+        assert_eq!(config.get_macro_fns(), &expected);
+        assert!(config.is_on_allowlist("MAX"));
+        assert!(config.is_on_allowlist("autocxx_macro_MAX"));
+        assert_eq!(
+            config.macro_fn_for_wrapper("autocxx_macro_PI"),
+            Some(&expected[2])
+        );
+    }
+
+    #[test]
+    fn test_unknown_directive() {
+        let config: Result<IncludeCppConfig, _> = syn::parse2(quote::quote! {
+            generate_everything!()
+        });
+        let message = config.err().unwrap().to_string();
+        assert!(message.contains("generate_pod"));
+        assert!(message.contains("snake_case_methods"));
+    }
+
+    #[test]
+    fn test_macro_fn_only() {
+        let config: IncludeCppConfig = parse_quote! {
+            macro_fn!("PI", "double")
+        };
+        assert!(matches!(config.allowlist, Allowlist::Unspecified));
+        assert!(config
+            .bindgen_allowlist()
+            .unwrap()
+            .any(|item| item == "autocxx_macro_PI"));
+    }
+
+    #[test]
+    fn test_rename() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::foo")
+            overloads_by_signature!()
+            rename!("ns::foo(int)", "foo_from_int")
+            rename!("ns::foo(const ns::Bar &, std::vector<int>)", "foo_from_bar")
+            rename!("ns::Type::method", "do_it")
+        };
+        assert!(config.overloads_by_signature());
+        assert_eq!(
+            config.rename_for("ns::foo", &["int".into()]),
+            Some("foo_from_int")
+        );
+        assert_eq!(config.rename_for("ns::foo", &["double".into()]), None);
+        assert_eq!(
+            config.rename_for("ns::foo", &["const Bar&".into(), "std::vector<int>".into()]),
+            Some("foo_from_bar")
+        );
+        assert_eq!(
+            config.rename_for("ns::Type::method", &["int".into()]),
+            Some("do_it")
+        );
+        assert_eq!(config.rename_for("ns::method", &[]), None);
+        assert!(!config.snake_case_methods());
+    }
+
+    #[test]
+    fn test_rename_type() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::widget_t")
+            snake_case_methods!()
+            rename!("ns::widget_t", "Widget")
+            rename!("ns::make_widget()", "new_widget")
+        };
+        assert!(config.snake_case_methods());
+        assert_eq!(config.rename_for_type("ns::widget_t"), Some("Widget"));
+        assert_eq!(config.rename_for_type("ns::make_widget"), None);
+        assert_eq!(config.rename_for_type("widget_t"), None);
+    }
+
+    #[test]
+    fn test_safety_overrides() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            safe!(unsafe "ns")
+            unsafe_fn!("ns::raw_memcpy")
+            unsafe_fn!("ns::Buffer")
+            safe!(unsafe "ns::Buffer::size")
+        };
+        assert_eq!(config.safety_override_for("ns::Widget::draw"), Some(true));
+        assert_eq!(config.safety_override_for("ns::raw_memcpy"), Some(false));
+        assert_eq!(config.safety_override_for("ns::Buffer::data"), Some(false));
+        assert_eq!(config.safety_override_for("ns::Buffer::size"), Some(true));
+        assert_eq!(config.safety_override_for("other::foo"), None);
+        assert_eq!(config.safety_override_for("foo"), None);
+    }
+
+    #[test]
+    fn test_safe_requires_unsafe() {
+        let config: Result<IncludeCppConfig, _> = syn::parse2(quote::quote! {
+            safe!("ns::add")
+        });
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_send_sync_require_unsafe() {
+        let config: Result<IncludeCppConfig, _> = syn::parse2(quote::quote! {
+            send!("ns::Service")
+        });
+        assert!(config.is_err());
+        let config: Result<IncludeCppConfig, _> = syn::parse2(quote::quote! {
+            sync!("ns::Service")
+        });
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_borrows_from() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::lookup")
+            borrows_from!("ns::lookup", "table")
+        };
+        assert_eq!(config.borrows_from("ns::lookup"), Some("table"));
+        assert_eq!(config.borrows_from("lookup"), None);
+    }
+
+    #[test]
+    fn test_pointer_params() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::read")
+            pointer_param!("ns::read", "buf", slice("len"))
+            pointer_param!("ns::read", "hint", nullable)
+            pointer_param!("ns::read", "count", out)
+        };
+        assert_eq!(
+            config.pointer_policy("ns::read", "buf"),
+            Some(&PointerPolicy::Slice {
+                length_param: "len".into()
+            })
+        );
+        assert_eq!(
+            config.pointer_policy("ns::read", "hint"),
+            Some(&PointerPolicy::Nullable)
+        );
+        assert_eq!(
+            config.pointer_policy("ns::read", "count"),
+            Some(&PointerPolicy::Out)
+        );
+        assert_eq!(config.pointer_policy("ns::read", "len"), None);
+        let config: Result<IncludeCppConfig, _> = syn::parse2(quote::quote! {
+            pointer_param!("ns::read", "buf", maybe)
+        });
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_out_params() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::parse")
+            out_param!("ns::parse", "result")
+            out_params_as_result!("ns::parse")
+        };
+        assert!(config.is_out_param("ns::parse", "result"));
+        assert!(!config.is_out_param("ns::parse", "input"));
+        assert!(!config.is_out_param("parse", "result"));
+        assert!(config.out_params_as_result("ns::parse"));
+        assert!(!config.detect_out_params());
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::parse")
+            detect_out_params!()
+        };
+        assert!(config.detect_out_params());
+        assert!(!config.out_params_as_result("ns::parse"));
+    }
+
+    #[test]
+    fn test_macro_fn_bad_signature() {
+        let config: Result<IncludeCppConfig, _> = syn::parse2(quote::quote! {
+            macro_fn!("MAX", "(int)")
+        });
+        assert!(config.is_err());
+    }
 }
@@ -20,7 +20,7 @@ use std::{
     hash::{Hash, Hasher},
 };
 
-pub use config::{IncludeCppConfig, UnsafePolicy};
+pub use config::{IncludeCppConfig, Instantiation, MacroFn, PointerPolicy, Rename, UnsafePolicy};
 use file_locations::FileLocationStrategy;
 use proc_macro2::TokenStream as TokenStream2;
 use syn::Result as ParseResult;
@@ -23,6 +23,7 @@
 
 #[allow(unused_imports)] // doc cross-reference only
 use autocxx_engine::IncludeCppEngine;
+use std::pin::Pin;
 
 #[cfg_attr(doc, aquamarine::aquamarine)]
 /// Include some C++ headers in your Rust project.
@@ -123,6 +124,14 @@ use autocxx_engine::IncludeCppEngine;
 /// is not declared as POD-safe, then we'll generate wrapper functions to move
 /// that type into and out of [UniquePtr][autocxx_engine::cxx::UniquePtr]s.
 ///
+/// The layout of each POD type is checked at compile time on both sides.
+/// The generated C++ contains a `static_assert` of its size, alignment
+/// and the offset of each public field, and the generated Rust contains
+/// an equivalent `const` assertion of its size and alignment. So, if the
+/// Rust and C++ ideas of the type ever differ, your build will fail rather
+/// than memory being corrupted at runtime. The Rust field offsets are
+/// checked by a generated `#[test]`, as bindgen does.
+///
 /// # Generated code
 ///
 /// You will find that this macro expands to the equivalent of:
@@ -165,12 +174,65 @@ use autocxx_engine::IncludeCppEngine;
 /// gain this if they have an explicit C++ constructor; this is a limitation
 /// which should be resolved in future.
 /// This will (of course) return a `UniquePtr` containing that type.
+/// They also gain an equivalent `make_shared` associated function which
+/// returns a `SharedPtr`. Use that for any type which derives from
+/// `std::enable_shared_from_this`, since `shared_from_this` only works
+/// for objects which are already owned by a `std::shared_ptr`.
+///
+/// ## Holding opaque types within Rust structs
+///
+/// Opaque (non-POD) types have the size and alignment of the C++ type,
+/// as calculated by bindgen, and gain associated constants `SIZE` and
+/// `ALIGN` giving that layout. (Types whose layout bindgen doesn't know,
+/// such as some template instantiations, are zero-sized instead.)
+/// Normally such types can only be held in Rust
+/// behind a pointer such as `UniquePtr`. If a type is named in a
+/// [storage] directive, autocxx additionally generates a type called
+/// (for instance) `ffi::AStorage`, which can be a field of a Rust struct
+/// and holds an `A` inline. Since C++ objects can't generally be moved
+/// in memory, the storage must be pinned before an object is constructed
+/// within it, using the `emplace` equivalent of each `make_unique`
+/// function. The object is destroyed when the storage is dropped.
+///
+/// ## Threads
+///
+/// Opaque C++ types are neither `Send` nor `Sync`, because autocxx has no
+/// idea whether a C++ class is thread-safe. If you know that it is, you can
+/// say so using [send] and [sync], which make the type (and `UniquePtr`s
+/// of it) `Send` or `Sync` respectively.
+///
+/// ## Reference counting
+///
+/// `std::shared_ptr` and `std::weak_ptr` are mapped to cxx's `SharedPtr`
+/// and `WeakPtr` respectively.
+///
+/// ## Bitfields
+///
+/// Plain-old-data structs may contain bitfields. Since Rust has no
+/// equivalent, each bitfield `x` is accessed using generated `get_x` and
+/// `set_x` methods.
+///
+/// ## Unions
+///
+/// A union whose members are all plain-old-data may be requested using
+/// `generate_pod!`, and becomes a Rust `union`. As with any Rust union,
+/// reading its fields requires `unsafe`. Members other than primitives
+/// are wrapped in `std::mem::ManuallyDrop`, as Rust requires. Such unions
+/// may also be fields of other POD structs. Unions with any non-trivial
+/// members are always opaque.
 ///
 /// ## Preprocessor symbols
 ///
 /// `#define` and other preprocessor symbols will appear as constants.
-/// At present there is no way to do compile-time disablement of code
-/// (equivalent of `#ifdef`).
+/// As well as literals, constant expressions of floating point, `char`
+/// or enum type are evaluated, for example `#define SCALE (2 * M_PI)` or
+/// `#define DEFAULT_COLOR Color::Green`. Floating point values become
+/// `f64` and `char`s become `u8`. An enum-valued constant is generated only
+/// if the enum is too. At present there is no way to do compile-time
+/// disablement of code (equivalent of `#ifdef`).
+///
+/// More complex macros, including function-like macros, can be made
+/// available as functions using [macro_fn].
 ///
 /// ## Integer types
 ///
@@ -195,6 +257,59 @@ use autocxx_engine::IncludeCppEngine;
 /// assert_eq!(std::str::from_utf8(&ffi::BOB).unwrap().trim_end_matches(char::from(0)), "Hello");
 /// ```
 ///
+/// ## Functions returning references
+///
+/// Rust needs to know what a returned reference borrows from. If a
+/// function takes exactly one reference, that's easy. Otherwise, methods
+/// are assumed to return references into their object, so
+/// `const Value& get(const Key& key) const` becomes
+/// `fn get<'a>(self: &'a Map, key: &Key) -> &'a Value`. For free functions,
+/// or methods which return something else, say which parameter is borrowed
+/// using [borrows_from]. Functions for which we can't tell are not
+/// generated.
+///
+/// ## Raw pointer parameters
+///
+/// Functions taking raw pointers are `unsafe`, because we don't know what
+/// the pointer means. [pointer_param] says, so that the function can be
+/// safe. `const T*` and `T*` parameters may be:
+/// * `nullable`, taking an `Option<&T>` or `Option<Pin<&mut T>>`,
+/// * `slice("len")`, taking a `&[T]` or `&mut [T]` and passing its length
+///   as the named parameter, which is no longer a parameter of the
+///   Rust function, or
+/// * `out`, for a `T*` through which C++ returns a value, as described
+///   below.
+///
+/// Slices must be of POD or primitive types.
+///
+/// ## Out parameters
+///
+/// A `T*` or `T&` through which C++ returns a value can be named using
+/// [out_param]. The Rust function then returns the value instead, after any
+/// actual return value, in a tuple if necessary. So
+/// `bool divide(uint32_t a, uint32_t b, uint32_t& quotient)` with
+/// `out_param!("divide", "quotient")` becomes
+/// `fn divide(a: u32, b: u32) -> (bool, u32)`. POD and primitive values are
+/// returned directly, and are value-initialized before the call, so they're
+/// zero if C++ doesn't write to them; other types are allocated by C++,
+/// which must be able to default-construct them, and returned in a
+/// `UniquePtr`.
+///
+/// If the function returns a status, [out_params_as_result] instead makes
+/// it return a `Result` of the out parameters, or the status if that's
+/// `false` or non-zero. [detect_out_params] treats all non-const
+/// reference parameters named `out_*` as out parameters.
+///
+/// ## Global variables and static members
+///
+/// Global variables, and static members of classes, are made available
+/// as functions of the same name which return a reference to the variable.
+/// Constant variables (including those of class type) give a `&'static T`.
+/// Mutable variables give a `Pin<&'static mut T>`, and their accessors are
+/// `unsafe` because there's nothing to stop other code (in either language)
+/// simultaneously mutating the same variable. Static members are associated
+/// functions of their class, e.g. `ffi::MyClass::MY_STATIC()`.
+///
 /// ## Namespaces
 ///
 /// The C++ namespace structure is reflected in mods within the generated
@@ -207,6 +322,44 @@ use autocxx_engine::IncludeCppEngine;
 /// C++ allows function overloads; Rust doesn't. `autocxx` follows the lead
 /// of `bindgen` here and generating overloads as `func`, `func1`, `func2` etc.
 ///
+/// Those numbers depend upon the order in which overloads are found, so
+/// adding an overload to a header may rename existing bindings. To avoid
+/// that, [overloads_by_signature] names overloads after their parameter
+/// types instead, so `func(int, double)` becomes `func_int_double`.
+/// Functions which aren't overloaded keep their plain names. Alternatively,
+/// [rename] gives an explicit Rust name to a function or to one particular
+/// overload, such as `rename!("ns::func(int)", "func_from_int")`.
+///
+/// Where a method has a const and a non-const overload taking the same
+/// parameters, such as `const T& get() const` and `T& get()`, they're
+/// instead named after the Rust convention: `get(&self) -> &T` and
+/// `get_mut(self: Pin<&mut Self>) -> Pin<&mut T>`. As `Pin` has its own
+/// `get_mut`, call the latter as `MyType::get_mut(obj.pin_mut())`. A name
+/// given by [rename] is used exactly as given, without the `_mut`.
+///
+/// ## Default arguments
+///
+/// Rust has no default arguments, so a C++ function
+/// `void draw(Shape s, Color c = Red, int width = 1)` is available in full as
+/// `draw(s, c, width)`, and also as `draw_with_c(s, c)` (named after the
+/// last parameter it takes) and `draw_with_defaults(s)`. The same applies to
+/// methods and constructors (e.g. `make_unique_with_defaults`). In each
+/// case, C++ fills in the values of the omitted parameters.
+///
+/// ## Documentation
+///
+/// Doc comments on C++ types, fields, enumerators and functions are carried
+/// across to the Rust bindings. Doxygen commands are converted to Markdown:
+/// `@param` and `@return` become "Parameters" and "Returns" sections, and
+/// `\brief`, `@note`, `@code` and `\c` are rendered as you'd expect. Each
+/// function's documentation also gives its original C++ signature.
+///
+/// ## Function attributes
+///
+/// A `[[nodiscard]]` C++ function or method becomes a `#[must_use]` Rust
+/// function, and a `[[deprecated]]` one becomes `#[deprecated]`, carrying
+/// across any message as its `note`.
+///
 /// ## C++ classes - why do I get warnings?
 ///
 /// autocxx is not currently able to distinguish a C++ struct from a C++ class.
@@ -214,6 +367,22 @@ use autocxx_engine::IncludeCppEngine;
 /// from most compilers, but could cause actual binary mismatches
 /// on some ABIs. This is a temporary known limitation.
 ///
+/// ## Inheritance
+///
+/// Methods of public base classes may be called on derived types, unless
+/// hidden by a method of the same name in the derived class, just as in C++.
+/// A reference to a derived type `B` can be converted to a reference to any
+/// base class `A` using `B::as_A` (which is also available as
+/// `AsRef<A>`), or `B::as_A_mut` which converts `Pin<&mut B>` to
+/// `Pin<&mut A>`. Bases which are ambiguous in C++ are skipped. At present
+/// autocxx doesn't know about base classes which have no data members, or
+/// virtual base classes, and it assumes that all base classes are public.
+///
+/// If `A` has virtual functions, you can go the other way too:
+/// `a.downcast_ref::<B>()` returns `Some(&B)` if `a` is really part of a `B`,
+/// and `None` otherwise, using `dynamic_cast`. `downcast_mut` is the
+/// equivalent for `Pin<&mut A>`. See [`DowncastFrom`].
+///
 /// ## Forward declarations
 ///
 /// A type which is incomplete in the C++ headers (i.e. represented only by a forward
@@ -234,6 +403,14 @@ use autocxx_engine::IncludeCppEngine;
 /// this will be improved in future. At present such types have a name
 /// `AutocxxConcrete{n}` but this may change in future.
 ///
+/// To do more with a particular instantiation, name it using [instantiate],
+/// for example `instantiate!("ns::Point<float>", "PointF")`. This generates
+/// a C++ typedef, and a Rust type of the same name which is used wherever
+/// that instantiation is encountered. Its fields are available, and it may
+/// be requested as POD using `generate_pod!("PointF")`. Its public methods
+/// are available too, except for operators, methods which are themselves
+/// templates, and constructors.
+///
 /// # Internals
 ///
 /// For documentation on how this all actually _works_, see
@@ -364,11 +541,252 @@ macro_rules! name {
 /// that you've analyzed all possible ways that the code
 /// can be used and you are guaranteeing to the compiler that
 /// no badness can occur. Good luck.
+///
+/// This policy can be overridden for particular functions, types
+/// or namespaces using [safe] and [unsafe_fn].
 #[macro_export]
 macro_rules! safety {
     ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
 }
 
+/// Declares that the functions of a given C++ item are safe,
+/// irrespective of the overall [safety] policy. The item may be a
+/// function (e.g. `safe!(unsafe "ns::add")`), a type, in which case this
+/// applies to all its methods and constructors (`safe!(unsafe "ns::Widget")`),
+/// or a namespace (`safe!(unsafe "ns")`). As with an `unsafe` block, the
+/// `unsafe` keyword marks that *you* are vouching for the safety of that
+/// C++ code. Where several [safe] and [unsafe_fn]
+/// directives apply, the most specific wins. Functions which take raw
+/// pointers, and mutable globals, remain `unsafe` regardless, unless
+/// those pointers are described using [pointer_param].
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! safe {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Declares that the functions of a given C++ item are `unsafe`,
+/// irrespective of the overall [safety] policy, for example
+/// `unsafe_fn!("ns::raw_memcpy")`. As with [safe], the item may be a
+/// function, a type or a namespace.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! unsafe_fn {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Represent a C++ enum as a newtype wrapper around its underlying
+/// integer, with an associated constant for each enumerator, instead of
+/// as a Rust `enum`. The newtype additionally implements the bitwise
+/// operators `|`, `&`, `|=` and `&=`, so this is suitable for enums
+/// which are used as sets of flags. Without this, receiving a combination
+/// of flags from C++ would be undefined behavior, since the value would
+/// not correspond to any variant of the Rust `enum`.
+/// For example, `bitflags!("ns::Options")`.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+/// See also [newtype_enum].
+#[macro_export]
+macro_rules! bitflags {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Represent a C++ enum as a newtype wrapper around its underlying
+/// integer, with an associated constant for each enumerator, instead of
+/// as a Rust `enum`. This means any value returned from C++ can safely
+/// be represented in Rust, even if it doesn't match any of the known
+/// enumerators.
+/// The newtype can be converted to and from its underlying integer
+/// using `From` and `TryFrom` respectively; `TryFrom` fails for any
+/// value which doesn't correspond to a known enumerator.
+/// For example, `newtype_enum!("ns::Protocol")`.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+/// See also [bitflags] and [newtype_all_enums].
+#[macro_export]
+macro_rules! newtype_enum {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Represent all C++ enums as newtype wrappers, as if each had been
+/// named in a [newtype_enum] directive. This is a good choice if your
+/// C++ enums evolve independently of the Rust code which uses them.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! newtype_all_enums {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Generate a storage type for an opaque C++ type, so that it can be held
+/// inline within Rust structs rather than behind a `UniquePtr`.
+/// For example, `storage!("A")` generates `ffi::AStorage`.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! storage {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Asserts that a C++ type may be sent to another thread, such that
+/// autocxx generates `unsafe impl Send` for it, for example
+/// `send!(unsafe "ns::Service")`. This is a safety assertion just like
+/// an `unsafe impl` in Rust, hence the `unsafe` keyword: *you* are
+/// promising that the C++ class doesn't depend upon which thread uses or
+/// destroys it, for instance by using thread-local storage.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! send {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Asserts that a C++ type may be used from several threads at once,
+/// such that autocxx generates `unsafe impl Sync` for it, for example
+/// `sync!(unsafe "ns::Service")`. This is a safety assertion just like
+/// an `unsafe impl` in Rust, hence the `unsafe` keyword: *you* are
+/// promising that all of its `const` methods are thread-safe.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! sync {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Give a name to a particular instantiation of a C++ template, such that
+/// it becomes a Rust type with access to its fields. For example,
+/// `instantiate!("ns::Matrix<float, 4>", "Matrix4f")` makes `ffi::Matrix4f`.
+/// The name may be within a namespace, such as `"ns::Matrix4f"`, and
+/// this can be combined with [generate_all].
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! instantiate {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Choose the Rust name of a C++ type, function or method, optionally
+/// picking out one overload of a function by its parameter types. For
+/// example, `rename!("ns::widget_t", "Widget")`,
+/// `rename!("ns::foo(int)", "foo_from_int")` or
+/// `rename!("ns::Widget::draw", "render")`. A renamed constructor
+/// `rename!("ns::Widget::Widget(int)", "from_int")` becomes
+/// `make_unique_from_int`. The C++ names are still used in
+/// [generate] and other directives.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! rename {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Name overloaded functions after their parameter types (e.g.
+/// `foo_int_double`) rather than numbering them in the order they're found.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! overloads_by_signature {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Convert C++ `CamelCase` method names to Rust `snake_case`, so that
+/// `GetValue` becomes `get_value`. Constructors are unaffected, and
+/// [rename] takes precedence.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! snake_case_methods {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Specifies which parameter the reference returned by a function borrows
+/// from, for functions which take several references, for example
+/// `borrows_from!("ns::lookup", "table")`. The returned reference then
+/// has the same lifetime as that parameter.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! borrows_from {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Specifies how a raw pointer parameter of a function should be presented
+/// in Rust, allowing the function to be safe: for example
+/// `pointer_param!("ns::read", "buf", slice("len"))`. The policy may be
+/// `nullable`, `slice("length_param")` or `out`. See the section on raw
+/// pointer parameters in [include_cpp].
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! pointer_param {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Specifies that a non-const pointer or reference parameter of a function
+/// is one through which C++ returns a value, for example
+/// `out_param!("ns::parse", "result")`. The Rust function returns it
+/// instead. See the section on out parameters in [include_cpp].
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! out_param {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Treat all non-const reference parameters named `out_*` as though they
+/// were named by [out_param].
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! detect_out_params {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// For a function with out parameters which returns a `bool` or integer
+/// status, return `Ok` with the out parameters if the status is `true` or
+/// zero, or else `Err` with the status: for example
+/// `out_params_as_result!("ns::parse")`.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! out_params_as_result {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Make a preprocessor macro available as a function, by generating a C++
+/// function which evaluates it. Provide the C++ signature of the function,
+/// for example `macro_fn!("MAX", "int(int, int)")`, which results in
+/// `ffi::MAX(a, b)`. For an object-like macro, provide just the type,
+/// for example `macro_fn!("SCALE", "double")`, or nothing at all, in which
+/// case the type is deduced from the expression: `macro_fn!("DEFAULT_COLOR")`.
+/// Either way, the resulting function takes no arguments.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! macro_fn {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! usage {
@@ -437,3 +855,15 @@ pub struct BindingGenerationFailure {
     _unallocatable: [*const u8; 0],
     _pinned: core::marker::PhantomData<core::marker::PhantomPinned>,
 }
+
+/// Implemented by generated code for each polymorphic C++ type (that is,
+/// one with virtual functions) for each of its base classes, so that a
+/// reference to the base may be converted to a reference to this type if
+/// that's what the object really is. You will normally use this via the
+/// `downcast_ref` and `downcast_mut` methods generated on the base class.
+pub trait DowncastFrom<Base>: Sized {
+    /// Uses `dynamic_cast` to convert a reference to the base class.
+    fn downcast_from(base: &Base) -> Option<&Self>;
+    /// Uses `dynamic_cast` to convert a mutable reference to the base class.
+    fn downcast_from_mut(base: Pin<&mut Base>) -> Option<Pin<&mut Self>>;
+}